@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use plist_rs::Plist;
+use plist_rs::bplist;
+use plist_rs::parse_options::ParseOptions;
+
+// The validator must never panic or hang, and whatever it accepts must be safe to hand to
+// libplist.
+fuzz_target!(|data: &[u8]| {
+    let options = ParseOptions::default();
+    if bplist::validate(data, &options).is_ok() {
+        let _ = Plist::from_bin_with(data, &options);
+    }
+});
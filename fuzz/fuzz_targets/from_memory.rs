@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use plist_rs::Plist;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Plist::from_memory(data);
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use plist_rs::Plist;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(xml) = std::str::from_utf8(data) {
+        let _ = Plist::from_xml(xml.to_owned());
+    }
+});
@@ -0,0 +1,69 @@
+#![no_main]
+
+use std::collections::HashMap;
+use libfuzzer_sys::fuzz_target;
+use plist_rs::Plist;
+use plist_rs::dict::DictSetter;
+use plist_rs::format::PlistFormat;
+use plist_rs::parse_options::ParseOptions;
+use plist_rs::plist_node_type::PlistNodeType;
+
+/// `plist` as `format` reads it back, or `None` if it holds a value `format` can't store
+/// exactly. The rules are those of `scalar()` in `src/format.rs`: no format keeps NaN or the
+/// infinities, XML drops the fraction of dates, and JSON reads integral reals back as integers
+/// and can't hold integers above `i64::MAX`.
+fn expected(plist: Plist, format: PlistFormat) -> Option<Plist> {
+    let expected = match plist.node_type() {
+        PlistNodeType::Dictionary => {
+            let dict = Plist::from(HashMap::new()).dict().unwrap();
+            for (key, value) in plist.dict().unwrap().iter().bytes() {
+                dict.set(key.as_slice(), expected(value, format)?);
+            }
+            dict.into_plist()
+        }
+        PlistNodeType::Array => {
+            let items = plist.array().unwrap().into_iter()
+                .map(|item| expected(item, format))
+                .collect::<Option<Vec<Plist>>>()?;
+            Plist::from(items)
+        }
+        PlistNodeType::Real => {
+            let real = plist.as_real()?;
+            if !real.is_finite() || (format == PlistFormat::Json && real.fract() == 0.0) {
+                return None
+            }
+            plist.copy()
+        }
+        PlistNodeType::UInt if format == PlistFormat::Json => {
+            plist.as_int()?;
+            plist.copy()
+        }
+        PlistNodeType::Date if format == PlistFormat::Xml => Plist::from_date(plist.as_date()?.0, 0),
+        _ => plist.copy()
+    };
+
+    Some(expected)
+}
+
+// Whatever parses must come back from every format as that format stores it. JSON can't hold
+// data, dates or UIDs, so it only has to round-trip when serializing succeeds.
+fuzz_target!(|data: &[u8]| {
+    let options = ParseOptions::default();
+    let plist = match Plist::from_memory_with(data, &options) {
+        Ok(plist) => plist,
+        Err(_) => return
+    };
+
+    for format in [PlistFormat::Xml, PlistFormat::Binary, PlistFormat::Json] {
+        let serialized = match plist.to_format(format, false) {
+            Ok(serialized) => serialized,
+            Err(_) if format == PlistFormat::Json => continue,
+            Err(error) => panic!("{:?} serialization failed: {}", format, error)
+        };
+        let parsed = Plist::from_memory_with(&serialized, &ParseOptions::unlimited())
+            .unwrap_or_else(|error| panic!("{:?} output doesn't parse: {}", format, error));
+        if let Some(expected) = expected(plist.copy(), format) {
+            assert_eq!(parsed, expected, "{:?} round trip changed the plist", format);
+        }
+    }
+});
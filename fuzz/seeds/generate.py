@@ -0,0 +1,152 @@
+#!/usr/bin/env python3
+"""Writes the fuzzing seed corpora in this directory.
+
+The documents are modeled on plists met in practice (an app's Info.plist, entitlements, a
+launchd job, lockdownd and usbmuxd messages, an NSKeyedArchiver archive) plus edge cases.
+Binary and XML seeds are serialized by Python's plistlib, JSON seeds by the json module, and
+the OpenStep seeds are written by hand, so none of them comes from Apple's own encoder.
+
+Run it from anywhere; it replaces the xml, bin, json and openstep directories next to it and
+leaves the real-world files in samples alone.
+"""
+
+import datetime
+import json
+import pathlib
+import plistlib
+import shutil
+
+ROOT = pathlib.Path(__file__).resolve().parent
+
+DOCUMENTS = {
+    "info_plist": {
+        "CFBundleDevelopmentRegion": "en",
+        "CFBundleExecutable": "Example",
+        "CFBundleIdentifier": "com.example.app",
+        "CFBundleInfoDictionaryVersion": "6.0",
+        "CFBundleName": "Example",
+        "CFBundlePackageType": "APPL",
+        "CFBundleShortVersionString": "2.4.1",
+        "CFBundleSupportedPlatforms": ["iPhoneOS"],
+        "CFBundleURLTypes": [{"CFBundleURLName": "com.example.app", "CFBundleURLSchemes": ["example"]}],
+        "CFBundleVersion": "241",
+        "LSRequiresIPhoneOS": True,
+        "MinimumOSVersion": "15.0",
+        "UIDeviceFamily": [1, 2],
+        "UIRequiredDeviceCapabilities": ["arm64"],
+        "NSCameraUsageDescription": "Scans QR codes.",
+    },
+    "entitlements": {
+        "application-identifier": "ABCDE12345.com.example.app",
+        "aps-environment": "production",
+        "com.apple.developer.team-identifier": "ABCDE12345",
+        "com.apple.security.application-groups": ["group.com.example"],
+        "get-task-allow": False,
+        "keychain-access-groups": ["ABCDE12345.*"],
+    },
+    "launchd": {
+        "Label": "com.example.agent",
+        "ProgramArguments": ["/usr/local/bin/agent", "--verbose"],
+        "RunAtLoad": True,
+        "KeepAlive": {"SuccessfulExit": False},
+        "StartCalendarInterval": [{"Hour": 3, "Minute": 15}],
+        "EnvironmentVariables": {"PATH": "/usr/bin:/bin"},
+        "StandardOutPath": "/tmp/agent.log",
+        "ThrottleInterval": 30,
+    },
+    "lockdown_request": {"Label": "plist-rs", "Request": "GetValue", "Key": "ProductVersion"},
+    "lockdown_reply": {
+        "Request": "StartSession",
+        "SessionID": "8F4C0D52-6B1E-4C8A-9A7F-0E7D5B2C3A91",
+        "EnableSessionSSL": True,
+    },
+    "usbmux_attached": {
+        "MessageType": "Attached",
+        "DeviceID": 3,
+        "Properties": {
+            "ConnectionSpeed": 480000000,
+            "ConnectionType": "USB",
+            "DeviceID": 3,
+            "LocationID": 336592896,
+            "ProductID": 4776,
+            "SerialNumber": "00008030-001A35E80C41802E",
+        },
+    },
+    "keyed_archive": {
+        "$archiver": "NSKeyedArchiver",
+        "$version": 100000,
+        "$top": {"root": plistlib.UID(1)},
+        "$objects": ["$null", {"NS.string": plistlib.UID(2), "$class": plistlib.UID(3)}, "hello",
+                     {"$classname": "NSString", "$classes": ["NSString", "NSObject"]}],
+    },
+    "pair_record": {
+        "HostID": "5AA9DE3D-1D1E-4E8D-9B5C-2C7E7B0D6F21",
+        "SystemBUID": "1C4BFB26-6F5E-4E5B-9C9B-2AF2A1E3A5C9",
+        "HostCertificate": b"-----BEGIN CERTIFICATE-----\nTUlJQg==\n-----END CERTIFICATE-----\n",
+        "EscrowBag": bytes(range(32)),
+        "WiFiMACAddress": "a4:83:e7:00:11:22",
+    },
+    "edge_cases": {
+        "empty dict": {},
+        "empty array": [],
+        "empty string": "",
+        "empty data": b"",
+        "unicode é中\U0001f600": "café — 日本語 \U0001f4f1",
+        "big": 2**63 - 1,
+        "unsigned": 2**64 - 1,
+        "negative": -2**63,
+        "real": 3.141592653589793,
+        "integral real": 1.0,
+        "tiny": 5e-324,
+        "before 2001": datetime.datetime(1970, 1, 1, 0, 0, 1),
+        "after 2001": datetime.datetime(2024, 2, 29, 12, 30, 45),
+        "nested": [[[[{"deep": [True, False]}]]]],
+        "shared": ["repeated", "repeated", "repeated"],
+    },
+}
+
+OPENSTEP = {
+    "simple": '{ name = "Example"; version = 3; list = (a, "b c", <0fbd77>); }',
+    "pbxproj": '// !$*UTF8*$!\n{\n\tarchiveVersion = 1;\n\tobjects = {\n\t\t13B07F961A680F5B00A75B9A /* App */ = {\n'
+               '\t\t\tisa = PBXNativeTarget;\n\t\t\tname = App;\n\t\t};\n\t};\n\trootObject = 83CBB9F71A601CBA00E9B192;\n}\n',
+    "strings": '"greeting" = "Hello, %@!";\n/* comment */\n"farewell" = "Goodbye";\n',
+    "empty": "{}",
+}
+
+
+def jsonable(value):
+    """JSON has no data, dates or UIDs; those documents are left out of the JSON seeds."""
+    if isinstance(value, dict):
+        return all(jsonable(item) for item in value.values())
+    if isinstance(value, list):
+        return all(jsonable(item) for item in value)
+    return isinstance(value, (str, bool, int, float)) and not (isinstance(value, int) and value >= 2**63)
+
+
+def has_uid(value):
+    """plistlib only writes UIDs in binary plists."""
+    if isinstance(value, dict):
+        return any(has_uid(item) for item in value.values())
+    if isinstance(value, list):
+        return any(has_uid(item) for item in value)
+    return isinstance(value, plistlib.UID)
+
+
+def main():
+    for name in ("xml", "bin", "json", "openstep"):
+        shutil.rmtree(ROOT / name, ignore_errors=True)
+        (ROOT / name).mkdir()
+
+    for name, document in DOCUMENTS.items():
+        if not has_uid(document):
+            (ROOT / "xml" / f"{name}.plist").write_bytes(plistlib.dumps(document, fmt=plistlib.FMT_XML))
+        (ROOT / "bin" / f"{name}.plist").write_bytes(plistlib.dumps(document, fmt=plistlib.FMT_BINARY))
+        if jsonable(document):
+            (ROOT / "json" / f"{name}.json").write_text(json.dumps(document, indent=2, ensure_ascii=False) + "\n")
+
+    for name, text in OPENSTEP.items():
+        (ROOT / "openstep" / f"{name}.plist").write_text(text)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,227 @@
+/* libplist 2.2, written to match include/plist/plist.h of the 2.2.0 release */
+/* Regenerate against an installed 2.2 with the `bindgen` feature */
+
+pub type plist_t = *mut ::std::os::raw::c_void;
+pub type plist_dict_iter = *mut ::std::os::raw::c_void;
+pub type plist_array_iter = *mut ::std::os::raw::c_void;
+pub const plist_type_PLIST_BOOLEAN: plist_type = 0;
+pub const plist_type_PLIST_UINT: plist_type = 1;
+pub const plist_type_PLIST_REAL: plist_type = 2;
+pub const plist_type_PLIST_STRING: plist_type = 3;
+pub const plist_type_PLIST_ARRAY: plist_type = 4;
+pub const plist_type_PLIST_DICT: plist_type = 5;
+pub const plist_type_PLIST_DATE: plist_type = 6;
+pub const plist_type_PLIST_DATA: plist_type = 7;
+pub const plist_type_PLIST_KEY: plist_type = 8;
+pub const plist_type_PLIST_UID: plist_type = 9;
+pub const plist_type_PLIST_NONE: plist_type = 10;
+pub type plist_type = u32;
+extern "C" {
+    pub fn plist_new_dict() -> plist_t;
+}
+extern "C" {
+    pub fn plist_new_array() -> plist_t;
+}
+extern "C" {
+    pub fn plist_new_string(val: *const ::std::os::raw::c_char) -> plist_t;
+}
+extern "C" {
+    pub fn plist_new_bool(val: u8) -> plist_t;
+}
+extern "C" {
+    pub fn plist_new_uint(val: u64) -> plist_t;
+}
+extern "C" {
+    pub fn plist_new_real(val: f64) -> plist_t;
+}
+extern "C" {
+    pub fn plist_new_data(val: *const ::std::os::raw::c_char, length: u64) -> plist_t;
+}
+extern "C" {
+    pub fn plist_new_date(sec: i32, usec: i32) -> plist_t;
+}
+extern "C" {
+    pub fn plist_new_uid(val: u64) -> plist_t;
+}
+extern "C" {
+    pub fn plist_free(plist: plist_t);
+}
+extern "C" {
+    pub fn plist_copy(node: plist_t) -> plist_t;
+}
+extern "C" {
+    pub fn plist_array_get_size(node: plist_t) -> u32;
+}
+extern "C" {
+    pub fn plist_array_get_item(node: plist_t, n: u32) -> plist_t;
+}
+extern "C" {
+    pub fn plist_array_get_item_index(node: plist_t) -> u32;
+}
+extern "C" {
+    pub fn plist_array_set_item(node: plist_t, item: plist_t, n: u32);
+}
+extern "C" {
+    pub fn plist_array_append_item(node: plist_t, item: plist_t);
+}
+extern "C" {
+    pub fn plist_array_insert_item(node: plist_t, item: plist_t, n: u32);
+}
+extern "C" {
+    pub fn plist_array_remove_item(node: plist_t, n: u32);
+}
+extern "C" {
+    pub fn plist_array_item_remove(node: plist_t);
+}
+extern "C" {
+    pub fn plist_array_new_iter(node: plist_t, iter: *mut plist_array_iter);
+}
+extern "C" {
+    pub fn plist_array_next_item(node: plist_t, iter: plist_array_iter, item: *mut plist_t);
+}
+extern "C" {
+    pub fn plist_dict_get_size(node: plist_t) -> u32;
+}
+extern "C" {
+    pub fn plist_dict_new_iter(node: plist_t, iter: *mut plist_dict_iter);
+}
+extern "C" {
+    pub fn plist_dict_next_item(
+        node: plist_t,
+        iter: plist_dict_iter,
+        key: *mut *mut ::std::os::raw::c_char,
+        val: *mut plist_t,
+    );
+}
+extern "C" {
+    pub fn plist_dict_get_item_key(node: plist_t, key: *mut *mut ::std::os::raw::c_char);
+}
+extern "C" {
+    pub fn plist_dict_get_item(node: plist_t, key: *const ::std::os::raw::c_char) -> plist_t;
+}
+extern "C" {
+    pub fn plist_dict_item_get_key(node: plist_t) -> plist_t;
+}
+extern "C" {
+    pub fn plist_dict_set_item(node: plist_t, key: *const ::std::os::raw::c_char, item: plist_t);
+}
+extern "C" {
+    pub fn plist_dict_remove_item(node: plist_t, key: *const ::std::os::raw::c_char);
+}
+extern "C" {
+    pub fn plist_dict_merge(target: *mut plist_t, source: plist_t);
+}
+extern "C" {
+    pub fn plist_get_parent(node: plist_t) -> plist_t;
+}
+extern "C" {
+    pub fn plist_get_node_type(node: plist_t) -> plist_type;
+}
+extern "C" {
+    pub fn plist_get_key_val(node: plist_t, val: *mut *mut ::std::os::raw::c_char);
+}
+extern "C" {
+    pub fn plist_get_string_val(node: plist_t, val: *mut *mut ::std::os::raw::c_char);
+}
+extern "C" {
+    pub fn plist_get_string_ptr(node: plist_t, length: *mut u64) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn plist_get_bool_val(node: plist_t, val: *mut u8);
+}
+extern "C" {
+    pub fn plist_get_uint_val(node: plist_t, val: *mut u64);
+}
+extern "C" {
+    pub fn plist_get_real_val(node: plist_t, val: *mut f64);
+}
+extern "C" {
+    pub fn plist_get_data_val(
+        node: plist_t,
+        val: *mut *mut ::std::os::raw::c_char,
+        length: *mut u64,
+    );
+}
+extern "C" {
+    pub fn plist_get_data_ptr(node: plist_t, length: *mut u64) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn plist_get_date_val(node: plist_t, sec: *mut i32, usec: *mut i32);
+}
+extern "C" {
+    pub fn plist_get_uid_val(node: plist_t, val: *mut u64);
+}
+extern "C" {
+    pub fn plist_set_key_val(node: plist_t, val: *const ::std::os::raw::c_char);
+}
+extern "C" {
+    pub fn plist_set_string_val(node: plist_t, val: *const ::std::os::raw::c_char);
+}
+extern "C" {
+    pub fn plist_set_bool_val(node: plist_t, val: u8);
+}
+extern "C" {
+    pub fn plist_set_uint_val(node: plist_t, val: u64);
+}
+extern "C" {
+    pub fn plist_set_real_val(node: plist_t, val: f64);
+}
+extern "C" {
+    pub fn plist_set_data_val(node: plist_t, val: *const ::std::os::raw::c_char, length: u64);
+}
+extern "C" {
+    pub fn plist_set_date_val(node: plist_t, sec: i32, usec: i32);
+}
+extern "C" {
+    pub fn plist_set_uid_val(node: plist_t, val: u64);
+}
+extern "C" {
+    pub fn plist_to_xml(
+        plist: plist_t,
+        plist_xml: *mut *mut ::std::os::raw::c_char,
+        length: *mut u32,
+    );
+}
+extern "C" {
+    pub fn plist_to_bin(
+        plist: plist_t,
+        plist_bin: *mut *mut ::std::os::raw::c_char,
+        length: *mut u32,
+    );
+}
+extern "C" {
+    pub fn plist_to_xml_free(plist_xml: *mut ::std::os::raw::c_char);
+}
+extern "C" {
+    pub fn plist_to_bin_free(plist_bin: *mut ::std::os::raw::c_char);
+}
+extern "C" {
+    pub fn plist_from_xml(
+        plist_xml: *const ::std::os::raw::c_char,
+        length: u32,
+        plist: *mut plist_t,
+    );
+}
+extern "C" {
+    pub fn plist_from_bin(
+        plist_bin: *const ::std::os::raw::c_char,
+        length: u32,
+        plist: *mut plist_t,
+    );
+}
+extern "C" {
+    pub fn plist_from_memory(
+        plist_data: *const ::std::os::raw::c_char,
+        length: u32,
+        plist: *mut plist_t,
+    );
+}
+extern "C" {
+    pub fn plist_is_binary(plist_data: *const ::std::os::raw::c_char, length: u32) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn plist_access_path(plist: plist_t, length: u32, ...) -> plist_t;
+}
+extern "C" {
+    pub fn plist_compare_node_value(node_l: plist_t, node_r: plist_t) -> ::std::os::raw::c_char;
+}
@@ -0,0 +1,181 @@
+#[cfg(feature = "bindgen")]
+extern crate bindgen;
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+const LIB_NAME: &str = "libplist-2.0";
+
+struct Library {
+    #[cfg_attr(not(feature = "bindgen"), allow(dead_code))]
+    cargs: Vec<String>,
+    v2_3: bool
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=wrapper.h");
+    println!("cargo:rerun-if-changed=bindings");
+
+    let vendored = env::var_os("CARGO_FEATURE_VENDORED").is_some();
+    let dynamic = env::var_os("CARGO_FEATURE_DYNAMIC").is_some();
+    if vendored && dynamic {
+        panic!("The `vendored` and `dynamic` features are mutually exclusive, enable only one of them");
+    }
+
+    let library = if vendored {
+        build_vendored()
+    } else {
+        link_system(dynamic)
+    };
+
+    // Exposed to dependents' build scripts through the `links` metadata.
+    println!("cargo:v2_3={}", if library.v2_3 { 1 } else { 0 });
+
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("bindings.rs");
+    write_bindings(&library, &out_path);
+}
+
+#[cfg(feature = "bindgen")]
+fn write_bindings(library: &Library, out_path: &std::path::Path) {
+    let bindings = bindgen::Builder::default()
+        .header("wrapper.h")
+        .clang_args(&library.cargs)
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks))
+        .whitelist_function("plist_.*")
+        .whitelist_type("plist_.*")
+        .size_t_is_usize(true)
+        .layout_tests(false)
+        .generate()
+        .expect("Unsable to generate bindings");
+
+    bindings
+        .write_to_file(out_path)
+        .expect("Couldn't write bindings!");
+}
+
+#[cfg(not(feature = "bindgen"))]
+fn write_bindings(library: &Library, out_path: &std::path::Path) {
+    let name = if library.v2_3 { "libplist_2_3.rs" } else { "libplist_2_2.rs" };
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    std::fs::copy(manifest_dir.join("bindings").join(name), out_path)
+        .expect("Couldn't copy pre-generated bindings!");
+}
+
+/// The major and minor parts of a pkg-config version such as `2.3.0`.
+fn major_minor(version: &str) -> (u32, u32) {
+    let mut parts = version.trim().split('.').map(|part| part.parse::<u32>().unwrap_or(0));
+
+    (parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+}
+
+fn link_system(dynamic: bool) -> Library {
+    let version = pkg_config(&["--modversion"]);
+    if major_minor(&version) < (2, 2) {
+        panic!("Found {} {}, but libplist 2.2 or newer is required", LIB_NAME, version);
+    }
+
+    let mut args = vec!["--libs-only-L"];
+    if !dynamic {
+        args.push("--static");
+    }
+
+    for path in pkg_config(&args).split_whitespace() {
+        if let Some(path) = path.strip_prefix("-L") {
+            println!("cargo:rustc-link-search=native={}", path);
+        }
+    }
+
+    let kind = if dynamic { "dylib" } else { "static" };
+    println!("cargo:rustc-link-lib={}=plist-2.0", kind);
+
+    Library {
+        cargs: pkg_config(&["--cflags"])
+            .split_whitespace()
+            .map(|arg| arg.to_owned())
+            .collect(),
+        // libplist 2.3 added `plist_err_t` results, JSON and OpenStep, signed integers,
+        // `PLIST_NULL` and `plist_mem_free`, so the bindings differ from 2.2.
+        v2_3: major_minor(&version) >= (2, 3)
+    }
+}
+
+fn pkg_config(args: &[&str]) -> String {
+    let output = match Command::new("pkg-config").args(args).arg(LIB_NAME).output() {
+        Ok(output) => output,
+        Err(error) => panic!(
+            "Failed to run pkg-config ({}). Install pkg-config and {}, or enable the `vendored` feature to build the bundled libplist sources",
+            error, LIB_NAME
+        )
+    };
+
+    if !output.status.success() {
+        panic!(
+            "pkg-config could not find {}: {}\nInstall the libplist development package, set PKG_CONFIG_PATH, or enable the `vendored` feature to build the bundled libplist sources",
+            LIB_NAME,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+
+    String::from_utf8_lossy(&output.stdout).trim_end().to_owned()
+}
+
+#[cfg(feature = "vendored")]
+fn build_vendored() -> Library {
+    // The libplist git submodule, which the package includes.
+    let source_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("vendor").join("libplist");
+    if !source_dir.join("src").join("plist.c").exists() {
+        panic!(
+            "The libplist sources are missing from {}. In a git checkout, run `git submodule update --init plist-sys/vendor/libplist`",
+            source_dir.display()
+        );
+    }
+    println!("cargo:rerun-if-changed={}", source_dir.display());
+
+    let include_dir = source_dir.join("include");
+    let mut build = cc::Build::new();
+    build
+        .include(&include_dir)
+        .include(source_dir.join("src"))
+        .include(source_dir.join("libcnary").join("include"))
+        .files(c_sources(&source_dir.join("src")))
+        .files(c_sources(&source_dir.join("libcnary")))
+        .define("LIBPLIST_STATIC", None)
+        .warnings(false);
+    if env::var("CARGO_CFG_TARGET_OS").map_or(true, |os| os != "windows") {
+        build
+            .define("HAVE_STRPTIME", None)
+            .define("HAVE_TIMEGM", None)
+            .define("HAVE_GMTIME_R", None)
+            .define("HAVE_LOCALTIME_R", None);
+    }
+    build.compile("plist-2.0");
+
+    // Release tarballs and git checkouts spell the version differently in
+    // configure.ac, so look for an API that only exists from 2.3 on.
+    let header = std::fs::read_to_string(include_dir.join("plist").join("plist.h"))
+        .expect("Couldn't read vendored plist.h");
+
+    Library {
+        cargs: vec![format!("-I{}", include_dir.display())],
+        v2_3: header.contains("plist_mem_free")
+    }
+}
+
+#[cfg(feature = "vendored")]
+fn c_sources(dir: &std::path::Path) -> Vec<PathBuf> {
+    let mut sources = std::fs::read_dir(dir)
+        .unwrap_or_else(|error| panic!("Couldn't read {}: {}", dir.display(), error))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        // libcnary/cnary.c is a standalone test program with its own main().
+        .filter(|path| path.extension().is_some_and(|ext| ext == "c") && !path.ends_with("cnary.c"))
+        .collect::<Vec<PathBuf>>();
+    sources.sort();
+
+    sources
+}
+
+#[cfg(not(feature = "vendored"))]
+fn build_vendored() -> Library {
+    unreachable!()
+}
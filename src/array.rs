@@ -65,6 +65,11 @@ impl PlistArray {
         self.inner.get(index)
     }
 
+    /// Turns the array back into a `Plist`, copying it if an iterator still shares it.
+    pub fn into_plist(self) -> Plist {
+        Rc::try_unwrap(self.inner).unwrap_or_else(|inner| inner.copy())
+    }
+
     pub fn set(&self, mut item: Plist, index: u32) {
         unsafe { plist_array_set_item(self.inner.as_ptr().unwrap(), item.as_ptr().unwrap(), index) }
         item.unowned_ptr();
@@ -0,0 +1,264 @@
+//! Tokio versions of the length-prefixed framing in [`crate::codec`]: [`AsyncPlistStream`] for
+//! request/reply exchanges and [`PlistCodec`] for `tokio_util`'s `Framed`.
+//!
+//! Parsing and serializing run on the calling task; messages are small enough that it isn't
+//! worth a `spawn_blocking`.
+
+use std::future::Future;
+use bytes::{Buf, BufMut, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::codec::{Decoder, Encoder};
+use crate::Plist;
+use crate::codec::{self, DEFAULT_MAX_SIZE, HEADER_SIZE};
+use crate::format::PlistFormat;
+use crate::plist_error::PlistError;
+
+pub struct AsyncPlistStream<T> {
+    inner: T,
+    format: PlistFormat,
+    max_size: u32
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncPlistStream<T> {
+    /// Sends XML plists and accepts messages up to [`DEFAULT_MAX_SIZE`] bytes.
+    pub fn new(inner: T) -> Self {
+        AsyncPlistStream {
+            inner,
+            format: PlistFormat::Xml,
+            max_size: DEFAULT_MAX_SIZE
+        }
+    }
+
+    /// The format of sent messages. Received messages may be in any format.
+    pub fn format(mut self, format: PlistFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// The largest message, in bytes, that is sent or received.
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Serializes `plist` before the returned future is first polled, so the future doesn't
+    /// borrow `plist` and is `Send` whenever `T` is.
+    pub fn send(&mut self, plist: &Plist) -> impl Future<Output = Result<(), PlistError>> + '_ {
+        let message = codec::frame(plist, self.format, self.max_size);
+
+        async move { self.write(&message?).await }
+    }
+
+    /// Reads the next message. After an oversized length the stream can't be resynchronized,
+    /// so it should be dropped.
+    pub async fn receive(&mut self) -> Result<Plist, PlistError> {
+        let mut header = [0u8; HEADER_SIZE];
+        self.inner.read_exact(&mut header).await?;
+        let length = codec::message_length(header, self.max_size)?;
+
+        let mut data = vec![0u8; length];
+        self.inner.read_exact(&mut data).await?;
+
+        codec::parse(&data)
+    }
+
+    /// Sends `request` and waits for the reply. Like [`AsyncPlistStream::send`], the request is
+    /// serialized up front, so the future is `Send` whenever `T` is.
+    pub fn request(&mut self, request: &Plist) -> impl Future<Output = Result<Plist, PlistError>> + '_ {
+        let message = codec::frame(request, self.format, self.max_size);
+
+        async move {
+            self.write(&message?).await?;
+            self.receive().await
+        }
+    }
+
+    async fn write(&mut self, message: &[u8]) -> Result<(), PlistError> {
+        self.inner.write_all(message).await?;
+        self.inner.flush().await?;
+
+        Ok(())
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// Length-prefixed plist messages for `tokio_util::codec::Framed`.
+#[derive(Debug, Clone, Copy)]
+pub struct PlistCodec {
+    format: PlistFormat,
+    max_size: u32
+}
+
+impl PlistCodec {
+    /// Encodes XML plists and accepts messages up to [`DEFAULT_MAX_SIZE`] bytes.
+    pub fn new() -> Self {
+        PlistCodec {
+            format: PlistFormat::Xml,
+            max_size: DEFAULT_MAX_SIZE
+        }
+    }
+
+    /// The format of encoded messages. Decoded messages may be in any format.
+    pub fn format(mut self, format: PlistFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// The largest message, in bytes, that is encoded or decoded.
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+impl Default for PlistCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for PlistCodec {
+    type Item = Plist;
+    type Error = PlistError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Plist>, PlistError> {
+        if src.len() < HEADER_SIZE {
+            return Ok(None)
+        }
+        let length = codec::message_length([src[0], src[1], src[2], src[3]], self.max_size)?;
+        if src.len() < HEADER_SIZE + length {
+            src.reserve(HEADER_SIZE + length - src.len());
+            return Ok(None)
+        }
+
+        src.advance(HEADER_SIZE);
+        let data = src.split_to(length);
+
+        codec::parse(&data).map(Some)
+    }
+}
+
+impl Encoder<&Plist> for PlistCodec {
+    type Error = PlistError;
+
+    fn encode(&mut self, item: &Plist, dst: &mut BytesMut) -> Result<(), PlistError> {
+        dst.put_slice(&codec::frame(item, self.format, self.max_size)?);
+
+        Ok(())
+    }
+}
+
+impl Encoder<Plist> for PlistCodec {
+    type Error = PlistError;
+
+    fn encode(&mut self, item: Plist, dst: &mut BytesMut) -> Result<(), PlistError> {
+        self.encode(&item, dst)
+    }
+}
+
+impl Plist {
+    /// Reads `reader` to the end and parses what it read, detecting the format like
+    /// [`Plist::from_memory`].
+    pub async fn from_async_reader<R: AsyncRead + Unpin>(mut reader: R) -> Result<Plist, PlistError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+
+        codec::parse(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::ErrorKind;
+    use bytes::BytesMut;
+    use tokio::io::AsyncWriteExt;
+    use tokio_util::codec::{Decoder, Encoder};
+    use crate::{Getter, Plist};
+    use crate::async_codec::{AsyncPlistStream, PlistCodec};
+    use crate::format::PlistFormat;
+    use crate::plist_error::PlistError;
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[tokio::test]
+    async fn round_trip() {
+        let (client, server) = tokio::io::duplex(64);
+        let mut client = AsyncPlistStream::new(client);
+        let mut server = AsyncPlistStream::new(server).format(PlistFormat::Binary);
+
+        let request = Plist::from(HashMap::from([("Request", Plist::from("GetValue"))]));
+        let send = client.send(&request);
+        assert_send(&send);
+        let (sent, received) = tokio::join!(send, server.receive());
+        sent.unwrap();
+        assert_eq!(received.unwrap(), request);
+
+        let reply = Plist::from(HashMap::from([("Value", Plist::from(42))]));
+        let (sent, received) = tokio::join!(server.send(&reply), client.receive());
+        sent.unwrap();
+        assert_eq!(received.unwrap().get("Value").unwrap().as_uint().unwrap(), 42);
+
+        let request = client.request(&reply);
+        assert_send(&request);
+        let echo = async {
+            let received = server.receive().await.unwrap();
+            server.send(&received).await.unwrap();
+        };
+        let (received, ()) = tokio::join!(request, echo);
+        assert_eq!(received.unwrap(), reply);
+    }
+
+    #[tokio::test]
+    async fn limits() {
+        let (client, mut server) = tokio::io::duplex(64);
+        let mut client = AsyncPlistStream::new(client).max_size(64);
+
+        let error = client.send(&Plist::from("x".repeat(100))).await.unwrap_err();
+        assert!(matches!(error, PlistError::Io(error) if error.kind() == ErrorKind::InvalidData));
+
+        server.write_all(&u32::MAX.to_be_bytes()).await.unwrap();
+        let error = client.receive().await.unwrap_err();
+        assert!(matches!(error, PlistError::Io(error) if error.kind() == ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn codec() {
+        let mut codec = PlistCodec::new().format(PlistFormat::Binary);
+        let mut buffer = BytesMut::new();
+        codec.encode(Plist::from("foo"), &mut buffer).unwrap();
+        codec.encode(&Plist::from(true), &mut buffer).unwrap();
+
+        let mut partial = buffer.split_to(5);
+        assert!(codec.decode(&mut partial).unwrap().is_none());
+        partial.unsplit(buffer);
+        let mut buffer = partial;
+
+        assert_eq!(codec.decode(&mut buffer).unwrap().unwrap().as_str().unwrap(), "foo");
+        assert!(codec.decode(&mut buffer).unwrap().unwrap().as_bool().unwrap());
+        assert!(codec.decode(&mut buffer).unwrap().is_none());
+
+        let mut buffer = BytesMut::from(&u32::MAX.to_be_bytes()[..]);
+        assert!(codec.max_size(16).decode(&mut buffer).is_err());
+    }
+
+    #[tokio::test]
+    async fn async_reader() {
+        let xml = Plist::from(vec![Plist::from("a"), Plist::from(1)]).xml().unwrap();
+        let plist = Plist::from_async_reader(xml.as_bytes()).await.unwrap();
+
+        assert_eq!(plist.get(1usize).unwrap().as_uint().unwrap(), 1);
+        assert!(Plist::from_async_reader(&b"bplist00\x00"[..]).await.is_err());
+    }
+}
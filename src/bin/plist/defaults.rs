@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, ErrorKind, Read};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::slice::Iter;
+use plist_rs::Plist;
+use plist_rs::defaults::Defaults;
+use plist_rs::dict::DictSetter;
+use plist_rs::plist_error::PlistError;
+use crate::{describe, parse_date, parse_integer, write_bytes, Style};
+
+const USAGE: &str = "usage: plist defaults [-dir path] <verb> ...
+The directory defaults to $PLIST_DEFAULTS_DIR or the current directory.
+
+Verbs:
+ domains                         list the domains
+ read [domain [key]]             print all domains, a domain or a single key
+ write domain key value          write a value for key
+ write domain plist              replace the domain with a plist literal
+ delete domain [key]             delete a domain or a single key
+ export domain path              write the domain to path as an XML plist ('-' for stdout)
+ import domain path              replace the domain with the plist at path ('-' for stdin)
+
+Values:
+ -string string, -data hex, -int number, -float number, -bool YES|NO, -date date,
+ -array value..., -array-add value..., -dict key value..., -dict-add key value...
+ A value without a type is a string.
+";
+
+pub fn run(args: &[String]) -> Result<i32, String> {
+    let (dir, args) = match args {
+        [flag, dir, args @ ..] if flag == "-dir" => (PathBuf::from(dir), args),
+        _ => (env::var_os("PLIST_DEFAULTS_DIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from(".")), args)
+    };
+    let defaults = Defaults::new(dir);
+    let (verb, args) = args.split_first().ok_or(USAGE)?;
+
+    match (verb.as_str(), args) {
+        ("domains", []) => {
+            println!("{}", defaults.domains().map_err(|error| error.to_string())?.join(", "));
+        }
+        ("read", []) => {
+            let mut domains = Vec::new();
+            for domain in defaults.domains().map_err(|error| error.to_string())? {
+                let dict: Rc<Plist> = defaults.read(&domain).map_err(|error| domain_error(&domain, error))?.into();
+                domains.push((domain, dict));
+            }
+            let all = domains.iter().map(|(domain, dict)| (domain.as_str(), dict.copy())).collect::<HashMap<&str, Plist>>();
+            print(Plist::from(all));
+        }
+        ("read", [domain]) => {
+            let dict: Rc<Plist> = defaults.read(domain).map_err(|error| domain_error(domain, error))?.into();
+            print(dict.copy());
+        }
+        ("read", [domain, key]) => {
+            let value = defaults.read_key(domain, key).map_err(|error| domain_error(domain, error))?;
+            let value = value.ok_or_else(|| pair_error(domain, key))?;
+            print(value);
+        }
+        ("write", [domain, plist]) => {
+            let plist = parse_plist(plist.as_bytes()).ok_or("Could not parse the domain as a dictionary plist.")?;
+            defaults.import(domain, plist).map_err(|error| domain_error(domain, error))?;
+        }
+        ("write", [domain, key, value @ ..]) => {
+            let value = parse_write(&defaults, domain, key, value)?;
+            defaults.write(domain, key, value).map_err(|error| domain_error(domain, error))?;
+        }
+        ("delete", [domain]) => {
+            defaults.delete(domain).map_err(|error| domain_error(domain, error))?;
+        }
+        ("delete", [domain, key]) => {
+            if !defaults.delete_key(domain, key).map_err(|error| domain_error(domain, error))? {
+                return Err(pair_error(domain, key))
+            }
+        }
+        ("export", [domain, path]) => {
+            let xml = defaults.export(domain).map_err(|error| domain_error(domain, error))?;
+            write_bytes(xml.as_bytes(), path)?;
+        }
+        ("import", [domain, path]) => {
+            let data = if path == "-" {
+                let mut data = Vec::new();
+                io::stdin().read_to_end(&mut data).map(|_| data)
+            } else {
+                fs::read(path)
+            };
+            let data = data.map_err(|error| format!("{}: {}", path, error))?;
+            let plist = parse_plist(&data).ok_or_else(|| format!("{}: could not parse the plist", path))?;
+            defaults.import(domain, plist).map_err(|error| domain_error(domain, error))?;
+        }
+        _ => return Err(USAGE.to_owned())
+    }
+
+    Ok(0)
+}
+
+fn domain_error(domain: &str, error: PlistError) -> String {
+    match error {
+        PlistError::Io(error) if error.kind() == ErrorKind::NotFound => format!("Domain {} does not exist", domain),
+        PlistError::InvalidArg => format!("Invalid domain name: {}", domain),
+        error => format!("{}: {}", domain, error)
+    }
+}
+
+fn pair_error(domain: &str, key: &str) -> String {
+    format!("The domain/default pair of ({}, {}) does not exist", domain, key)
+}
+
+fn parse_plist(data: &[u8]) -> Option<Plist> {
+    Plist::from_memory(data).ok()
+}
+
+fn parse_write(defaults: &Defaults, domain: &str, key: &str, args: &[String]) -> Result<Plist, String> {
+    let (kind, rest) = args.split_first().ok_or(USAGE)?;
+    match kind.as_str() {
+        "-array" | "-array-add" => {
+            let mut items = Vec::new();
+            if kind == "-array-add" {
+                if let Some(existing) = defaults.read_key(domain, key).ok().flatten().and_then(|existing| existing.array()) {
+                    items.extend(existing.into_iter().map(|item| item.copy()));
+                }
+            }
+            let mut args = rest.iter();
+            while let Some(item) = parse_value(&mut args)? {
+                items.push(item);
+            }
+
+            Ok(Plist::from(items))
+        }
+        "-dict" | "-dict-add" => {
+            let existing = if kind == "-dict-add" {
+                defaults.read_key(domain, key).ok().flatten().and_then(|existing| existing.dict())
+            } else {
+                None
+            };
+            let dict = existing.unwrap_or_else(|| Plist::from(HashMap::new()).dict().unwrap());
+            let mut args = rest.iter();
+            while let Some(key) = args.next() {
+                let value = parse_value(&mut args)?.ok_or_else(|| format!("Missing value for key {}", key))?;
+                dict.set(key.as_str(), value);
+            }
+            let dict: Rc<Plist> = dict.into();
+
+            Ok(Rc::try_unwrap(dict).unwrap_or_else(|dict| dict.copy()))
+        }
+        _ => {
+            let mut args = args.iter();
+            let value = parse_value(&mut args)?.ok_or(USAGE)?;
+            if args.next().is_some() {
+                return Err(USAGE.to_owned())
+            }
+
+            Ok(value)
+        }
+    }
+}
+
+fn parse_value(args: &mut Iter<String>) -> Result<Option<Plist>, String> {
+    let kind = match args.next() {
+        Some(kind) => kind,
+        None => return Ok(None)
+    };
+    if !kind.starts_with('-') {
+        return Ok(Some(Plist::from(kind.as_str())))
+    }
+
+    let value = args.next().ok_or_else(|| format!("Missing value for {}", kind))?;
+    let invalid = || format!("Invalid value for {}: {}", kind, value);
+    let plist = match kind.as_str() {
+        "-string" => Plist::from(value.as_str()),
+        "-int" | "-integer" => parse_integer(value).ok_or_else(invalid)?,
+        "-float" => Plist::from(value.parse::<f64>().map_err(|_| invalid())?),
+        "-bool" | "-boolean" => match value.to_ascii_lowercase().as_str() {
+            "yes" | "true" | "1" => Plist::from(true),
+            "no" | "false" | "0" => Plist::from(false),
+            _ => return Err(invalid())
+        },
+        "-date" => Plist::from_date(parse_date(value).ok_or_else(invalid)?, 0),
+        "-data" => {
+            let hex = value.bytes().filter(|byte| !byte.is_ascii_whitespace()).collect::<Vec<u8>>();
+            if hex.len() % 2 != 0 {
+                return Err(invalid())
+            }
+            let data = hex.chunks(2)
+                .map(|pair| std::str::from_utf8(pair).ok().and_then(|pair| u8::from_str_radix(pair, 16).ok()).map(|byte| byte as i8))
+                .collect::<Option<Vec<i8>>>()
+                .ok_or_else(invalid)?;
+            Plist::from(data.as_slice())
+        }
+        _ => return Err(format!("Unknown type: {}", kind))
+    };
+
+    Ok(Some(plist))
+}
+
+fn print(plist: Plist) {
+    println!("{}", describe(plist, Style::Defaults));
+}
@@ -0,0 +1,738 @@
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::exit;
+use std::rc::Rc;
+use plist_rs::Plist;
+use plist_rs::array::PlistArray;
+use plist_rs::dict::{DictGetter, DictRemove, DictSetter, PlistDict};
+use plist_rs::format::PlistFormat;
+use plist_rs::plist_node_type::PlistNodeType;
+use plist_rs::value::MAC_EPOCH;
+
+mod defaults;
+
+const USAGE: &str = "usage: plist <command> <options> <file>...
+The file '-' means stdin or stdout.
+
+Commands:
+ -help                           show this message
+ -lint                           check the property list files for syntax errors
+ -p                              print the property list in a human-readable fashion
+ -convert fmt                    rewrite the files in format fmt (xml1, binary1, json, openstep)
+ -extract keypath fmt            write the value at keypath in format fmt (xml1, binary1, json, openstep, raw)
+ -insert keypath -type value     insert a value at keypath
+ -replace keypath -type value    replace the value at keypath, inserting it if missing
+ -remove keypath                 remove the value at keypath
+ defaults <verb> ...             read and write a directory of preference domains, see 'plist defaults'
+
+Options:
+ -s                              don't print anything on success
+ -r                              human-readable JSON or OpenStep output
+ -o path                         write to path instead of the input file
+ -e extension                    write to the input file with the extension changed
+ -n                              no trailing newline for -extract raw
+ -append                         with -insert, append the value to the array at keypath
+
+Types for -insert and -replace:
+ -bool YES|NO, -integer number, -float number, -string string, -date date (ISO 8601),
+ -data base64, -xml plist, -json json, -array, -dictionary
+
+Key paths are dot separated; array elements are addressed by index and '\\.' escapes a dot.
+";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let code = match run(&args) {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    };
+
+    exit(code)
+}
+
+fn run(args: &[String]) -> Result<i32, String> {
+    let (command, args) = match args.split_first() {
+        Some(split) => split,
+        None => return Err(USAGE.to_owned())
+    };
+
+    match command.as_str() {
+        "-help" | "-h" | "--help" => {
+            print!("{}", USAGE);
+            Ok(0)
+        }
+        "-lint" => lint(args),
+        "-p" => print(args),
+        "-convert" => convert(args),
+        "-extract" => extract(args),
+        "-insert" => set(args, true),
+        "-replace" => set(args, false),
+        "-remove" => remove(args),
+        "defaults" => defaults::run(args),
+        _ => Err(format!("unrecognized command: {}\n{}", command, USAGE))
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    silent: bool,
+    readable: bool,
+    newline: bool,
+    append: bool,
+    output: Option<String>,
+    extension: Option<String>,
+    files: Vec<String>
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            newline: true,
+            ..Default::default()
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-s" => options.silent = true,
+                "-r" => options.readable = true,
+                "-n" => options.newline = false,
+                "-append" => options.append = true,
+                "-o" => options.output = Some(args.next().ok_or("missing argument for -o")?.clone()),
+                "-e" => options.extension = Some(args.next().ok_or("missing argument for -e")?.clone()),
+                "--" => options.files.extend(args.by_ref().cloned()),
+                _ if arg.len() > 1 && arg.starts_with('-') => return Err(format!("unrecognized option: {}", arg)),
+                _ => options.files.push(arg.clone())
+            }
+        }
+
+        if options.files.is_empty() {
+            return Err("No files specified.".to_owned())
+        }
+
+        Ok(options)
+    }
+
+    fn single_file(&self) -> Result<&str, String> {
+        match self.files.as_slice() {
+            [file] => Ok(file),
+            _ => Err("Only one file may be specified for this command.".to_owned())
+        }
+    }
+
+    fn output_path(&self, file: &str) -> String {
+        if let Some(output) = &self.output {
+            return output.clone()
+        }
+
+        match &self.extension {
+            Some(extension) => {
+                let stem = match file.rfind('.') {
+                    Some(dot) if !file[dot..].contains('/') => &file[..dot],
+                    _ => file
+                };
+                format!("{}.{}", stem, extension)
+            }
+            None => file.to_owned()
+        }
+    }
+}
+
+fn lint(args: &[String]) -> Result<i32, String> {
+    let options = Options::parse(args)?;
+    let mut code = 0;
+    for file in &options.files {
+        match read(file) {
+            Ok(_) => {
+                if !options.silent {
+                    println!("{}: OK", file);
+                }
+            }
+            Err(message) => {
+                println!("{}", message);
+                code = 1;
+            }
+        }
+    }
+
+    Ok(code)
+}
+
+fn print(args: &[String]) -> Result<i32, String> {
+    let options = Options::parse(args)?;
+    for file in &options.files {
+        let (plist, _) = read(file)?;
+        println!("{}", describe(plist, Style::Plutil));
+    }
+
+    Ok(0)
+}
+
+fn convert(args: &[String]) -> Result<i32, String> {
+    let (format, args) = args.split_first().ok_or("Missing format specifier for command.")?;
+    let format = parse_format(format)?;
+    let options = Options::parse(args)?;
+    if options.output.is_some() && options.files.len() > 1 {
+        return Err("-o may only be used with a single file.".to_owned())
+    }
+
+    for file in &options.files {
+        let (plist, _) = read(file)?;
+        write(&plist, format, options.readable, &options.output_path(file))?;
+    }
+
+    Ok(0)
+}
+
+fn extract(args: &[String]) -> Result<i32, String> {
+    let (keypath, args) = args.split_first().ok_or("'Extract' requires a key path and a plist format.")?;
+    let (format, args) = args.split_first().ok_or("'Extract' requires a key path and a plist format.")?;
+    let options = Options::parse(args)?;
+    let file = options.single_file()?;
+    let path = split_keypath(keypath);
+
+    let (plist, _) = read(file)?;
+    let root = Container::new(plist).ok_or_else(|| no_value(keypath))?;
+    let (key, parents) = path.split_last().ok_or_else(|| no_value(keypath))?;
+    let parent = parent(&root, parents).ok_or_else(|| no_value(keypath))?;
+    let value = parent.as_ref().unwrap_or(&root).get(key).ok_or_else(|| no_value(keypath))?;
+
+    if format == "raw" {
+        let mut raw = describe_raw(&value).ok_or_else(|| format!("Value at [{}] can't be formatted as raw.", keypath))?;
+        if options.newline {
+            raw.push('\n');
+        }
+        let output = options.output.clone().unwrap_or_else(|| "-".to_owned());
+        return write_bytes(raw.as_bytes(), &output).map(|_| 0)
+    }
+
+    write(&value, parse_format(format)?, options.readable, &options.output_path(file))?;
+
+    Ok(0)
+}
+
+fn set(args: &[String], insert: bool) -> Result<i32, String> {
+    let (keypath, args) = args.split_first().ok_or("Missing key path.")?;
+    let (kind, args) = args.split_first().ok_or("Missing value type.")?;
+    let (value, args) = match kind.as_str() {
+        "-array" | "-dictionary" => (None, args),
+        _ => {
+            let (value, args) = args.split_first().ok_or("Missing value.")?;
+            (Some(value.as_str()), args)
+        }
+    };
+    let value = parse_value(kind, value)?;
+    let options = Options::parse(args)?;
+    let file = options.single_file()?;
+    let path = split_keypath(keypath);
+
+    let (plist, format) = read(file)?;
+    let root = Container::new(plist).ok_or("The root object is not a dictionary or an array.")?;
+
+    if options.append {
+        let target = parent(&root, &path).ok_or_else(|| no_value(keypath))?;
+        match target.as_ref().unwrap_or(&root) {
+            Container::Array(array) => array.append(value),
+            Container::Dict(_) => return Err(format!("Value at [{}] is not an array.", keypath))
+        }
+    } else {
+        let (key, parents) = path.split_last().ok_or_else(|| no_value(keypath))?;
+        let parent = parent(&root, parents).ok_or_else(|| no_value(keypath))?;
+        match parent.as_ref().unwrap_or(&root) {
+            Container::Dict(dict) => {
+                if insert && dict.get(key.as_str()).is_some() {
+                    return Err(format!("Value already exists at key path {}", keypath))
+                }
+                dict.set(key.as_str(), value);
+            }
+            Container::Array(array) => {
+                let index = key.parse::<u32>().map_err(|_| no_value(keypath))?;
+                let length = array.len() as u32;
+                match insert {
+                    true if index <= length => array.insert(value, index),
+                    false if index < length => array.set(value, index),
+                    false if index == length => array.append(value),
+                    _ => return Err(no_value(keypath))
+                }
+            }
+        }
+    }
+
+    let output = options.output_path(file);
+    write(&root.into_plist(), format, options.readable, &output)?;
+
+    Ok(0)
+}
+
+fn remove(args: &[String]) -> Result<i32, String> {
+    let (keypath, args) = args.split_first().ok_or("Missing key path.")?;
+    let options = Options::parse(args)?;
+    let file = options.single_file()?;
+    let path = split_keypath(keypath);
+
+    let (plist, format) = read(file)?;
+    let root = Container::new(plist).ok_or_else(|| no_value(keypath))?;
+    let (key, parents) = path.split_last().ok_or_else(|| no_value(keypath))?;
+    let parent = parent(&root, parents).ok_or_else(|| no_value(keypath))?;
+    let parent = parent.as_ref().unwrap_or(&root);
+    if parent.get(key).is_none() {
+        return Err(no_value(keypath))
+    }
+    match parent {
+        Container::Dict(dict) => dict.remove(key.as_str()),
+        Container::Array(array) => array.remove(key.parse::<u32>().unwrap())
+    }
+
+    let output = options.output_path(file);
+    write(&root.into_plist(), format, options.readable, &output)?;
+
+    Ok(0)
+}
+
+enum Container {
+    Dict(PlistDict),
+    Array(PlistArray)
+}
+
+impl Container {
+    fn new(plist: Plist) -> Option<Self> {
+        match plist.node_type() {
+            PlistNodeType::Dictionary => plist.dict().map(Container::Dict),
+            PlistNodeType::Array => plist.array().map(Container::Array),
+            _ => None
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<Plist> {
+        match self {
+            Container::Dict(dict) => dict.get(key),
+            Container::Array(array) => array.get(key.parse::<usize>().ok()?)
+        }
+    }
+
+    fn into_plist(self) -> Rc<Plist> {
+        match self {
+            Container::Dict(dict) => dict.into(),
+            Container::Array(array) => array.into()
+        }
+    }
+}
+
+/// Walks `path` from `root`. `Some(None)` means the path is empty and `root` itself is meant.
+fn parent(root: &Container, path: &[String]) -> Option<Option<Container>> {
+    let mut current: Option<Container> = None;
+    for key in path {
+        let child = current.as_ref().unwrap_or(root).get(key)?;
+        current = Some(Container::new(child)?);
+    }
+
+    Some(current)
+}
+
+fn split_keypath(keypath: &str) -> Vec<String> {
+    let mut path = vec![String::new()];
+    let mut chars = keypath.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    path.last_mut().unwrap().push(c);
+                }
+            }
+            '.' => path.push(String::new()),
+            _ => path.last_mut().unwrap().push(c)
+        }
+    }
+
+    path
+}
+
+fn no_value(keypath: &str) -> String {
+    format!("No value at that key path or invalid key path: {}", keypath)
+}
+
+fn parse_format(format: &str) -> Result<PlistFormat, String> {
+    match format {
+        "xml1" => Ok(PlistFormat::Xml),
+        "binary1" => Ok(PlistFormat::Binary),
+        "json" => Ok(PlistFormat::Json),
+        "openstep" => Ok(PlistFormat::OpenStep),
+        _ => Err(format!("Unknown format specifier: {}", format))
+    }
+}
+
+fn parse_value(kind: &str, value: Option<&str>) -> Result<Plist, String> {
+    let value = value.unwrap_or_default();
+    let invalid = || format!("Invalid value for {}: {}", kind, value);
+    let plist = match kind {
+        "-bool" => match value {
+            "YES" | "yes" | "true" | "1" => Plist::from(true),
+            "NO" | "no" | "false" | "0" => Plist::from(false),
+            _ => return Err(invalid())
+        },
+        "-integer" => parse_integer(value).ok_or_else(invalid)?,
+        "-float" => Plist::from(value.parse::<f64>().map_err(|_| invalid())?),
+        "-string" => Plist::from(value),
+        "-date" => Plist::from_date(parse_date(value).ok_or_else(invalid)?, 0),
+        "-data" => {
+            let data = base64_decode(value).ok_or_else(invalid)?;
+            let data = data.iter().map(|&byte| byte as i8).collect::<Vec<i8>>();
+            Plist::from(data.as_slice())
+        }
+        "-xml" => Plist::from_xml(value.to_owned()).map_err(|_| invalid())?,
+        "-json" => Plist::from_json(value.to_owned()).map_err(|_| invalid())?,
+        "-array" => Plist::from(Vec::new()),
+        "-dictionary" => Plist::from(std::collections::HashMap::new()),
+        _ => return Err(format!("Unknown type: {}", kind))
+    };
+
+    Ok(plist)
+}
+
+/// Negative values are stored signed; values above `i64::MAX` still fit as unsigned.
+fn parse_integer(value: &str) -> Option<Plist> {
+    match value.parse::<i64>() {
+        Ok(int) => Some(Plist::from_int(int)),
+        Err(_) => value.parse::<u64>().ok().map(Plist::from)
+    }
+}
+
+fn read(path: &str) -> Result<(Plist, PlistFormat), String> {
+    let data = if path == "-" {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data).map(|_| data)
+    } else {
+        fs::read(path)
+    };
+    let data = data.map_err(|error| format!("{}: {}", path, error))?;
+
+    let format = PlistFormat::detect(&data);
+    let plist = Plist::from_memory(&data).map_err(|error| format!("{}: {}", path, error))?;
+
+    Ok((plist, format))
+}
+
+fn write(plist: &Plist, format: PlistFormat, readable: bool, path: &str) -> Result<(), String> {
+    let data = plist.to_format(format, readable).map_err(|error| format!("{}: {}", path, error))?;
+
+    write_bytes(&data, path)
+}
+
+fn write_bytes(data: &[u8], path: &str) -> Result<(), String> {
+    let result = if path == "-" {
+        io::stdout().write_all(data)
+    } else {
+        fs::write(path, data)
+    };
+
+    result.map_err(|error| format!("{}: {}", path, error))
+}
+
+/// The layouts of the human-readable printers.
+#[derive(Clone, Copy, PartialEq)]
+enum Style {
+    /// `plutil -p`: `"key" => value` dictionaries and indexed arrays.
+    Plutil,
+    /// `defaults read`: old-style `key = value;` dictionaries and `( ... )` arrays.
+    Defaults
+}
+
+impl Style {
+    fn indent(self, level: usize) -> String {
+        match self {
+            Style::Plutil => "  ",
+            Style::Defaults => "    "
+        }.repeat(level)
+    }
+}
+
+fn describe(plist: Plist, style: Style) -> String {
+    let mut out = String::new();
+    describe_into(plist, style, 0, &mut out);
+
+    out
+}
+
+fn describe_into(plist: Plist, style: Style, indent: usize, out: &mut String) {
+    let padding = style.indent(indent + 1);
+    match plist.node_type() {
+        PlistNodeType::Dictionary => {
+            let dict = plist.dict().unwrap();
+            let mut entries = dict.iter().bytes().collect::<Vec<(Vec<u8>, Plist)>>();
+            entries.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+            out.push_str("{\n");
+            for (key, value) in entries {
+                let key = String::from_utf8_lossy(&key);
+                match style {
+                    Style::Plutil => out.push_str(&format!("{}\"{}\" => ", padding, key)),
+                    Style::Defaults => out.push_str(&format!("{}{} = ", padding, quote(&key)))
+                }
+                describe_into(value, style, indent + 1, out);
+                out.push_str(if style == Style::Defaults { ";\n" } else { "\n" });
+            }
+            out.push_str(&style.indent(indent));
+            out.push('}');
+        }
+        PlistNodeType::Array => {
+            let items = plist.array().unwrap().into_iter().collect::<Vec<Plist>>();
+            let count = items.len();
+            out.push_str(if style == Style::Plutil { "[\n" } else { "(\n" });
+            for (index, value) in items.into_iter().enumerate() {
+                out.push_str(&padding);
+                if style == Style::Plutil {
+                    out.push_str(&format!("{} => ", index));
+                }
+                describe_into(value, style, indent + 1, out);
+                out.push_str(if style == Style::Defaults && index + 1 < count { ",\n" } else { "\n" });
+            }
+            out.push_str(&style.indent(indent));
+            out.push(if style == Style::Plutil { ']' } else { ')' });
+        }
+        PlistNodeType::String | PlistNodeType::Key => {
+            let string = plist.as_str_lossy().unwrap_or_default();
+            match style {
+                Style::Plutil => out.push_str(&format!("\"{}\"", string)),
+                Style::Defaults => out.push_str(&quote(&string))
+            }
+        }
+        PlistNodeType::Boolean if style == Style::Defaults => {
+            out.push_str(if plist.as_bool().unwrap() { "1" } else { "0" });
+        }
+        PlistNodeType::Date => {
+            let date = format_date(plist.as_date().unwrap().0, " ", " +0000");
+            match style {
+                Style::Plutil => out.push_str(&date),
+                Style::Defaults => out.push_str(&quote(&date))
+            }
+        }
+        PlistNodeType::Data => {
+            let data = plist.as_data().unwrap();
+            let hex = |bytes: &[i8]| bytes.iter().map(|byte| format!("{:02x}", *byte as u8)).collect::<String>();
+            let bytes = if style == Style::Plutil && data.len() > 24 {
+                format!("{} ... {}", hex(&data[..8]), hex(&data[data.len() - 8..]))
+            } else {
+                hex(&data)
+            };
+            out.push_str(&format!("{{length = {}, bytes = 0x{}}}", data.len(), bytes));
+        }
+        PlistNodeType::UID if style == Style::Plutil => {
+            out.push_str(&format!("{{value = {}}}", plist.as_uid().unwrap()));
+        }
+        _ => out.push_str(&describe_raw(&plist).unwrap_or_default())
+    }
+}
+
+/// Leaves strings that only use safe characters bare and quotes the rest, like `defaults read`.
+fn quote(string: &str) -> String {
+    let bare = !string.is_empty() && string.chars().all(|c| c.is_ascii_alphanumeric() || "_$./:-".contains(c));
+    if bare {
+        return string.to_owned()
+    }
+
+    let mut quoted = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+fn describe_raw(plist: &Plist) -> Option<String> {
+    let raw = match plist.node_type() {
+        PlistNodeType::String | PlistNodeType::Key => plist.as_str_lossy()?,
+        PlistNodeType::Boolean => plist.as_bool()?.to_string(),
+        PlistNodeType::UInt => match plist.as_int() {
+            Some(int) => int.to_string(),
+            None => plist.as_uint()?.to_string()
+        },
+        PlistNodeType::Real => plist.as_real()?.to_string(),
+        PlistNodeType::Date => format_date(plist.as_date()?.0, "T", "Z"),
+        PlistNodeType::Data => {
+            let data = plist.as_data()?;
+            base64_encode(&data.iter().map(|&byte| byte as u8).collect::<Vec<u8>>())
+        }
+        PlistNodeType::UID => plist.as_uid()?.to_string(),
+        PlistNodeType::Null => "null".to_owned(),
+        _ => return None
+    };
+
+    Some(raw)
+}
+
+fn format_date(sec: i32, separator: &str, zone: &str) -> String {
+    let time = sec as i64 + MAC_EPOCH;
+    let (year, month, day) = civil_from_days(time.div_euclid(86_400));
+    let seconds = time.rem_euclid(86_400);
+
+    format!(
+        "{:04}-{:02}-{:02}{}{:02}:{:02}:{:02}{}",
+        year, month, day, separator, seconds / 3600, seconds / 60 % 60, seconds % 60, zone
+    )
+}
+
+/// Parses `YYYY-MM-DDTHH:MM:SSZ` or `YYYY-MM-DD HH:MM:SS +0000` into seconds since 2001-01-01.
+fn parse_date(date: &str) -> Option<i32> {
+    let date = date.strip_suffix('Z').or_else(|| date.strip_suffix(" +0000")).unwrap_or(date);
+    let (day, time) = date.split_once(['T', ' ']).unwrap_or((date, "00:00:00"));
+
+    let day = day.splitn(3, '-').map(|part| part.parse::<i64>().ok()).collect::<Option<Vec<i64>>>()?;
+    let time = time.splitn(3, ':').map(|part| part.parse::<i64>().ok()).collect::<Option<Vec<i64>>>()?;
+    if day.len() != 3 || time.len() != 3 || !(1..=12).contains(&day[1]) || !(1..=days_in_month(day[0], day[1])).contains(&day[2]) {
+        return None
+    }
+
+    let seconds = days_from_civil(day[0], day[1], day[2]) * 86_400 + time[0] * 3600 + time[1] * 60 + time[2];
+
+    i32::try_from(seconds - MAC_EPOCH).ok()
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+// Gregorian calendar conversions from http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (index, &byte)| bits | ((byte as u32) << (16 - 8 * index)));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(BASE64[((bits >> (18 - 6 * index)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut bits = 0u32;
+    let mut count = 0;
+    for byte in encoded.bytes().filter(|byte| !byte.is_ascii_whitespace() && *byte != b'=') {
+        let value = BASE64.iter().position(|&c| c == byte)? as u32;
+        bits = (bits << 6) | value;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            data.push((bits >> count) as u8);
+        }
+    }
+
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use plist_rs::Plist;
+    use crate::{base64_decode, base64_encode, describe, describe_raw, format_date, parse_date, parse_value, split_keypath, Style};
+
+    #[test]
+    fn keypath() {
+        assert_eq!(split_keypath("a.b.0"), ["a", "b", "0"]);
+        assert_eq!(split_keypath("com\\.apple\\.key.value"), ["com.apple.key", "value"]);
+    }
+
+    #[test]
+    fn date() {
+        assert_eq!(parse_date("2001-01-01T00:00:00Z").unwrap(), 0);
+        assert_eq!(parse_date("2000-12-31T23:59:59Z").unwrap(), -1);
+        assert_eq!(parse_date("2000-12-31 23:59:59 +0000").unwrap(), -1);
+        assert_eq!(format_date(0, "T", "Z"), "2001-01-01T00:00:00Z");
+        assert_eq!(format_date(parse_date("2024-02-29T12:34:56Z").unwrap(), "T", "Z"), "2024-02-29T12:34:56Z");
+        assert_eq!(format_date(parse_date("1970-01-01T00:00:00Z").unwrap(), " ", " +0000"), "1970-01-01 00:00:00 +0000");
+        assert!(parse_date("2024-13-01T00:00:00Z").is_none());
+        assert!(parse_date("2024-02-31T00:00:00Z").is_none());
+        assert!(parse_date("2023-02-29T00:00:00Z").is_none());
+        assert!(parse_date("1900-02-29T00:00:00Z").is_none());
+        assert!(parse_date("2000-02-29T00:00:00Z").is_some());
+        assert!(parse_date("2024-04-31T00:00:00Z").is_none());
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64_decode("Zm9vYg==").unwrap(), b"foob");
+        assert_eq!(base64_decode("Zm9v\nYmFy").unwrap(), b"foobar");
+        assert!(base64_decode("Zm9v!").is_none());
+    }
+
+    #[test]
+    fn integer() {
+        let negative = parse_value("-integer", Some("-1")).unwrap();
+        assert_eq!(negative.as_int().unwrap(), -1);
+        assert_eq!(describe_raw(&negative).unwrap(), "-1");
+
+        #[cfg(libplist_2_3)]
+        {
+            let large = parse_value("-integer", Some("18446744073709551615")).unwrap();
+            assert_eq!(describe_raw(&large).unwrap(), "18446744073709551615");
+        }
+        assert!(parse_value("-integer", Some("1.5")).is_err());
+    }
+
+    #[test]
+    #[cfg_attr(not(libplist_2_3), ignore = "libplist 2.2 reads every integer as signed")]
+    fn styles() {
+        let plist = || Plist::from(HashMap::from([
+            ("name", Plist::from("a b")),
+            ("values", Plist::from(vec![Plist::from_int(-1), Plist::from(u64::MAX), Plist::from(true)])),
+        ]));
+
+        assert_eq!(
+            describe(plist(), Style::Plutil),
+            "{\n  \"name\" => \"a b\"\n  \"values\" => [\n    0 => -1\n    1 => 18446744073709551615\n    2 => true\n  ]\n}"
+        );
+        assert_eq!(
+            describe(plist(), Style::Defaults),
+            "{\n    name = \"a b\";\n    values = (\n        -1,\n        18446744073709551615,\n        1\n    );\n}"
+        );
+    }
+}
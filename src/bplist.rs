@@ -0,0 +1,312 @@
+//! A strict structural check of `bplist00` data, run before libplist parses untrusted input.
+//!
+//! libplist trusts the trailer and offset table, follows references recursively and copies a
+//! shared object every time it is referenced. [`validate`] bounds-checks every offset, object
+//! and reference, rejects reference cycles, and computes how large the tree would be once
+//! shared references are expanded, all without recursion and in time linear in the input.
+
+use crate::parse_options::{Limit, ParseOptions};
+use crate::plist_error::PlistError;
+
+const MAGIC: &[u8] = b"bplist00";
+const TRAILER_SIZE: usize = 32;
+
+struct Trailer {
+    offset_size: usize,
+    ref_size: usize,
+    objects: usize,
+    top: usize,
+    offset_table: usize
+}
+
+/// An object's references to other objects, as indices into the offset table.
+enum Refs<'a> {
+    None,
+    Items { data: &'a [u8], size: usize }
+}
+
+impl Refs<'_> {
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        let (data, size) = match self {
+            Self::None => (&[][..], 1),
+            Self::Items { data, size } => (*data, *size)
+        };
+
+        data.chunks_exact(size).map(|chunk| read_uint(chunk) as usize)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Unvisited,
+    Visiting,
+    Done
+}
+
+/// Checks that `data` is a well-formed binary plist whose expanded tree stays within
+/// `options`. Structural problems are reported as [`PlistError::Parse`].
+pub fn validate(data: &[u8], options: &ParseOptions) -> Result<(), PlistError> {
+    options.check_limit(Limit::Size, data.len())?;
+    let trailer = read_trailer(data)?;
+
+    let offsets = &data[trailer.offset_table..trailer.offset_table + trailer.objects * trailer.offset_size];
+    let offset = |index: usize| read_uint(&offsets[index * trailer.offset_size..(index + 1) * trailer.offset_size]);
+
+    // Walk the graph depth-first from the top object with an explicit stack, computing for
+    // each object the size and depth of its expanded subtree once all its children are done.
+    let mut state = vec![State::Unvisited; trailer.objects];
+    let mut expanded = vec![0usize; trailer.objects];
+    let mut depth = vec![0usize; trailer.objects];
+    let mut stack = vec![trailer.top];
+
+    while let Some(&index) = stack.last() {
+        let object = object_at(data, &trailer, offset(index))?;
+        match state[index] {
+            State::Unvisited => {
+                state[index] = State::Visiting;
+                options.check_object(&object)?;
+                for child in object.refs.iter() {
+                    match state.get(child) {
+                        None => return Err(PlistError::Parse),
+                        Some(State::Visiting) => return Err(PlistError::Parse),
+                        Some(State::Unvisited) => stack.push(child),
+                        Some(State::Done) => {}
+                    }
+                }
+            }
+            State::Visiting => {
+                let mut size = 1usize;
+                let mut child_depth = 0usize;
+                for child in object.refs.iter() {
+                    size = size.saturating_add(expanded[child]);
+                    child_depth = child_depth.max(depth[child]);
+                }
+                expanded[index] = size;
+                depth[index] = child_depth + object.container as usize;
+                options.check_limit(Limit::Objects, size)?;
+                options.check_limit(Limit::Depth, depth[index])?;
+
+                state[index] = State::Done;
+                stack.pop();
+            }
+            State::Done => {
+                stack.pop();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_trailer(data: &[u8]) -> Result<Trailer, PlistError> {
+    if data.len() < MAGIC.len() + TRAILER_SIZE || !data.starts_with(MAGIC) {
+        return Err(PlistError::Parse)
+    }
+
+    let trailer = &data[data.len() - TRAILER_SIZE..];
+    let field = |start: usize| usize::try_from(read_uint(&trailer[start..start + 8])).map_err(|_| PlistError::Parse);
+    let trailer = Trailer {
+        offset_size: trailer[6] as usize,
+        ref_size: trailer[7] as usize,
+        objects: field(8)?,
+        top: field(16)?,
+        offset_table: field(24)?
+    };
+
+    let table_end = trailer.objects.checked_mul(trailer.offset_size)
+        .and_then(|size| size.checked_add(trailer.offset_table));
+    let valid = (1..=8).contains(&trailer.offset_size)
+        && (1..=8).contains(&trailer.ref_size)
+        && trailer.top < trailer.objects
+        && trailer.offset_table >= MAGIC.len()
+        && table_end.is_some_and(|end| end <= data.len() - TRAILER_SIZE);
+
+    if !valid {
+        return Err(PlistError::Parse)
+    }
+
+    Ok(trailer)
+}
+
+struct Object<'a> {
+    /// Characters of a string, or bytes of data.
+    length: usize,
+    kind: Kind,
+    container: bool,
+    refs: Refs<'a>
+}
+
+#[derive(PartialEq)]
+enum Kind {
+    Scalar,
+    String,
+    Data
+}
+
+/// Decodes the object header at `offset` and checks that the whole object lies between the
+/// magic and the offset table.
+fn object_at<'a>(data: &'a [u8], trailer: &Trailer, offset: u64) -> Result<Object<'a>, PlistError> {
+    let start = usize::try_from(offset).map_err(|_| PlistError::Parse)?;
+    if start < MAGIC.len() || start >= trailer.offset_table {
+        return Err(PlistError::Parse)
+    }
+    let body = &data[start..trailer.offset_table];
+
+    let marker = body[0];
+    let (kind, info) = (marker >> 4, (marker & 0x0f) as usize);
+    let mut position = 1;
+    let mut count = || -> Result<usize, PlistError> {
+        if info != 0x0f {
+            return Ok(info)
+        }
+        // The count follows as an integer object.
+        let int = *body.get(position).ok_or(PlistError::Parse)?;
+        let size = 1usize.checked_shl((int & 0x0f) as u32).filter(|&size| int >> 4 == 0x1 && size <= 8).ok_or(PlistError::Parse)?;
+        let bytes = body.get(position + 1..position + 1 + size).ok_or(PlistError::Parse)?;
+        position += 1 + size;
+
+        usize::try_from(read_uint(bytes)).map_err(|_| PlistError::Parse)
+    };
+
+    let (length, object_kind, container, ref_count) = match kind {
+        0x0 if matches!(info, 0x0 | 0x8 | 0x9 | 0xf) => (0, Kind::Scalar, false, 0),
+        0x1 if info <= 4 => (1 << info, Kind::Scalar, false, 0),
+        0x2 if matches!(info, 2 | 3) => (1 << info, Kind::Scalar, false, 0),
+        0x3 if info == 3 => (8, Kind::Scalar, false, 0),
+        0x4 => (count()?, Kind::Data, false, 0),
+        0x5 => (count()?, Kind::String, false, 0),
+        0x6 => (count()?, Kind::String, false, 0),
+        0x8 => (info + 1, Kind::Scalar, false, 0),
+        0xa..=0xc => (0, Kind::Scalar, true, count()?),
+        0xd => (0, Kind::Scalar, true, count()?.checked_mul(2).ok_or(PlistError::Parse)?),
+        _ => return Err(PlistError::Parse)
+    };
+
+    // Strings of type 6 are UTF-16, two bytes per character.
+    let payload = match kind {
+        0x6 => length.checked_mul(2),
+        0xa..=0xd => ref_count.checked_mul(trailer.ref_size),
+        _ => Some(length)
+    };
+    let end = payload.and_then(|payload| payload.checked_add(position)).filter(|&end| end <= body.len()).ok_or(PlistError::Parse)?;
+
+    let refs = match container {
+        true => Refs::Items { data: &body[position..end], size: trailer.ref_size },
+        false => Refs::None
+    };
+
+    Ok(Object {
+        length: if object_kind == Kind::Scalar { 0 } else { length },
+        kind: object_kind,
+        container,
+        refs
+    })
+}
+
+fn read_uint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0u64, |value, &byte| value << 8 | byte as u64)
+}
+
+impl ParseOptions {
+    fn check_object(&self, object: &Object) -> Result<(), PlistError> {
+        match object.kind {
+            Kind::String => self.check_limit(Limit::StringLength, object.length),
+            Kind::Data => self.check_limit(Limit::DataLength, object.length),
+            Kind::Scalar => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bplist::validate;
+    use crate::parse_options::{Limit, ParseOptions};
+    use crate::plist_error::PlistError;
+
+    /// Assembles a bplist from encoded objects, with 1-byte offsets and references.
+    fn bplist(objects: &[&[u8]], top: u8) -> Vec<u8> {
+        let mut data = b"bplist00".to_vec();
+        let mut offsets = Vec::new();
+        for object in objects {
+            offsets.push(data.len() as u8);
+            data.extend_from_slice(object);
+        }
+        let offset_table = data.len() as u64;
+        data.extend_from_slice(&offsets);
+
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 1, 1]);
+        data.extend_from_slice(&(objects.len() as u64).to_be_bytes());
+        data.extend_from_slice(&(top as u64).to_be_bytes());
+        data.extend_from_slice(&offset_table.to_be_bytes());
+        data
+    }
+
+    fn limit(result: Result<(), PlistError>) -> Option<Limit> {
+        match result {
+            Err(PlistError::LimitExceeded { limit, .. }) => Some(limit),
+            _ => None
+        }
+    }
+
+    #[test]
+    fn valid() {
+        // { "key": [1, "abc"] }
+        let data = bplist(&[b"\xd1\x01\x02", b"\x53key", b"\xa2\x03\x04", b"\x10\x01", b"\x53abc"], 0);
+        validate(&data, &ParseOptions::default()).unwrap();
+
+        let data = bplist(&[b"\x4f\x10\x03abc"], 0);
+        validate(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(limit(validate(&data, &ParseOptions::default().max_data_length(2))), Some(Limit::DataLength));
+    }
+
+    #[test]
+    fn malformed() {
+        let options = ParseOptions::default();
+        let data = bplist(&[b"\xa2\x01\x02", b"\x10\x01", b"\x10\x02"], 0);
+        validate(&data, &options).unwrap();
+
+        // Reference past the last object.
+        let mut bad = data.clone();
+        bad[10] = 7;
+        assert!(matches!(validate(&bad, &options), Err(PlistError::Parse)));
+        // Offset pointing into the offset table.
+        let mut bad = data.clone();
+        let table = bad.len() - 32 - 3;
+        bad[table + 1] = table as u8;
+        assert!(matches!(validate(&bad, &options), Err(PlistError::Parse)));
+        // Object running into the offset table.
+        let mut bad = data.clone();
+        bad[8] = 0xa9;
+        assert!(matches!(validate(&bad, &options), Err(PlistError::Parse)));
+        // Offset table outside the file.
+        let mut bad = data.clone();
+        let length = bad.len();
+        bad[length - 1] = 0xff;
+        assert!(matches!(validate(&bad, &options), Err(PlistError::Parse)));
+        // Truncated.
+        assert!(matches!(validate(&data[..data.len() - 1], &options), Err(PlistError::Parse)));
+        assert!(matches!(validate(b"bplist00", &options), Err(PlistError::Parse)));
+    }
+
+    #[test]
+    fn cycles() {
+        let options = ParseOptions::default();
+        assert!(matches!(validate(&bplist(&[b"\xa1\x00"], 0), &options), Err(PlistError::Parse)));
+        assert!(matches!(validate(&bplist(&[b"\xa1\x01", b"\xd1\x02\x00", b"\x50"], 0), &options), Err(PlistError::Parse)));
+
+        // Sharing without a cycle is fine.
+        validate(&bplist(&[b"\xa2\x01\x01", b"\xa1\x02", b"\x09"], 0), &options).unwrap();
+    }
+
+    #[test]
+    fn expansion() {
+        // Each array holds its successor twice, so 40 objects expand to 2^40 nodes.
+        let mut objects: Vec<Vec<u8>> = (0..39u8).map(|index| vec![0xa2, index + 1, index + 1]).collect();
+        objects.push(b"\x09".to_vec());
+        let objects = objects.iter().map(Vec::as_slice).collect::<Vec<&[u8]>>();
+        let data = bplist(&objects, 0);
+
+        assert_eq!(limit(validate(&data, &ParseOptions::default())), Some(Limit::Objects));
+        assert_eq!(limit(validate(&data, &ParseOptions::unlimited().max_depth(16))), Some(Limit::Depth));
+    }
+}
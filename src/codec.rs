@@ -0,0 +1,187 @@
+//! The framing lockdownd and most other device services use: each message is a plist preceded
+//! by its length as a 4 byte big-endian integer.
+
+use std::io::{self, ErrorKind, Read, Write};
+use crate::Plist;
+use crate::format::PlistFormat;
+use crate::plist_error::PlistError;
+
+/// Large enough for any lockdown reply, small enough that a bogus length can't exhaust memory.
+pub const DEFAULT_MAX_SIZE: u32 = 16 * 1024 * 1024;
+pub(crate) const HEADER_SIZE: usize = 4;
+
+pub struct PlistStream<T> {
+    inner: T,
+    format: PlistFormat,
+    max_size: u32
+}
+
+impl<T: Read + Write> PlistStream<T> {
+    /// Sends XML plists and accepts messages up to [`DEFAULT_MAX_SIZE`] bytes.
+    pub fn new(inner: T) -> Self {
+        PlistStream {
+            inner,
+            format: PlistFormat::Xml,
+            max_size: DEFAULT_MAX_SIZE
+        }
+    }
+
+    /// The format of sent messages. Received messages may be in any format.
+    pub fn format(mut self, format: PlistFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// The largest message, in bytes, that is sent or received.
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn send(&mut self, plist: &Plist) -> Result<(), PlistError> {
+        let message = frame(plist, self.format, self.max_size)?;
+        self.inner.write_all(&message)?;
+        self.inner.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads the next message. After an oversized length the stream can't be resynchronized,
+    /// so it should be dropped.
+    pub fn receive(&mut self) -> Result<Plist, PlistError> {
+        let mut header = [0u8; HEADER_SIZE];
+        self.inner.read_exact(&mut header)?;
+        let length = message_length(header, self.max_size)?;
+
+        let mut data = vec![0u8; length];
+        self.inner.read_exact(&mut data)?;
+
+        parse(&data)
+    }
+
+    /// Sends `request` and waits for the reply.
+    pub fn request(&mut self, request: &Plist) -> Result<Plist, PlistError> {
+        self.send(request)?;
+        self.receive()
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// Serializes `plist` and prefixes it with its length.
+pub(crate) fn frame(plist: &Plist, format: PlistFormat, max_size: u32) -> Result<Vec<u8>, PlistError> {
+    let data = plist.to_format(format, false)?;
+    let length = u32::try_from(data.len()).ok()
+        .filter(|&length| length <= max_size)
+        .ok_or_else(|| too_large(data.len(), max_size))?;
+
+    let mut message = Vec::with_capacity(HEADER_SIZE + data.len());
+    message.extend_from_slice(&length.to_be_bytes());
+    message.extend_from_slice(&data);
+
+    Ok(message)
+}
+
+/// Decodes a length prefix, refusing lengths over `max_size`.
+pub(crate) fn message_length(header: [u8; HEADER_SIZE], max_size: u32) -> Result<usize, PlistError> {
+    let length = u32::from_be_bytes(header);
+    if length > max_size {
+        return Err(too_large(length as usize, max_size))
+    }
+
+    Ok(length as usize)
+}
+
+pub(crate) fn parse(data: &[u8]) -> Result<Plist, PlistError> {
+    Plist::from_memory(data)
+}
+
+fn too_large(length: usize, max_size: u32) -> PlistError {
+    PlistError::Io(io::Error::new(
+        ErrorKind::InvalidData,
+        format!("message of {} bytes exceeds the limit of {} bytes", length, max_size)
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::{ErrorKind, Write};
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+    use crate::{Getter, Plist};
+    use crate::codec::PlistStream;
+    use crate::format::PlistFormat;
+    use crate::plist_error::PlistError;
+
+    #[test]
+    fn round_trip() {
+        let (client, server) = UnixStream::pair().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut server = PlistStream::new(server).format(PlistFormat::Binary);
+            let request = server.receive().unwrap();
+            let reply = Plist::from(HashMap::from([
+                ("Request", Plist::from(request.get("Request").unwrap().as_str().unwrap())),
+                ("Value", Plist::from(42)),
+            ]));
+            server.send(&reply).unwrap();
+        });
+
+        let mut client = PlistStream::new(client);
+        let reply = client.request(&Plist::from(HashMap::from([
+            ("Request", Plist::from("GetValue")),
+        ]))).unwrap();
+        server.join().unwrap();
+
+        assert_eq!(reply.get("Request").unwrap().as_str().unwrap(), "GetValue");
+        assert_eq!(reply.get("Value").unwrap().as_uint().unwrap(), 42);
+    }
+
+    #[test]
+    fn framing() {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let mut client = PlistStream::new(client);
+        client.send(&Plist::from("foo")).unwrap();
+
+        let mut header = [0u8; 4];
+        std::io::Read::read_exact(&mut server, &mut header).unwrap();
+        let mut body = vec![0u8; u32::from_be_bytes(header) as usize];
+        std::io::Read::read_exact(&mut server, &mut body).unwrap();
+        assert!(String::from_utf8(body).unwrap().contains("<string>foo</string>"));
+    }
+
+    #[test]
+    fn limits() {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let mut client = PlistStream::new(client).max_size(64);
+
+        let error = client.send(&Plist::from("x".repeat(100))).unwrap_err();
+        assert!(matches!(error, PlistError::Io(error) if error.kind() == ErrorKind::InvalidData));
+
+        server.write_all(&u32::MAX.to_be_bytes()).unwrap();
+        let error = client.receive().unwrap_err();
+        assert!(matches!(error, PlistError::Io(error) if error.kind() == ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn truncated() {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let mut client = PlistStream::new(client);
+        server.write_all(&[0, 0, 0, 10, b'<']).unwrap();
+        drop(server);
+
+        let error = client.receive().unwrap_err();
+        assert!(matches!(error, PlistError::Io(error) if error.kind() == ErrorKind::UnexpectedEof));
+    }
+}
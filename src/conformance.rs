@@ -0,0 +1,313 @@
+//! Decodes the fixtures in `tests/fixtures/conformance` with every parser and checks the
+//! decoded values. Each case comes as XML and as a binary plist written by another encoder;
+//! see `tests/fixtures/conformance/generate.py` for where they come from. The files in
+//! `tests/fixtures/samples` were written by Apple's tools; see `tests/fixtures/README.md`.
+
+use std::collections::HashMap;
+use crate::{Getter, Plist};
+use crate::bplist;
+use crate::format::PlistFormat;
+use crate::parse_options::ParseOptions;
+#[cfg(libplist_2_3)]
+use crate::pbxproj::PbxProject;
+use crate::plist_node_type::PlistNodeType;
+
+struct Fixture {
+    name: &'static str,
+    xml: &'static str,
+    bplist: &'static [u8]
+}
+
+macro_rules! fixture {
+    ($name:literal) => {
+        Fixture {
+            name: $name,
+            xml: include_str!(concat!("../tests/fixtures/conformance/", $name, ".xml")),
+            bplist: include_bytes!(concat!("../tests/fixtures/conformance/", $name, ".bplist"))
+        }
+    };
+}
+
+/// Parses both encodings of `fixture` with every parser, checks that they all agree and that
+/// the XML and binary writers' output reads back the same, and returns the decoded tree.
+fn decode(fixture: Fixture) -> Plist {
+    let options = ParseOptions::default();
+    bplist::validate(fixture.bplist, &options).unwrap();
+
+    let expected = Plist::from_xml(fixture.xml.to_owned()).unwrap();
+    let decoded = [
+        ("from_memory(xml)", Plist::from_memory(fixture.xml.as_bytes())),
+        ("from_xml_with", Plist::from_xml_with(fixture.xml.to_owned(), &options)),
+        ("from_memory_with(xml)", Plist::from_memory_with(fixture.xml.as_bytes(), &options)),
+        ("from_bin", Plist::from_bin(fixture.bplist)),
+        ("from_bin_with", Plist::from_bin_with(fixture.bplist, &options)),
+        ("from_memory(bplist)", Plist::from_memory(fixture.bplist)),
+        ("from_memory_with(bplist)", Plist::from_memory_with(fixture.bplist, &options))
+    ];
+    for (parser, plist) in decoded {
+        assert_eq!(plist.unwrap(), expected, "{}: {}", fixture.name, parser);
+    }
+
+    for format in [PlistFormat::Xml, PlistFormat::Binary] {
+        let data = expected.to_format(format, false).unwrap();
+        assert_eq!(Plist::from_memory(&data).unwrap(), expected, "{}: written as {:?}", fixture.name, format);
+    }
+
+    expected
+}
+
+/// Parses a binary plist from `tests/fixtures/samples` with every binary parser, checks that
+/// they all agree and that libplist's own binary output reads back the same, and returns the
+/// decoded tree.
+fn decode_sample(name: &str, bplist: &[u8]) -> Plist {
+    let options = ParseOptions::default();
+    bplist::validate(bplist, &options).unwrap();
+
+    let expected = Plist::from_bin(bplist).unwrap();
+    let decoded = [
+        ("from_bin_with", Plist::from_bin_with(bplist, &options)),
+        ("from_memory", Plist::from_memory(bplist)),
+        ("from_memory_with", Plist::from_memory_with(bplist, &options))
+    ];
+    for (parser, plist) in decoded {
+        assert_eq!(plist.unwrap(), expected, "{}: {}", name, parser);
+    }
+
+    let data = expected.bin().unwrap();
+    assert_eq!(Plist::from_bin(&data).unwrap(), expected, "{}: written by libplist", name);
+
+    expected
+}
+
+#[test]
+fn empty_containers() {
+    let plist = decode(fixture!("empty_containers"));
+
+    assert_eq!(plist.get("array").unwrap(), Plist::from(Vec::new()));
+    assert_eq!(plist.get("dict").unwrap(), Plist::from(HashMap::new()));
+    assert_eq!(plist.get("string").unwrap().as_str().unwrap(), "");
+    assert_eq!(plist.get("data").unwrap().as_data().unwrap(), Vec::<i8>::new());
+    assert_eq!(plist.get("nested").unwrap(), Plist::from(vec![Plist::from(Vec::new()), Plist::from(HashMap::new())]));
+}
+
+#[test]
+fn integers() {
+    let plist = decode(fixture!("integers"));
+
+    let expected = [
+        ("zero", 0),
+        ("one_byte", 255),
+        ("two_bytes", 65535),
+        ("four_bytes", u32::MAX as u64),
+        ("eight_bytes", 1 << 32),
+        ("max_i64", i64::MAX as u64),
+        ("max_u64", u64::MAX),
+        ("minus_one", -1i64 as u64),
+        ("min_i64", i64::MIN as u64)
+    ];
+    for (key, value) in expected {
+        let item = plist.get(key).unwrap();
+        assert_eq!(item.node_type(), PlistNodeType::UInt, "{}", key);
+        assert_eq!(item.as_uint().unwrap(), value, "{}", key);
+    }
+}
+
+#[test]
+fn unicode_keys() {
+    let plist = decode(fixture!("unicode_keys"));
+
+    for (key, value) in [("caf\u{e9}", 1), ("\u{65e5}\u{672c}\u{8a9e}", 2), ("\u{1f600}", 3), ("", 4), ("plain", 5)] {
+        assert_eq!(plist.get(key).unwrap().as_uint().unwrap(), value, "{:?}", key);
+    }
+    assert_eq!(plist.dict().unwrap().len(), 5);
+}
+
+#[test]
+fn utf16_strings() {
+    let fixture = fixture!("utf16_strings");
+    // Strings outside ASCII are stored as UTF-16 (marker 0x6_).
+    assert!(fixture.bplist.windows(2).any(|bytes| bytes == b"\x64\x00"));
+    let plist = decode(fixture);
+
+    let expected = [
+        ("ascii", "plain"),
+        ("latin", "caf\u{e9}"),
+        ("cjk", "\u{65e5}\u{672c}\u{8a9e}"),
+        ("emoji", "\u{1f4f1} phone"),
+        ("mixed", "na\u{ef}ve \u{2014} \u{fc}")
+    ];
+    for (key, value) in expected {
+        assert_eq!(plist.get(key).unwrap().as_str().unwrap(), value, "{}", key);
+    }
+}
+
+#[test]
+fn dates() {
+    let plist = decode(fixture!("dates"));
+
+    // Seconds since 2001-01-01T00:00:00Z.
+    let expected = [
+        ("reference", 0),
+        ("unix_epoch", -978307200),
+        ("before_2001", -1595160000),
+        ("after_2001", 730902645)
+    ];
+    for (key, value) in expected {
+        assert_eq!(plist.get(key).unwrap().as_date().unwrap(), (value, 0), "{}", key);
+    }
+}
+
+#[test]
+fn reals_and_ints() {
+    let plist = decode(fixture!("reals_and_ints"));
+
+    let int = plist.get("int_one").unwrap();
+    assert_eq!(int.node_type(), PlistNodeType::UInt);
+    assert_eq!(int.as_uint().unwrap(), 1);
+
+    let expected = [
+        ("real_one", 1.0),
+        ("real_zero", 0.0),
+        ("half", 0.5),
+        ("negative", -2.5),
+        ("pi", std::f64::consts::PI),
+        ("huge", 1e300),
+        ("tiny", 5e-324)
+    ];
+    for (key, value) in expected {
+        let real = plist.get(key).unwrap();
+        assert_eq!(real.node_type(), PlistNodeType::Real, "{}", key);
+        assert_eq!(real.as_real().unwrap().to_bits(), f64::to_bits(value), "{}", key);
+    }
+}
+
+#[test]
+fn nested_data() {
+    let plist = decode(fixture!("nested_data"));
+
+    let data = |bytes: &[u8]| Plist::from(bytes.iter().map(|&byte| byte as i8).collect::<Vec<i8>>().as_slice());
+    let expected = Plist::from(vec![
+        Plist::from(HashMap::from([("blob", data(&(0..16).collect::<Vec<u8>>()))])),
+        Plist::from(vec![data(b"\x00\xff"), Plist::from(vec![data(b"")])])
+    ]);
+    assert_eq!(plist.get("items").unwrap(), expected);
+
+    let expected = Plist::from(vec![data(b"same"), data(b"same"), Plist::from("same"), Plist::from("same")]);
+    assert_eq!(plist.get("shared").unwrap(), expected);
+}
+
+#[test]
+fn sample_binary() {
+    let plist = decode_sample("binary.plist", include_bytes!("../tests/fixtures/samples/binary.plist"));
+
+    assert_eq!(plist.get("Author").unwrap().as_str().unwrap(), "William Shakespeare");
+    assert_eq!(plist.get("Blank").unwrap().as_str().unwrap(), "");
+    // 1981-05-16T11:32:06Z.
+    assert_eq!(plist.get("Birthdate").unwrap().as_date().unwrap(), (-619446474, 0));
+    assert_eq!(plist.get("Death").unwrap().as_int().unwrap(), 1564);
+    assert_eq!(plist.get("SmallestNumber").unwrap().as_int().unwrap(), i64::MIN);
+    assert_eq!(plist.get("BiggestNumber").unwrap().as_uint().unwrap(), u64::MAX);
+    #[cfg(libplist_2_3)]
+    assert_eq!(plist.get("BiggestNumber").unwrap().as_int(), None);
+    assert_eq!(plist.get("Height").unwrap().as_real().unwrap().to_bits(), 1.6f64.to_bits());
+    assert!(plist.get("IsTrue").unwrap().as_bool().unwrap());
+    assert!(!plist.get("IsNotFalse").unwrap().as_bool().unwrap());
+    assert_eq!(plist.get("EmptyArray").unwrap(), Plist::from(Vec::new()));
+    assert_eq!(plist.get("EmptyDictionary").unwrap(), Plist::from(HashMap::new()));
+
+    let lines = plist.get("Lines").unwrap();
+    assert_eq!(lines.get(0).unwrap().as_str().unwrap(), "It is a tale told by an idiot,     ");
+    assert_eq!(lines.get(1).unwrap().as_str().unwrap(), "Full of sound and fury, signifying nothing.");
+    assert_eq!(lines.get(2), None);
+
+    let data = [0, 0, 0, 190, 0, 0, 0, 3, 0, 0, 0, 30, 0, 0, 0].map(|byte: u8| byte as i8);
+    assert_eq!(plist.get("Data").unwrap().as_data().unwrap(), data);
+    assert_eq!(plist.dict().unwrap().len(), 13);
+}
+
+#[test]
+fn sample_keyed_archive() {
+    let bplist = include_bytes!("../tests/fixtures/samples/binary_NSKeyedArchiver.plist");
+    let plist = decode_sample("binary_NSKeyedArchiver.plist", bplist);
+
+    assert_eq!(plist.get("$archiver").unwrap().as_str().unwrap(), "NSKeyedArchiver");
+    assert_eq!(plist.get("$version").unwrap().as_uint().unwrap(), 100000);
+
+    let root = plist.get("$top").unwrap().get("foundItems").unwrap();
+    assert_eq!(root.node_type(), PlistNodeType::UID);
+    assert_eq!(root.as_uid().unwrap(), 1);
+
+    let objects = plist.get("$objects").unwrap();
+    assert_eq!(objects.get(0).unwrap().as_str().unwrap(), "$null");
+    let index_set = objects.get(1).unwrap();
+    assert_eq!(index_set.get("NSRangeCount").unwrap().as_uint().unwrap(), 42);
+    assert_eq!(index_set.get("NSRangeData").unwrap().as_uid().unwrap(), 2);
+    let class = index_set.get("$class").unwrap().as_uid().unwrap();
+    assert_eq!(objects.get(class as usize).unwrap().get("$classname").unwrap().as_str().unwrap(), "NSMutableIndexSet");
+    assert_eq!(objects.get(5), None);
+}
+
+#[test]
+fn sample_utf16_strings() {
+    let plist = decode_sample("utf16_bplist.plist", include_bytes!("../tests/fixtures/samples/utf16_bplist.plist"));
+
+    assert_eq!(plist.get("name").unwrap().as_str().unwrap(), "\u{2605} or better");
+    let text = plist.get("longText").unwrap().as_str().unwrap();
+    assert!(text.starts_with("The sun was shining on the sea, \nShining with all his might: \n"));
+    assert!(text.ends_with("Dreaming as the summer die.\n\u{2605}"));
+    assert_eq!(text.len(), 643);
+}
+
+#[test]
+fn sample_animals() {
+    let xml = include_str!("../tests/fixtures/samples/xml-animals.plist");
+    let plist = Plist::from_xml(xml.to_owned()).unwrap();
+
+    let parsed = [
+        #[cfg(libplist_2_3)]
+        ("from_openstep", Plist::from_openstep(include_str!("../tests/fixtures/samples/ascii-animals.plist").to_owned())),
+        #[cfg(libplist_2_3)]
+        ("from_json", Plist::from_json(include_str!("../tests/fixtures/samples/json-animals.json").to_owned())),
+        ("from_memory(xml)", Plist::from_memory(xml.as_bytes()))
+    ];
+    for (parser, parsed) in parsed {
+        assert_eq!(parsed.unwrap(), plist, "{}", parser);
+    }
+    assert_eq!(plist.get("AnimalSounds").unwrap().get("Lisa").unwrap().as_str().unwrap(), "Why is the worm talking like a lamb?");
+
+    // plutil's layout: tabs, and keys in the order they were read. 2.2 writes XML its own way.
+    #[cfg(libplist_2_3)]
+    assert_eq!(plist.xml().unwrap(), xml);
+}
+
+#[cfg(libplist_2_3)]
+#[test]
+fn sample_pbxproj() {
+    let text = include_str!("../tests/fixtures/samples/netnewswire.pbxproj");
+    let project = PbxProject::parse(text).unwrap();
+
+    assert_eq!(project.root_object_id(), "849C64581ED37A5D003D8FC0");
+    assert_eq!(project.as_plist().get("objectVersion").unwrap().as_str().unwrap(), "46");
+    assert_eq!(project.object_ids().len(), 1282);
+
+    let targets = project.targets().unwrap();
+    let names = targets.iter().map(|target| target.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, [
+        "NetNewsWire",
+        "NetNewsWire MAS",
+        "NetNewsWireTests",
+        "NetNewsWire-iOS",
+        "Subscribe to Feed",
+        "Subscribe to Feed MAS",
+        "NetNewsWire iOS Share Extension",
+        "NetNewsWire iOS Intents Extension",
+        "NetNewsWire-iOSTests"
+    ]);
+    let app = project.target_named("NetNewsWire").unwrap().unwrap();
+    assert_eq!(app.id, "849C645F1ED37A5D003D8FC0");
+    assert_eq!(app.product_type.as_deref(), Some("com.apple.product-type.application"));
+    assert_eq!(app.build_phases.len(), 11);
+    assert_eq!(app.dependencies.len(), 15);
+
+    assert_eq!(project.to_text().unwrap(), text);
+}
@@ -0,0 +1,237 @@
+//! A directory of `<domain>.plist` files treated like the `defaults` preferences system.
+//!
+//! Every domain is a dictionary. Edited files are written back in the format they were read in;
+//! new domains are written as binary plists, like `cfprefsd` does.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::Plist;
+use crate::dict::{DictGetter, DictRemove, DictSetter, PlistDict};
+use crate::format::PlistFormat;
+use crate::plist_error::PlistError;
+
+const EXTENSION: &str = "plist";
+
+pub struct Defaults {
+    dir: PathBuf
+}
+
+impl Defaults {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Defaults {
+            dir: dir.into()
+        }
+    }
+
+    pub fn path(&self, domain: &str) -> Result<PathBuf, PlistError> {
+        if domain.is_empty() || domain.starts_with('.') || domain.contains(['/', '\\']) {
+            return Err(PlistError::InvalidArg)
+        }
+
+        Ok(self.dir.join(format!("{}.{}", domain, EXTENSION)))
+    }
+
+    pub fn domains(&self) -> Result<Vec<String>, PlistError> {
+        let mut domains = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == EXTENSION) {
+                if let Some(domain) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    domains.push(domain.to_owned());
+                }
+            }
+        }
+        domains.sort();
+
+        Ok(domains)
+    }
+
+    pub fn exists(&self, domain: &str) -> Result<bool, PlistError> {
+        Ok(self.path(domain)?.is_file())
+    }
+
+    pub fn read(&self, domain: &str) -> Result<PlistDict, PlistError> {
+        let (dict, _) = self.load(domain)?;
+
+        Ok(dict)
+    }
+
+    /// Returns a copy of the value stored for `key`, or `None` if the domain has no such key.
+    pub fn read_key(&self, domain: &str, key: &str) -> Result<Option<Plist>, PlistError> {
+        let dict = self.read(domain)?;
+
+        Ok(dict.get(key).map(|value| value.copy()))
+    }
+
+    /// Sets `key`, creating the domain if it doesn't exist yet.
+    pub fn write(&self, domain: &str, key: &str, value: Plist) -> Result<(), PlistError> {
+        let (dict, format) = self.load_or_create(domain)?;
+        dict.set(key, value);
+
+        self.store(domain, dict, format)
+    }
+
+    /// Removes `key` from the domain and returns whether it was present.
+    pub fn delete_key(&self, domain: &str, key: &str) -> Result<bool, PlistError> {
+        let (dict, format) = self.load(domain)?;
+        if dict.get(key).is_none() {
+            return Ok(false)
+        }
+        dict.remove(key);
+        self.store(domain, dict, format)?;
+
+        Ok(true)
+    }
+
+    pub fn delete(&self, domain: &str) -> Result<(), PlistError> {
+        fs::remove_file(self.path(domain)?)?;
+
+        Ok(())
+    }
+
+    /// Serializes the whole domain as an XML plist.
+    pub fn export(&self, domain: &str) -> Result<String, PlistError> {
+        let dict: Rc<Plist> = self.read(domain)?.into();
+
+        dict.xml()
+    }
+
+    /// Replaces the contents of the domain with `plist`, which must be a dictionary.
+    pub fn import(&self, domain: &str, plist: Plist) -> Result<(), PlistError> {
+        let dict = plist.dict().ok_or(PlistError::Format)?;
+        let format = match self.load(domain) {
+            Ok((_, format)) => format,
+            Err(PlistError::Io(error)) if error.kind() == ErrorKind::NotFound => PlistFormat::Binary,
+            Err(error) => return Err(error)
+        };
+
+        self.store(domain, dict, format)
+    }
+
+    fn load(&self, domain: &str) -> Result<(PlistDict, PlistFormat), PlistError> {
+        let data = fs::read(self.path(domain)?)?;
+        let format = PlistFormat::detect(&data);
+        let dict = Plist::from_memory(&data)?.dict().ok_or(PlistError::Format)?;
+
+        Ok((dict, format))
+    }
+
+    fn load_or_create(&self, domain: &str) -> Result<(PlistDict, PlistFormat), PlistError> {
+        match self.load(domain) {
+            Err(PlistError::Io(error)) if error.kind() == ErrorKind::NotFound => {
+                let dict = Plist::from(HashMap::new()).dict().unwrap();
+                Ok((dict, PlistFormat::Binary))
+            }
+            result => result
+        }
+    }
+
+    /// Replaces the domain's file atomically, so readers never see a partly written plist. The
+    /// directory is synced too, so the new file survives a crash once this returns.
+    fn store(&self, domain: &str, dict: PlistDict, format: PlistFormat) -> Result<(), PlistError> {
+        let path = self.path(domain)?;
+        let plist: Rc<Plist> = dict.into();
+        let data = plist.to_format(format, true)?;
+
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let temp = self.dir.join(format!(".{}.{}-{}.tmp", domain, std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+        let result = fs::File::create(&temp)
+            .and_then(|mut file| file.write_all(&data).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temp, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result?;
+
+        #[cfg(unix)]
+        fs::File::open(&self.dir)?.sync_all()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::Plist;
+    use crate::defaults::Defaults;
+    use crate::dict::DictGetter;
+    use crate::format::PlistFormat;
+
+    fn temp_dir() -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "plist-rs-defaults-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn write_read_delete() {
+        let dir = temp_dir();
+        let defaults = Defaults::new(&dir);
+
+        defaults.write("com.example.app", "name", Plist::from("foo")).unwrap();
+        defaults.write("com.example.app", "count", Plist::from(1)).unwrap();
+        assert_eq!(defaults.domains().unwrap(), ["com.example.app"]);
+        assert_eq!(defaults.read_key("com.example.app", "name").unwrap().unwrap().as_str().unwrap(), "foo");
+        assert_eq!(defaults.read("com.example.app").unwrap().get("count").unwrap().as_uint().unwrap(), 1);
+
+        let data = fs::read(defaults.path("com.example.app").unwrap()).unwrap();
+        assert_eq!(PlistFormat::detect(&data), PlistFormat::Binary);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        assert!(defaults.delete_key("com.example.app", "name").unwrap());
+        assert!(!defaults.delete_key("com.example.app", "name").unwrap());
+        assert!(defaults.read_key("com.example.app", "name").unwrap().is_none());
+
+        defaults.delete("com.example.app").unwrap();
+        assert!(defaults.domains().unwrap().is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn preserves_format() {
+        let dir = temp_dir();
+        let defaults = Defaults::new(&dir);
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>name</key>
+	<string>foo</string>
+</dict>
+</plist>
+"#;
+        fs::write(defaults.path("com.example.xml").unwrap(), xml).unwrap();
+
+        defaults.write("com.example.xml", "enabled", Plist::from(true)).unwrap();
+        let data = fs::read(defaults.path("com.example.xml").unwrap()).unwrap();
+        assert_eq!(PlistFormat::detect(&data), PlistFormat::Xml);
+        assert!(defaults.read_key("com.example.xml", "enabled").unwrap().unwrap().as_bool().unwrap());
+
+        let exported = defaults.export("com.example.xml").unwrap();
+        defaults.import("com.example.copy", Plist::from_xml(exported).unwrap()).unwrap();
+        assert_eq!(defaults.read_key("com.example.copy", "name").unwrap().unwrap().as_str().unwrap(), "foo");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_domain() {
+        let defaults = Defaults::new(std::env::temp_dir());
+        assert!(defaults.path("../escape").is_err());
+        assert!(defaults.path("").is_err());
+    }
+}
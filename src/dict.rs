@@ -1,9 +1,11 @@
 use std::collections::HashMap;
-use std::ffi::{CStr, CString};
+use std::error::Error;
+use std::ffi::{c_void, CStr, CString};
+use std::fmt::{Display, Formatter};
 use std::os::raw::c_char;
 use std::ptr::null_mut;
 use std::rc::Rc;
-use crate::{Getter, Plist, plist_copy, plist_dict_get_item, plist_dict_get_size, plist_dict_iter, plist_dict_merge, plist_dict_new_iter, plist_dict_next_item, plist_dict_remove_item, plist_dict_set_item, plist_new_dict, plist_t};
+use crate::{Getter, Plist, plist_copy, plist_dict_get_item, plist_dict_get_size, plist_dict_iter, plist_dict_new_iter, plist_dict_next_item, plist_dict_remove_item, plist_dict_set_item, plist_mem_free, plist_new_dict, plist_t};
 use crate::plist_node_type::PlistNodeType;
 
 impl From<HashMap<&str, Plist>> for Plist {
@@ -35,6 +37,22 @@ impl Getter<&str> for Plist {
     }
 }
 
+impl Getter<&[u8]> for Plist {
+    fn get(&self, index: &[u8]) -> Option<Self> {
+        let key = CString::new(index).ok()?;
+        let key = key.as_ptr();
+        let p = unsafe {
+            plist_dict_get_item(self.as_ptr().ok()?, key)
+        };
+
+        if p.is_null() {
+            return None
+        }
+
+        Some(Plist::new_with_weak(p))
+    }
+}
+
 impl Getter<String> for Plist {
     fn get(&self, index: String) -> Option<Self> where Self: Sized {
         let key = CString::new(index).unwrap();
@@ -61,9 +79,199 @@ impl PlistDict {
     }
 
     pub fn merge(&self, source: &PlistDict) {
-        let mut p = self.inner.as_ptr().unwrap();
-        let s = source.inner.as_ptr().unwrap();
-        unsafe { plist_dict_merge(&mut p, s) }
+        self.merge_with(source, MergeOptions::shallow());
+    }
+
+    pub fn merge_with(&self, source: &PlistDict, options: MergeOptions) -> MergeReport {
+        let mut report = MergeReport::default();
+        let mut path = Vec::new();
+        self.merge_into(source, &options, &mut path, &mut report);
+
+        report
+    }
+
+    /// Turns the dictionary back into a `Plist`, copying it if an iterator still shares it.
+    pub fn into_plist(self) -> Plist {
+        Rc::try_unwrap(self.inner).unwrap_or_else(|inner| inner.copy())
+    }
+
+    pub fn iter(&self) -> PlistDictIter {
+        let mut iter: plist_dict_iter = null_mut();
+        unsafe { plist_dict_new_iter(self.inner.as_ptr().unwrap(), &mut iter) };
+
+        PlistDictIter {
+            p: Rc::clone(&self.inner),
+            iter
+        }
+    }
+
+    fn merge_into(&self, source: &PlistDict, options: &MergeOptions, path: &mut KeyPath, report: &mut MergeReport) {
+        // Keys are matched by their bytes, so ones that aren't UTF-8 still find their entry.
+        for (key, incoming) in source.iter().bytes() {
+            path.push(String::from_utf8_lossy(&key).into_owned());
+            match self.get(key.as_slice()) {
+                Some(existing) => self.merge_value(&key, existing, incoming, options, path, report),
+                None => {
+                    self.set(key.as_slice(), incoming.copy());
+                    report.push(path, MergeChangeKind::Added);
+                }
+            }
+            path.pop();
+        }
+    }
+
+    fn merge_value(&self, key: &[u8], existing: Plist, incoming: Plist, options: &MergeOptions, path: &mut KeyPath, report: &mut MergeReport) {
+        let existing_type = existing.node_type();
+        let incoming_type = incoming.node_type();
+
+        if options.recursive && existing_type == PlistNodeType::Dictionary && incoming_type == PlistNodeType::Dictionary {
+            let target = existing.dict().unwrap();
+            let source = incoming.dict().unwrap();
+            target.merge_into(&source, options, path, report);
+            return
+        }
+
+        // Concat appends even an array equal to the existing one; otherwise equal values
+        // aren't a conflict.
+        let arrays = existing_type == PlistNodeType::Array && incoming_type == PlistNodeType::Array;
+        if existing == incoming && !(arrays && options.arrays == ArrayMergeStrategy::Concat) {
+            return
+        }
+
+        let resolution = match &options.resolver {
+            Some(resolver) => resolver(path.as_slice(), &existing, &incoming),
+            None => MergeResolution::TakeIncoming
+        };
+
+        match resolution {
+            MergeResolution::KeepExisting => report.push(path, MergeChangeKind::Kept),
+            MergeResolution::TakeIncoming if arrays => self.merge_array(key, existing, incoming, options, path, report),
+            MergeResolution::TakeIncoming => {
+                self.set(key, incoming.copy());
+                report.push(path, MergeChangeKind::Replaced);
+            }
+            MergeResolution::Value(value) => {
+                self.set(key, value);
+                report.push(path, MergeChangeKind::Replaced);
+            }
+        }
+    }
+
+    fn merge_array(&self, key: &[u8], existing: Plist, incoming: Plist, options: &MergeOptions, path: &mut KeyPath, report: &mut MergeReport) {
+        match options.arrays {
+            ArrayMergeStrategy::Replace => {
+                self.set(key, incoming.copy());
+                report.push(path, MergeChangeKind::Replaced);
+            }
+            ArrayMergeStrategy::Concat | ArrayMergeStrategy::Union => {
+                let union = options.arrays == ArrayMergeStrategy::Union;
+                let target = existing.array().unwrap();
+                let source = incoming.array().unwrap();
+                let mut appended = false;
+                for index in 0..source.len() {
+                    let item = source.get(index).unwrap();
+                    if union && (0..target.len()).any(|i| target.get(i).as_ref() == Some(&item)) {
+                        continue
+                    }
+                    target.append(item.copy());
+                    appended = true;
+                }
+                if appended {
+                    report.push(path, MergeChangeKind::Extended);
+                }
+            }
+        }
+    }
+}
+
+/// The keys leading to an entry. Keys that aren't valid UTF-8 have U+FFFD in place of the
+/// invalid bytes.
+pub type KeyPath = Vec<String>;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ArrayMergeStrategy {
+    Replace,
+    Concat,
+    Union
+}
+
+pub enum MergeResolution {
+    KeepExisting,
+    TakeIncoming,
+    Value(Plist)
+}
+
+pub type MergeResolver = dyn Fn(&[String], &Plist, &Plist) -> MergeResolution;
+
+pub struct MergeOptions {
+    recursive: bool,
+    arrays: ArrayMergeStrategy,
+    resolver: Option<Box<MergeResolver>>
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            recursive: true,
+            arrays: ArrayMergeStrategy::Replace,
+            resolver: None
+        }
+    }
+}
+
+impl MergeOptions {
+    pub fn shallow() -> Self {
+        MergeOptions {
+            recursive: false,
+            ..Default::default()
+        }
+    }
+
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    pub fn arrays(mut self, strategy: ArrayMergeStrategy) -> Self {
+        self.arrays = strategy;
+        self
+    }
+
+    pub fn resolver<F>(mut self, resolver: F) -> Self where F: Fn(&[String], &Plist, &Plist) -> MergeResolution + 'static {
+        self.resolver = Some(Box::new(resolver));
+        self
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum MergeChangeKind {
+    Added,
+    Replaced,
+    Kept,
+    Extended
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct MergeChange {
+    pub path: KeyPath,
+    pub kind: MergeChangeKind
+}
+
+#[derive(Debug, Default)]
+pub struct MergeReport {
+    pub changes: Vec<MergeChange>
+}
+
+impl MergeReport {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn push(&mut self, path: &[String], kind: MergeChangeKind) {
+        self.changes.push(MergeChange {
+            path: path.to_vec(),
+            kind
+        })
     }
 }
 
@@ -89,6 +297,15 @@ impl DictSetter<&str> for PlistDict {
     }
 }
 
+impl DictSetter<&[u8]> for PlistDict {
+    fn set(&self, key: &[u8], mut value: Plist) {
+        let key = CString::new(key).unwrap();
+        let key = key.as_ptr();
+        unsafe { plist_dict_set_item(self.inner.as_ptr().unwrap(), key, value.as_ptr().unwrap()) }
+        value.unowned_ptr();
+    }
+}
+
 pub trait DictGetter<T> {
     fn get(&self, key: T) -> Option<Plist>;
 }
@@ -105,6 +322,12 @@ impl DictGetter<&str> for PlistDict {
     }
 }
 
+impl DictGetter<&[u8]> for PlistDict {
+    fn get(&self, key: &[u8]) -> Option<Plist> {
+        self.inner.get(key)
+    }
+}
+
 pub trait DictRemove<T> {
     fn remove(&self, key: T);
 }
@@ -125,43 +348,82 @@ impl DictRemove<&str> for PlistDict {
     }
 }
 
+impl DictRemove<&[u8]> for PlistDict {
+    fn remove(&self, key: &[u8]) {
+        let key = CString::new(key).unwrap();
+        let key = key.as_ptr();
+        unsafe { plist_dict_remove_item(self.inner.as_ptr().unwrap(), key) }
+    }
+}
+
+/// A dictionary entry whose key isn't valid UTF-8, as `PlistDictIter` yields it.
+#[derive(Debug)]
+pub struct InvalidKey {
+    pub key: Vec<u8>,
+    pub value: Plist
+}
+
+impl Display for InvalidKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The key {:?} is not valid UTF-8", String::from_utf8_lossy(&self.key))
+    }
+}
+
+impl Error for InvalidKey {}
+
+/// Yields an `InvalidKey` for keys that aren't valid UTF-8; `next_bytes` and `bytes` give every
+/// key as bytes instead.
 pub struct PlistDictIter {
     p: Rc<Plist>,
     iter: plist_dict_iter
 }
 
-impl Iterator for PlistDictIter {
-    type Item = (String, Plist);
-
-    fn next(&mut self) -> Option<Self::Item> {
+impl PlistDictIter {
+    /// The next entry, with the key's bytes as they are.
+    pub fn next_bytes(&mut self) -> Option<(Vec<u8>, Plist)> {
         let mut key: *mut c_char = null_mut();
         let mut value: plist_t = null_mut();
         unsafe { plist_dict_next_item(self.p.as_ptr().ok()?, self.iter, &mut key, &mut value) };
 
-        if key.is_null() || value.is_null() {
+        if key.is_null() {
             return None
         }
 
-        let key = unsafe { CStr::from_ptr(key).to_str().unwrap() };
-        let key = key.to_owned();
-        let value = Plist::new_with_weak(value);
+        let bytes = unsafe { CStr::from_ptr(key) }.to_bytes().to_vec();
+        unsafe { plist_mem_free(key as *mut c_void) };
+        if value.is_null() {
+            return None
+        }
+
+        Some((bytes, Plist::new_with_weak(value)))
+    }
 
-        Some((key, value))
+    /// The remaining entries, with the keys' bytes as they are.
+    pub fn bytes(mut self) -> impl Iterator<Item = (Vec<u8>, Plist)> {
+        std::iter::from_fn(move || self.next_bytes())
+    }
+}
+
+impl Iterator for PlistDictIter {
+    type Item = Result<(String, Plist), InvalidKey>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.next_bytes()?;
+        let entry = match String::from_utf8(key) {
+            Ok(key) => Ok((key, value)),
+            Err(error) => Err(InvalidKey { key: error.into_bytes(), value })
+        };
+
+        Some(entry)
     }
 }
 
 impl IntoIterator for PlistDict {
-    type Item = (String, Plist);
+    type Item = Result<(String, Plist), InvalidKey>;
     type IntoIter = PlistDictIter;
 
     fn into_iter(self) -> Self::IntoIter {
-        let mut iter: plist_dict_iter = null_mut();
-        unsafe { plist_dict_new_iter(self.inner.as_ptr().unwrap(), &mut iter) };
-
-        PlistDictIter {
-            p: Rc::clone(&self.inner.clone()),
-            iter
-        }
+        self.iter()
     }
 }
 
@@ -187,7 +449,7 @@ impl Plist {
 mod tests {
     use std::collections::HashMap;
     use crate::{Getter, Plist};
-    use crate::dict::{DictGetter, DictRemove, DictSetter};
+    use crate::dict::{ArrayMergeStrategy, DictGetter, DictRemove, DictSetter, MergeChange, MergeChangeKind, MergeOptions, MergeResolution};
 
     #[test]
     fn new_test() {
@@ -251,9 +513,161 @@ mod tests {
             dict.set(key, Plist::from(value));
         }
 
-        for (index, (key, value)) in dict.into_iter().enumerate() {
+        for (index, entry) in dict.into_iter().enumerate() {
+            let (key, value) = entry.unwrap();
             assert_eq!(key, keys[index]);
             assert_eq!(value.as_uint().unwrap(), keyValues[keys[index]]);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn merge() {
+        let target = Plist::from(HashMap::from([
+            ("key1", Plist::from(0)),
+            ("key2", Plist::from(1)),
+        ])).dict().unwrap();
+        let source = Plist::from(HashMap::from([
+            ("key2", Plist::from(2)),
+            ("key3", Plist::from(3)),
+        ])).dict().unwrap();
+        target.merge(&source);
+
+        assert_eq!(target.len(), 3);
+        assert_eq!(target.get("key2").unwrap().as_uint().unwrap(), 2);
+        assert_eq!(target.get("key3").unwrap().as_uint().unwrap(), 3);
+    }
+
+    #[test]
+    fn merge_with_recursive() {
+        let target = Plist::from(HashMap::from([
+            ("nested", Plist::from(HashMap::from([
+                ("key1", Plist::from(0)),
+                ("key2", Plist::from(1)),
+            ]))),
+        ])).dict().unwrap();
+        let source = Plist::from(HashMap::from([
+            ("nested", Plist::from(HashMap::from([
+                ("key2", Plist::from(2)),
+                ("key3", Plist::from(3)),
+            ]))),
+        ])).dict().unwrap();
+        let report = target.merge_with(&source, MergeOptions::default());
+
+        let nested = target.get("nested").unwrap();
+        assert_eq!(nested.get("key1").unwrap().as_uint().unwrap(), 0);
+        assert_eq!(nested.get("key2").unwrap().as_uint().unwrap(), 2);
+        assert_eq!(nested.get("key3").unwrap().as_uint().unwrap(), 3);
+        assert_eq!(report.changes.len(), 2);
+        assert!(report.changes.iter().any(|change| change.path == ["nested", "key2"] && change.kind == MergeChangeKind::Replaced));
+        assert!(report.changes.iter().any(|change| change.path == ["nested", "key3"] && change.kind == MergeChangeKind::Added));
+    }
+
+    #[test]
+    fn merge_with_arrays() {
+        let target = Plist::from(HashMap::from([
+            ("values", Plist::from(vec![Plist::from(0), Plist::from(1)])),
+        ])).dict().unwrap();
+        let source = Plist::from(HashMap::from([
+            ("values", Plist::from(vec![Plist::from(1), Plist::from(2)])),
+        ])).dict().unwrap();
+
+        target.merge_with(&source, MergeOptions::default().arrays(ArrayMergeStrategy::Union));
+        let values = target.get("values").unwrap().array().unwrap();
+        assert_eq!(values.len(), 3);
+        assert_eq!(values.get(2).unwrap().as_uint().unwrap(), 2);
+
+        target.merge_with(&source, MergeOptions::default().arrays(ArrayMergeStrategy::Concat));
+        let values = target.get("values").unwrap().array().unwrap();
+        assert_eq!(values.len(), 5);
+
+        target.merge_with(&source, MergeOptions::default().arrays(ArrayMergeStrategy::Replace));
+        let values = target.get("values").unwrap().array().unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values.get(0).unwrap().as_uint().unwrap(), 1);
+    }
+
+    #[test]
+    fn merge_with_resolver() {
+        let target = Plist::from(HashMap::from([
+            ("keep", Plist::from("old")),
+            ("custom", Plist::from("old")),
+        ])).dict().unwrap();
+        let source = Plist::from(HashMap::from([
+            ("keep", Plist::from("new")),
+            ("custom", Plist::from("new")),
+        ])).dict().unwrap();
+        let options = MergeOptions::default().resolver(|path, _, _| {
+            match path[0].as_str() {
+                "keep" => MergeResolution::KeepExisting,
+                _ => MergeResolution::Value(Plist::from("resolved"))
+            }
+        });
+        let report = target.merge_with(&source, options);
+
+        assert_eq!(target.get("keep").unwrap().as_str().unwrap(), "old");
+        assert_eq!(target.get("custom").unwrap().as_str().unwrap(), "resolved");
+        assert!(report.changes.iter().any(|change| change.path == ["keep"] && change.kind == MergeChangeKind::Kept));
+    }
+
+    #[test]
+    fn merge_with_equal_arrays() {
+        let values = || Plist::from(HashMap::from([("values", Plist::from(vec![Plist::from(1), Plist::from(2)]))]));
+        let target = values().dict().unwrap();
+        let source = values().dict().unwrap();
+
+        let report = target.merge_with(&source, MergeOptions::default().arrays(ArrayMergeStrategy::Union));
+        assert!(report.is_empty());
+        let report = target.merge_with(&source, MergeOptions::default());
+        assert!(report.is_empty());
+
+        let report = target.merge_with(&source, MergeOptions::default().arrays(ArrayMergeStrategy::Concat));
+        let values = target.get("values").unwrap().array().unwrap();
+        assert_eq!(values.len(), 4);
+        assert_eq!(values.get(3).unwrap().as_uint().unwrap(), 2);
+        assert_eq!(report.changes[0].kind, MergeChangeKind::Extended);
+    }
+
+    #[test]
+    fn merge_with_resolver_conflicts() {
+        let target = Plist::from(HashMap::from([
+            ("values", Plist::from(vec![Plist::from(0)])),
+            ("nested", Plist::from(HashMap::from([("key", Plist::from(0))]))),
+        ])).dict().unwrap();
+        let source = Plist::from(HashMap::from([
+            ("values", Plist::from(vec![Plist::from(1)])),
+            ("nested", Plist::from(HashMap::from([("key", Plist::from(1))]))),
+        ])).dict().unwrap();
+
+        // Arrays and, when not recursing, dictionaries are conflicts for the resolver too.
+        let options = MergeOptions::shallow().arrays(ArrayMergeStrategy::Concat).resolver(|path, existing, _| {
+            match path[0].as_str() {
+                "values" => MergeResolution::TakeIncoming,
+                _ => MergeResolution::Value(existing.copy())
+            }
+        });
+        let report = target.merge_with(&source, options);
+        assert_eq!(target.get("values").unwrap().array().unwrap().len(), 2);
+        assert_eq!(target.get("nested").unwrap().get("key").unwrap().as_uint().unwrap(), 0);
+        assert_eq!(report.changes.len(), 2);
+
+        let options = MergeOptions::shallow().resolver(|_, _, _| MergeResolution::KeepExisting);
+        let report = target.merge_with(&source, options);
+        assert_eq!(target.get("values").unwrap().array().unwrap().len(), 2);
+        assert!(report.changes.iter().all(|change| change.kind == MergeChangeKind::Kept));
+        assert_eq!(report.changes.len(), 2);
+    }
+
+    #[test]
+    fn merge_invalid_utf8_keys() {
+        let target = Plist::from_memory(b"<plist><dict><key>caf\xe9</key><integer>0</integer></dict></plist>").unwrap().dict().unwrap();
+        let source = Plist::from_memory(b"<plist><dict><key>caf\xe9</key><integer>1</integer></dict></plist>").unwrap().dict().unwrap();
+
+        let report = target.merge_with(&source, MergeOptions::default());
+        assert_eq!(target.len(), 1);
+        assert_eq!(target.get(b"caf\xe9".as_slice()).unwrap().as_uint().unwrap(), 1);
+        assert_eq!(report.changes, [MergeChange { path: vec!["caf\u{fffd}".to_owned()], kind: MergeChangeKind::Replaced }]);
+
+        let entries = target.iter().bytes().map(|(key, _)| key).collect::<Vec<Vec<u8>>>();
+        assert_eq!(entries, [b"caf\xe9"]);
+    }
+}
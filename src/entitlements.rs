@@ -0,0 +1,275 @@
+//! Code signing entitlements, and checking them against what a provisioning profile grants.
+
+use crate::{Getter, Plist};
+use crate::dict::PlistDict;
+use crate::model::{self, ModelError};
+use crate::plist_node_type::PlistNodeType;
+
+pub const APPLICATION_IDENTIFIER: &str = "application-identifier";
+pub const TEAM_IDENTIFIER: &str = "com.apple.developer.team-identifier";
+pub const KEYCHAIN_ACCESS_GROUPS: &str = "keychain-access-groups";
+pub const GET_TASK_ALLOW: &str = "get-task-allow";
+pub const APS_ENVIRONMENT: &str = "aps-environment";
+pub const APP_SANDBOX: &str = "com.apple.security.app-sandbox";
+pub const APPLICATION_GROUPS: &str = "com.apple.security.application-groups";
+
+const SECURITY_PREFIX: &str = "com.apple.security.";
+const TEMPORARY_EXCEPTION_PREFIX: &str = "com.apple.security.temporary-exception.";
+
+const STRING_KEYS: [&str; 4] = [APPLICATION_IDENTIFIER, TEAM_IDENTIFIER, APS_ENVIRONMENT, "com.apple.developer.aps-environment"];
+const STRING_ARRAY_KEYS: [&str; 4] = [KEYCHAIN_ACCESS_GROUPS, APPLICATION_GROUPS, "com.apple.developer.associated-domains", "com.apple.developer.icloud-container-identifiers"];
+
+#[derive(Debug, PartialEq)]
+pub struct Entitlements {
+    plist: Plist
+}
+
+enum Expected {
+    String,
+    Strings,
+    Boolean
+}
+
+impl Entitlements {
+    /// Reads an entitlements dictionary, checking that the well-known keys hold the right type.
+    pub fn from_plist(plist: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(plist)?;
+        for entry in dict.iter() {
+            let (key, _) = entry.map_err(model::invalid_key)?;
+            match expected_type(&key) {
+                Some(Expected::String) => { model::get_string(&dict, &key)?; }
+                Some(Expected::Strings) => { model::get_strings(&dict, &key)?; }
+                Some(Expected::Boolean) => { model::get_bool(&dict, &key)?; }
+                None => {}
+            }
+        }
+
+        Ok(Entitlements {
+            plist: dict.into_plist()
+        })
+    }
+
+    pub fn to_plist(&self) -> Plist {
+        self.plist.copy()
+    }
+
+    /// Returns a copy of the value of any entitlement.
+    pub fn get(&self, key: &str) -> Option<Plist> {
+        self.plist.get(key).map(|value| value.copy())
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        self.entries().map(|(key, _)| key).collect()
+    }
+
+    pub fn application_identifier(&self) -> Option<String> {
+        self.string(APPLICATION_IDENTIFIER)
+    }
+
+    pub fn team_identifier(&self) -> Option<String> {
+        self.string(TEAM_IDENTIFIER)
+    }
+
+    pub fn aps_environment(&self) -> Option<String> {
+        self.string(APS_ENVIRONMENT)
+    }
+
+    pub fn keychain_access_groups(&self) -> Vec<String> {
+        self.strings(KEYCHAIN_ACCESS_GROUPS)
+    }
+
+    pub fn application_groups(&self) -> Vec<String> {
+        self.strings(APPLICATION_GROUPS)
+    }
+
+    pub fn get_task_allow(&self) -> bool {
+        self.flag(GET_TASK_ALLOW)
+    }
+
+    pub fn is_sandboxed(&self) -> bool {
+        self.flag(APP_SANDBOX)
+    }
+
+    /// Returns the boolean `com.apple.security.*` entitlements.
+    pub fn security_flags(&self) -> Vec<(String, bool)> {
+        self.entries()
+            .filter(|(key, _)| key.starts_with(SECURITY_PREFIX))
+            .filter_map(|(key, value)| value.as_bool().map(|enabled| (key, enabled)))
+            .collect()
+    }
+
+    /// Returns the entitlements requested here that `granted` does not allow.
+    ///
+    /// Granted strings may end in `*` to allow any value with that prefix, as provisioning
+    /// profiles do for `application-identifier` and `keychain-access-groups`. A requested array
+    /// is allowed when each of its items is, and a boolean set to `false` is always allowed.
+    pub fn excess(&self, granted: &Entitlements) -> Vec<String> {
+        self.entries()
+            .filter(|(key, value)| match granted.plist.get(key.as_str()) {
+                Some(allowed) => !is_allowed(value, &allowed),
+                None => value.as_bool() != Some(false)
+            })
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    pub fn is_subset_of(&self, granted: &Entitlements) -> bool {
+        self.excess(granted).is_empty()
+    }
+
+    fn dict(&self) -> PlistDict {
+        model::view(&self.plist).unwrap()
+    }
+
+    /// `from_plist` rejects keys that aren't UTF-8, so every entry is kept.
+    fn entries(&self) -> impl Iterator<Item = (String, Plist)> {
+        self.dict().iter().flatten()
+    }
+
+    fn string(&self, key: &str) -> Option<String> {
+        self.plist.get(key).and_then(|value| value.as_str())
+    }
+
+    fn strings(&self, key: &str) -> Vec<String> {
+        model::get_strings(&self.dict(), key).ok().flatten().unwrap_or_default()
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.plist.get(key).and_then(|value| value.as_bool()).unwrap_or(false)
+    }
+}
+
+fn expected_type(key: &str) -> Option<Expected> {
+    if STRING_KEYS.contains(&key) {
+        Some(Expected::String)
+    } else if STRING_ARRAY_KEYS.contains(&key) {
+        Some(Expected::Strings)
+    } else if key == GET_TASK_ALLOW || (key.starts_with(SECURITY_PREFIX) && !key.starts_with(TEMPORARY_EXCEPTION_PREFIX)) {
+        Some(Expected::Boolean)
+    } else {
+        None
+    }
+}
+
+fn is_allowed(requested: &Plist, granted: &Plist) -> bool {
+    match (requested.node_type(), granted.node_type()) {
+        (PlistNodeType::Array, _) => items(requested).iter()
+            .all(|item| item.node_type() != PlistNodeType::Array && is_allowed(item, granted)),
+        (_, PlistNodeType::Array) => items(granted).iter()
+            .any(|item| is_allowed(requested, item)),
+        (PlistNodeType::String, PlistNodeType::String) => matches(&requested.as_str().unwrap_or_default(), &granted.as_str().unwrap_or_default()),
+        (PlistNodeType::Boolean, PlistNodeType::Boolean) => !requested.as_bool().unwrap_or(true) || granted.as_bool().unwrap_or(false),
+        (PlistNodeType::Dictionary, PlistNodeType::Dictionary) => model::view(requested).unwrap().iter().bytes()
+            .all(|(key, value)| granted.get(key.as_slice()).is_some_and(|allowed| is_allowed(&value, &allowed))),
+        _ => requested == granted
+    }
+}
+
+fn items(array: &Plist) -> Vec<Plist> {
+    (0..).map_while(|index: usize| array.get(index)).collect()
+}
+
+fn matches(value: &str, pattern: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => value.starts_with(prefix),
+        None => value == pattern
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Plist;
+    use crate::entitlements::Entitlements;
+    use crate::model::ModelError;
+    use crate::plist_node_type::PlistNodeType;
+
+    fn entitlements(body: &str) -> Result<Entitlements, ModelError> {
+        let xml = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+{}
+</dict>
+</plist>
+"#, body);
+
+        Entitlements::from_plist(&Plist::from_xml(xml).unwrap())
+    }
+
+    const PROFILE: &str = r#"
+	<key>application-identifier</key>
+	<string>ABCDE12345.*</string>
+	<key>keychain-access-groups</key>
+	<array>
+		<string>ABCDE12345.*</string>
+		<string>com.apple.token</string>
+	</array>
+	<key>get-task-allow</key>
+	<true/>
+	<key>aps-environment</key>
+	<string>development</string>
+	<key>com.apple.developer.team-identifier</key>
+	<string>ABCDE12345</string>
+"#;
+
+    #[test]
+    fn accessors() {
+        let app = entitlements(r#"
+	<key>application-identifier</key>
+	<string>ABCDE12345.com.example.app</string>
+	<key>keychain-access-groups</key>
+	<array>
+		<string>ABCDE12345.com.example.app</string>
+	</array>
+	<key>com.apple.security.app-sandbox</key>
+	<true/>
+	<key>com.apple.security.network.client</key>
+	<false/>
+"#).unwrap();
+
+        assert_eq!(app.application_identifier().as_deref(), Some("ABCDE12345.com.example.app"));
+        assert_eq!(app.keychain_access_groups(), ["ABCDE12345.com.example.app"]);
+        assert!(app.is_sandboxed());
+        assert!(!app.get_task_allow());
+        assert_eq!(app.security_flags().len(), 2);
+        assert_eq!(app.keys().len(), 4);
+    }
+
+    #[test]
+    fn invalid_type() {
+        let error = entitlements("<key>get-task-allow</key><string>yes</string>").unwrap_err();
+        assert_eq!(error, ModelError::InvalidType { key: "get-task-allow".to_owned(), expected: PlistNodeType::Boolean });
+
+        let error = entitlements("<key>keychain-access-groups</key><array><integer>1</integer></array>").unwrap_err();
+        assert_eq!(error.key(), Some("keychain-access-groups.0"));
+    }
+
+    #[test]
+    fn subset() {
+        let profile = entitlements(PROFILE).unwrap();
+        let app = entitlements(r#"
+	<key>application-identifier</key>
+	<string>ABCDE12345.com.example.app</string>
+	<key>keychain-access-groups</key>
+	<array>
+		<string>ABCDE12345.com.example.shared</string>
+		<string>com.apple.token</string>
+	</array>
+	<key>get-task-allow</key>
+	<true/>
+	<key>com.apple.security.network.client</key>
+	<false/>
+"#).unwrap();
+        assert!(app.is_subset_of(&profile));
+
+        let app = entitlements(r#"
+	<key>application-identifier</key>
+	<string>OTHER12345.com.example.app</string>
+	<key>aps-environment</key>
+	<string>production</string>
+	<key>com.apple.developer.healthkit</key>
+	<true/>
+"#).unwrap();
+        assert_eq!(app.excess(&profile), ["application-identifier", "aps-environment", "com.apple.developer.healthkit"]);
+    }
+}
@@ -0,0 +1,301 @@
+use crate::Plist;
+use crate::plist_error::PlistError;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum PlistFormat {
+    Xml,
+    Binary,
+    Json,
+    OpenStep
+}
+
+impl PlistFormat {
+    /// Guesses the format of serialized data the same way `plist_from_memory` does.
+    pub fn detect(data: &[u8]) -> Self {
+        if data.starts_with(b"bplist00") {
+            return Self::Binary
+        }
+
+        let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+        let mut bytes = data.iter().copied().skip_while(u8::is_ascii_whitespace).peekable();
+        match bytes.next() {
+            Some(b'<') => match bytes.peek() {
+                Some(next) if next.is_ascii_hexdigit() => Self::OpenStep,
+                _ => Self::Xml
+            },
+            Some(b'[') => Self::Json,
+            Some(b'{') => {
+                let mut bytes = bytes.skip_while(u8::is_ascii_whitespace);
+                match bytes.next() {
+                    Some(b'}') => Self::Json,
+                    Some(b'"') => {
+                        // Skip the quoted key, then a JSON object continues with ':'.
+                        let mut escaped = false;
+                        for byte in bytes.by_ref() {
+                            match byte {
+                                _ if escaped => escaped = false,
+                                b'\\' => escaped = true,
+                                b'"' => break,
+                                _ => {}
+                            }
+                        }
+                        match bytes.find(|byte| !byte.is_ascii_whitespace()) {
+                            Some(b':') => Self::Json,
+                            _ => Self::OpenStep
+                        }
+                    }
+                    _ => Self::OpenStep
+                }
+            }
+            _ => Self::OpenStep
+        }
+    }
+}
+
+impl Plist {
+    /// Strings that aren't valid UTF-8 are written byte for byte, unlike `xml()`, `json()` and
+    /// `openstep()`, which refuse them.
+    pub fn to_format(&self, format: PlistFormat, prettify: bool) -> Result<Vec<u8>, PlistError> {
+        let data = match format {
+            PlistFormat::Xml => self.xml_bytes()?,
+            PlistFormat::Binary => self.bin()?,
+            PlistFormat::Json => self.json_bytes(prettify)?,
+            PlistFormat::OpenStep => self.openstep_bytes(prettify)?
+        };
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use proptest::collection::{hash_map, vec};
+    use proptest::prelude::*;
+    use crate::Plist;
+    #[cfg(libplist_2_3)]
+    use crate::Getter;
+    use crate::format::PlistFormat;
+    use crate::plist_error::PlistError;
+
+    #[test]
+    fn detect() {
+        assert_eq!(PlistFormat::detect(b"bplist00\x00\x01"), PlistFormat::Binary);
+        assert_eq!(PlistFormat::detect(b"  <?xml version=\"1.0\"?><plist/>"), PlistFormat::Xml);
+        assert_eq!(PlistFormat::detect(b"\xef\xbb\xbf<plist/>"), PlistFormat::Xml);
+        assert_eq!(PlistFormat::detect(b"[1, 2]"), PlistFormat::Json);
+        assert_eq!(PlistFormat::detect(b"{}"), PlistFormat::Json);
+        assert_eq!(PlistFormat::detect(b"{ \"key\" : 1 }"), PlistFormat::Json);
+        assert_eq!(PlistFormat::detect(b"{ \"key\" = 1; }"), PlistFormat::OpenStep);
+        assert_eq!(PlistFormat::detect(b"{ key = value; }"), PlistFormat::OpenStep);
+        assert_eq!(PlistFormat::detect(b"(a, b)"), PlistFormat::OpenStep);
+        assert_eq!(PlistFormat::detect(b"<0fbd77>"), PlistFormat::OpenStep);
+    }
+
+    /// A plist tree that proptest can generate, shrink and print.
+    #[derive(Debug, Clone)]
+    enum Node {
+        Boolean(bool),
+        UInt(u64),
+        Int(i64),
+        Real(f64),
+        String(String),
+        Data(Vec<u8>),
+        Date(i32),
+        Uid(u64),
+        Null,
+        Array(Vec<Node>),
+        Dictionary(HashMap<String, Node>)
+    }
+
+    impl Node {
+        fn to_plist(&self) -> Plist {
+            match self {
+                Node::Boolean(value) => Plist::from(*value),
+                Node::UInt(value) => Plist::from(*value),
+                Node::Int(value) => Plist::from_int(*value),
+                Node::Real(value) => Plist::from(*value),
+                Node::String(value) => Plist::from(value.as_str()),
+                Node::Data(value) => Plist::from(value.iter().map(|&byte| byte as i8).collect::<Vec<i8>>().as_slice()),
+                Node::Date(sec) => Plist::from_date(*sec, 0),
+                Node::Uid(value) => Plist::from_uid(*value),
+                #[cfg(libplist_2_3)]
+                Node::Null => Plist::null(),
+                // Only JSON documents hold nulls, and JSON needs libplist 2.3.
+                #[cfg(not(libplist_2_3))]
+                Node::Null => unreachable!(),
+                Node::Array(items) => Plist::from(items.iter().map(Node::to_plist).collect::<Vec<Plist>>()),
+                Node::Dictionary(entries) => Plist::from(entries.iter()
+                    .map(|(key, value)| (key.as_str(), value.to_plist()))
+                    .collect::<HashMap<&str, Plist>>())
+            }
+        }
+    }
+
+    /// Strings and keys. Text formats get printable characters only, binary anything but NUL,
+    /// which C strings can't hold.
+    fn text(format: PlistFormat) -> BoxedStrategy<String> {
+        match format {
+            PlistFormat::Binary => "[^\\x00]{0,24}".boxed(),
+            _ => "\\PC{0,24}".boxed()
+        }
+    }
+
+    fn real() -> impl Strategy<Value = f64> {
+        prop::num::f64::NORMAL | prop::num::f64::SUBNORMAL | prop::num::f64::ZERO
+    }
+
+    /// Scalars `format` stores losslessly. Dates are whole seconds, since XML drops the
+    /// fraction.
+    fn scalar(format: PlistFormat) -> BoxedStrategy<Node> {
+        let string = text(format).prop_map(Node::String);
+        let data = vec(any::<u8>(), 0..32).prop_map(Node::Data);
+        match format {
+            PlistFormat::Xml | PlistFormat::Binary => prop_oneof![
+                any::<bool>().prop_map(Node::Boolean),
+                any::<u64>().prop_map(Node::UInt),
+                any::<i64>().prop_map(Node::Int),
+                real().prop_map(Node::Real),
+                string,
+                data,
+                any::<i32>().prop_map(Node::Date),
+                any::<u32>().prop_map(|uid| Node::Uid(uid as u64))
+            ].boxed(),
+            // JSON has one number type: integral reals read back as integers, and integers are
+            // signed.
+            PlistFormat::Json => prop_oneof![
+                any::<bool>().prop_map(Node::Boolean),
+                (0..=i64::MAX as u64).prop_map(Node::UInt),
+                any::<i64>().prop_map(Node::Int),
+                real().prop_filter("integral reals read back as integers", |value| value.fract() != 0.0).prop_map(Node::Real),
+                string,
+                Just(Node::Null)
+            ].boxed(),
+            PlistFormat::OpenStep => prop_oneof![string, data].boxed()
+        }
+    }
+
+    /// Values `format` can't represent at all, which serializing must reject.
+    #[cfg(libplist_2_3)]
+    fn unsupported(format: PlistFormat) -> BoxedStrategy<Node> {
+        match format {
+            PlistFormat::Json => prop_oneof![
+                vec(any::<u8>(), 0..32).prop_map(Node::Data),
+                any::<i32>().prop_map(Node::Date),
+                any::<u32>().prop_map(|uid| Node::Uid(uid as u64))
+            ].boxed(),
+            PlistFormat::OpenStep => prop_oneof![
+                any::<bool>().prop_map(Node::Boolean),
+                any::<i32>().prop_map(Node::Date),
+                any::<u32>().prop_map(|uid| Node::Uid(uid as u64)),
+                Just(Node::Null)
+            ].boxed(),
+            PlistFormat::Xml | PlistFormat::Binary => unreachable!()
+        }
+    }
+
+    fn container(node: BoxedStrategy<Node>, key: BoxedStrategy<String>) -> impl Strategy<Value = Node> {
+        prop_oneof![
+            vec(node.clone(), 0..6).prop_map(Node::Array),
+            hash_map(key, node, 0..6).prop_map(Node::Dictionary)
+        ]
+    }
+
+    /// A tree of everything `format` stores losslessly. The root is always a container, since
+    /// not every format accepts a bare scalar.
+    fn document(format: PlistFormat) -> impl Strategy<Value = Node> {
+        let tree = scalar(format).prop_recursive(4, 48, 6, move |node| container(node, text(format)));
+        container(tree.boxed(), text(format))
+    }
+
+    /// A document with one value `format` can't represent added to its root.
+    #[cfg(libplist_2_3)]
+    fn unsupported_document(format: PlistFormat) -> impl Strategy<Value = Node> {
+        (document(format), unsupported(format), text(format)).prop_map(|(root, value, key)| match root {
+            Node::Array(mut items) => {
+                items.push(value);
+                Node::Array(items)
+            }
+            Node::Dictionary(mut entries) => {
+                entries.insert(key, value);
+                Node::Dictionary(entries)
+            }
+            _ => unreachable!()
+        })
+    }
+
+    fn parse(data: Vec<u8>, format: PlistFormat) -> Result<Plist, PlistError> {
+        match format {
+            PlistFormat::Xml => Plist::from_xml(String::from_utf8(data).unwrap()),
+            PlistFormat::Binary => Plist::from_bin(&data),
+            PlistFormat::Json => Plist::from_json(String::from_utf8(data).unwrap()),
+            PlistFormat::OpenStep => Plist::from_openstep(String::from_utf8(data).unwrap())
+        }
+    }
+
+    fn round_trip(node: &Node, format: PlistFormat, prettify: bool) -> Result<(), TestCaseError> {
+        let plist = node.to_plist();
+        let data = plist.to_format(format, prettify)
+            .map_err(|error| TestCaseError::fail(format!("serializing as {:?}: {}", format, error)))?;
+        let parsed = parse(data, format)
+            .map_err(|error| TestCaseError::fail(format!("parsing {:?}: {}", format, error)))?;
+        prop_assert_eq!(parsed, plist);
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn xml_round_trip(node in document(PlistFormat::Xml)) {
+            round_trip(&node, PlistFormat::Xml, false)?;
+        }
+
+        #[test]
+        fn bin_round_trip(node in document(PlistFormat::Binary)) {
+            round_trip(&node, PlistFormat::Binary, false)?;
+        }
+    }
+
+    #[cfg(libplist_2_3)]
+    proptest! {
+        #[test]
+        fn json_round_trip(node in document(PlistFormat::Json), prettify in any::<bool>()) {
+            round_trip(&node, PlistFormat::Json, prettify)?;
+        }
+
+        #[test]
+        fn json_unsupported(node in unsupported_document(PlistFormat::Json)) {
+            let result = node.to_plist().to_format(PlistFormat::Json, false);
+            prop_assert!(matches!(result, Err(PlistError::Format)), "{:?}", result);
+        }
+
+        #[test]
+        fn openstep_round_trip(node in document(PlistFormat::OpenStep), prettify in any::<bool>()) {
+            round_trip(&node, PlistFormat::OpenStep, prettify)?;
+        }
+
+        #[test]
+        fn openstep_unsupported(node in unsupported_document(PlistFormat::OpenStep)) {
+            let result = node.to_plist().to_format(PlistFormat::OpenStep, false);
+            prop_assert!(matches!(result, Err(PlistError::Format)), "{:?}", result);
+        }
+
+        /// OpenStep has no number type, so integers are written bare and read back as strings.
+        #[test]
+        fn openstep_integers(value in any::<u64>()) {
+            let data = Plist::from(vec![Plist::from(value)]).to_format(PlistFormat::OpenStep, false).unwrap();
+            let parsed = parse(data, PlistFormat::OpenStep).unwrap();
+            prop_assert_eq!(parsed, Plist::from(vec![Plist::from(value.to_string())]));
+        }
+
+        /// Reals are written bare too, with enough digits to read back as strings holding the
+        /// same value. Kept to values printed without an exponent.
+        #[test]
+        fn openstep_reals(value in 1e-4f64..1e15) {
+            let data = Plist::from(vec![Plist::from(value)]).to_format(PlistFormat::OpenStep, false).unwrap();
+            let parsed = parse(data, PlistFormat::OpenStep).unwrap();
+            let string = parsed.get(0usize).unwrap().as_str().unwrap();
+            prop_assert_eq!(string.parse::<f64>().unwrap().to_bits(), value.to_bits(), "{}", string);
+        }
+    }
+}
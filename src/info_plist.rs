@@ -0,0 +1,414 @@
+//! A typed view of a bundle's `Info.plist`.
+//!
+//! Keys without a field are kept as they were read and written back by [`InfoPlist::to_plist`],
+//! so editing a few fields never drops anything else from the file or reorders its keys.
+
+use crate::Plist;
+use crate::dict::{DictGetter, DictSetter, PlistDict};
+use crate::model::{self, ModelError};
+use crate::plist_node_type::PlistNodeType;
+
+const BUNDLE_IDENTIFIER: &str = "CFBundleIdentifier";
+const BUNDLE_NAME: &str = "CFBundleName";
+const BUNDLE_DISPLAY_NAME: &str = "CFBundleDisplayName";
+const BUNDLE_EXECUTABLE: &str = "CFBundleExecutable";
+const BUNDLE_PACKAGE_TYPE: &str = "CFBundlePackageType";
+const BUNDLE_SHORT_VERSION: &str = "CFBundleShortVersionString";
+const BUNDLE_VERSION: &str = "CFBundleVersion";
+const DEVELOPMENT_REGION: &str = "CFBundleDevelopmentRegion";
+const SUPPORTED_PLATFORMS: &str = "CFBundleSupportedPlatforms";
+const URL_TYPES: &str = "CFBundleURLTypes";
+const MINIMUM_SYSTEM_VERSION: &str = "LSMinimumSystemVersion";
+const MINIMUM_OS_VERSION: &str = "MinimumOSVersion";
+const DEVICE_FAMILY: &str = "UIDeviceFamily";
+const REQUIRED_DEVICE_CAPABILITIES: &str = "UIRequiredDeviceCapabilities";
+
+const URL_NAME: &str = "CFBundleURLName";
+const URL_SCHEMES: &str = "CFBundleURLSchemes";
+const URL_ROLE: &str = "CFBundleTypeRole";
+
+const KEYS: [&str; 14] = [
+    BUNDLE_IDENTIFIER, BUNDLE_NAME, BUNDLE_DISPLAY_NAME, BUNDLE_EXECUTABLE, BUNDLE_PACKAGE_TYPE,
+    BUNDLE_SHORT_VERSION, BUNDLE_VERSION, DEVELOPMENT_REGION, SUPPORTED_PLATFORMS, URL_TYPES,
+    MINIMUM_SYSTEM_VERSION, MINIMUM_OS_VERSION, DEVICE_FAMILY, REQUIRED_DEVICE_CAPABILITIES
+];
+const URL_KEYS: [&str; 3] = [URL_NAME, URL_SCHEMES, URL_ROLE];
+
+#[derive(Debug, PartialEq)]
+pub struct InfoPlist {
+    pub bundle_identifier: Option<String>,
+    pub bundle_name: Option<String>,
+    pub bundle_display_name: Option<String>,
+    pub bundle_executable: Option<String>,
+    pub bundle_package_type: Option<String>,
+    pub bundle_short_version: Option<String>,
+    pub bundle_version: Option<String>,
+    pub development_region: Option<String>,
+    pub supported_platforms: Option<Vec<String>>,
+    pub url_types: Option<Vec<UrlType>>,
+    pub minimum_system_version: Option<String>,
+    pub minimum_os_version: Option<String>,
+    pub device_family: Option<Vec<u64>>,
+    pub required_device_capabilities: Option<DeviceCapabilities>,
+    other: model::Source
+}
+
+/// `UIRequiredDeviceCapabilities` is either a list of required capabilities or a dictionary
+/// mapping each capability to whether it must be present or absent.
+#[derive(Debug, PartialEq)]
+pub enum DeviceCapabilities {
+    List(Vec<String>),
+    Map(Vec<(String, bool)>)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UrlType {
+    pub name: Option<String>,
+    pub schemes: Vec<String>,
+    pub role: Option<String>,
+    other: model::Source
+}
+
+impl Default for InfoPlist {
+    fn default() -> Self {
+        InfoPlist {
+            bundle_identifier: None,
+            bundle_name: None,
+            bundle_display_name: None,
+            bundle_executable: None,
+            bundle_package_type: None,
+            bundle_short_version: None,
+            bundle_version: None,
+            development_region: None,
+            supported_platforms: None,
+            url_types: None,
+            minimum_system_version: None,
+            minimum_os_version: None,
+            device_family: None,
+            required_device_capabilities: None,
+            other: model::Source::new(&KEYS)
+        }
+    }
+}
+
+impl InfoPlist {
+    /// Reads the known keys of `plist`. Missing keys are fine here, but a key holding the wrong
+    /// type of value is an error.
+    pub fn from_plist(plist: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(plist)?;
+
+        let url_types = match model::get_array(&dict, URL_TYPES)? {
+            Some(array) => Some(array.into_iter().enumerate()
+                .map(|(index, item)| UrlType::from_plist(&item).map_err(|error| error.within(&format!("{}.{}", URL_TYPES, index))))
+                .collect::<Result<Vec<UrlType>, ModelError>>()?),
+            None => None
+        };
+        let device_family = match model::get_array(&dict, DEVICE_FAMILY)? {
+            Some(array) => Some(array.into_iter().enumerate()
+                .map(|(index, item)| item.as_uint().ok_or_else(|| ModelError::InvalidType {
+                    key: format!("{}.{}", DEVICE_FAMILY, index),
+                    expected: PlistNodeType::UInt
+                }))
+                .collect::<Result<Vec<u64>, ModelError>>()?),
+            None => None
+        };
+
+        Ok(InfoPlist {
+            bundle_identifier: model::get_string(&dict, BUNDLE_IDENTIFIER)?,
+            bundle_name: model::get_string(&dict, BUNDLE_NAME)?,
+            bundle_display_name: model::get_string(&dict, BUNDLE_DISPLAY_NAME)?,
+            bundle_executable: model::get_string(&dict, BUNDLE_EXECUTABLE)?,
+            bundle_package_type: model::get_string(&dict, BUNDLE_PACKAGE_TYPE)?,
+            bundle_short_version: model::get_string(&dict, BUNDLE_SHORT_VERSION)?,
+            bundle_version: model::get_string(&dict, BUNDLE_VERSION)?,
+            development_region: model::get_string(&dict, DEVELOPMENT_REGION)?,
+            supported_platforms: model::get_strings(&dict, SUPPORTED_PLATFORMS)?,
+            url_types,
+            minimum_system_version: model::get_string(&dict, MINIMUM_SYSTEM_VERSION)?,
+            minimum_os_version: model::get_string(&dict, MINIMUM_OS_VERSION)?,
+            device_family,
+            required_device_capabilities: DeviceCapabilities::from_dict(&dict)?,
+            other: model::Source::read(dict, &KEYS)
+        })
+    }
+
+    /// Writes the fields over the dictionary this was read from, leaving each key in its place.
+    /// Fields set to `None` remove their key, and keys the dictionary didn't have are appended.
+    pub fn to_plist(&self) -> Plist {
+        let dict = self.other.dict_copy();
+        let string = |value: &Option<String>| value.as_deref().map(Plist::from);
+
+        model::set(&dict, BUNDLE_IDENTIFIER, string(&self.bundle_identifier));
+        model::set(&dict, BUNDLE_NAME, string(&self.bundle_name));
+        model::set(&dict, BUNDLE_DISPLAY_NAME, string(&self.bundle_display_name));
+        model::set(&dict, BUNDLE_EXECUTABLE, string(&self.bundle_executable));
+        model::set(&dict, BUNDLE_PACKAGE_TYPE, string(&self.bundle_package_type));
+        model::set(&dict, BUNDLE_SHORT_VERSION, string(&self.bundle_short_version));
+        model::set(&dict, BUNDLE_VERSION, string(&self.bundle_version));
+        model::set(&dict, DEVELOPMENT_REGION, string(&self.development_region));
+        model::set(&dict, SUPPORTED_PLATFORMS, self.supported_platforms.as_deref().map(model::strings));
+        model::set(&dict, URL_TYPES, self.url_types.as_ref().map(|url_types| {
+            Plist::from(url_types.iter().map(UrlType::to_plist).collect::<Vec<Plist>>())
+        }));
+        model::set(&dict, MINIMUM_SYSTEM_VERSION, string(&self.minimum_system_version));
+        model::set(&dict, MINIMUM_OS_VERSION, string(&self.minimum_os_version));
+        model::set(&dict, DEVICE_FAMILY, self.device_family.as_ref().map(|families| {
+            Plist::from(families.iter().map(|&family| Plist::from(family)).collect::<Vec<Plist>>())
+        }));
+        model::set(&dict, REQUIRED_DEVICE_CAPABILITIES, self.required_device_capabilities.as_ref().map(DeviceCapabilities::to_plist));
+
+        dict.into_plist()
+    }
+
+    /// Returns a copy of a key that has no field of its own.
+    pub fn get_other(&self, key: &str) -> Option<Plist> {
+        self.other.get(key)
+    }
+
+    /// Sets a key that has no field of its own. Keys that do have a field are overwritten by
+    /// the field in [`InfoPlist::to_plist`].
+    pub fn set_other(&mut self, key: &str, value: Option<Plist>) {
+        self.other.set(key, value);
+    }
+
+    /// Checks the required keys and the format of identifiers and version strings, returning
+    /// every problem found.
+    pub fn validate(&self) -> Vec<ModelError> {
+        let mut errors = Vec::new();
+
+        match &self.bundle_identifier {
+            None => errors.push(ModelError::MissingKey(BUNDLE_IDENTIFIER.to_owned())),
+            Some(identifier) if !is_bundle_identifier(identifier) => errors.push(ModelError::InvalidValue {
+                key: BUNDLE_IDENTIFIER.to_owned(),
+                reason: format!("{:?} may only contain letters, digits, '-' and '.'", identifier)
+            }),
+            _ => {}
+        }
+        if self.bundle_version.is_none() {
+            errors.push(ModelError::MissingKey(BUNDLE_VERSION.to_owned()));
+        }
+
+        let versions = [
+            (BUNDLE_VERSION, &self.bundle_version),
+            (BUNDLE_SHORT_VERSION, &self.bundle_short_version),
+            (MINIMUM_SYSTEM_VERSION, &self.minimum_system_version),
+            (MINIMUM_OS_VERSION, &self.minimum_os_version)
+        ];
+        for (key, version) in versions {
+            if let Some(version) = version {
+                if !is_version(version) {
+                    errors.push(ModelError::InvalidValue {
+                        key: key.to_owned(),
+                        reason: format!("{:?} is not one to three period-separated integers", version)
+                    });
+                }
+            }
+        }
+
+        if let Some(url_types) = &self.url_types {
+            for (index, url_type) in url_types.iter().enumerate() {
+                if url_type.schemes.is_empty() {
+                    errors.push(ModelError::MissingKey(format!("{}.{}.{}", URL_TYPES, index, URL_SCHEMES)));
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+impl UrlType {
+    pub fn new(schemes: Vec<String>) -> Self {
+        UrlType {
+            name: None,
+            schemes,
+            role: None,
+            other: model::Source::new(&URL_KEYS)
+        }
+    }
+
+    fn from_plist(plist: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(plist)?;
+
+        Ok(UrlType {
+            name: model::get_string(&dict, URL_NAME)?,
+            schemes: model::get_strings(&dict, URL_SCHEMES)?.unwrap_or_default(),
+            role: model::get_string(&dict, URL_ROLE)?,
+            other: model::Source::read(dict, &URL_KEYS)
+        })
+    }
+
+    fn to_plist(&self) -> Plist {
+        let dict = self.other.dict_copy();
+        model::set(&dict, URL_NAME, self.name.as_deref().map(Plist::from));
+        model::set(&dict, URL_SCHEMES, Some(model::strings(&self.schemes)));
+        model::set(&dict, URL_ROLE, self.role.as_deref().map(Plist::from));
+
+        dict.into_plist()
+    }
+}
+
+impl DeviceCapabilities {
+    fn from_dict(dict: &PlistDict) -> Result<Option<Self>, ModelError> {
+        let value = match dict.get(REQUIRED_DEVICE_CAPABILITIES) {
+            Some(value) => value,
+            None => return Ok(None)
+        };
+
+        match value.node_type() {
+            PlistNodeType::Array => Ok(model::get_strings(dict, REQUIRED_DEVICE_CAPABILITIES)?.map(Self::List)),
+            PlistNodeType::Dictionary => {
+                let entries = value.dict().unwrap().iter()
+                    .map(|entry| {
+                        let (key, value) = entry.map_err(|error| model::invalid_key(error).within(REQUIRED_DEVICE_CAPABILITIES))?;
+                        match value.as_bool() {
+                            Some(required) => Ok((key, required)),
+                            None => Err(ModelError::InvalidType {
+                                key: format!("{}.{}", REQUIRED_DEVICE_CAPABILITIES, key),
+                                expected: PlistNodeType::Boolean
+                            })
+                        }
+                    })
+                    .collect::<Result<Vec<(String, bool)>, ModelError>>()?;
+                Ok(Some(Self::Map(entries)))
+            }
+            _ => Err(ModelError::InvalidType { key: REQUIRED_DEVICE_CAPABILITIES.to_owned(), expected: PlistNodeType::Array })
+        }
+    }
+
+    fn to_plist(&self) -> Plist {
+        match self {
+            Self::List(capabilities) => model::strings(capabilities),
+            Self::Map(capabilities) => {
+                let dict = model::new_dict();
+                for (capability, required) in capabilities {
+                    dict.set(capability.as_str(), Plist::from(*required));
+                }
+                dict.into_plist()
+            }
+        }
+    }
+}
+
+fn is_bundle_identifier(identifier: &str) -> bool {
+    !identifier.is_empty() && identifier.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+fn is_version(version: &str) -> bool {
+    let components = version.split('.').collect::<Vec<&str>>();
+
+    components.len() <= 3 && components.iter().all(|component| !component.is_empty() && component.bytes().all(|byte| byte.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Getter, Plist};
+    use crate::info_plist::{BUNDLE_VERSION, DeviceCapabilities, InfoPlist, UrlType};
+    use crate::model::ModelError;
+    use crate::plist_node_type::PlistNodeType;
+
+    const INFO: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>CFBundleIdentifier</key>
+	<string>com.example.app</string>
+	<key>CFBundleShortVersionString</key>
+	<string>1.2</string>
+	<key>CFBundleVersion</key>
+	<string>42</string>
+	<key>CFBundleURLTypes</key>
+	<array>
+		<dict>
+			<key>CFBundleURLSchemes</key>
+			<array>
+				<string>example</string>
+			</array>
+			<key>CFBundleURLIconFile</key>
+			<string>Icon</string>
+		</dict>
+	</array>
+	<key>UIDeviceFamily</key>
+	<array>
+		<integer>1</integer>
+		<integer>2</integer>
+	</array>
+	<key>UIRequiredDeviceCapabilities</key>
+	<dict>
+		<key>arm64</key>
+		<true/>
+	</dict>
+	<key>NSCameraUsageDescription</key>
+	<string>Scanning</string>
+</dict>
+</plist>
+"#;
+
+    #[test]
+    fn round_trip() {
+        let mut info = InfoPlist::from_plist(&Plist::from_xml(INFO.to_owned()).unwrap()).unwrap();
+        assert_eq!(info.bundle_identifier.as_deref(), Some("com.example.app"));
+        assert_eq!(info.device_family, Some(vec![1, 2]));
+        assert_eq!(info.required_device_capabilities, Some(DeviceCapabilities::Map(vec![("arm64".to_owned(), true)])));
+        assert_eq!(info.url_types.as_ref().unwrap()[0].schemes, ["example"]);
+        assert!(info.validate().is_empty());
+
+        info.bundle_version = Some("43".to_owned());
+        info.device_family = None;
+        info.url_types.as_mut().unwrap().push(UrlType::new(vec!["other".to_owned()]));
+
+        let copy = InfoPlist::from_plist(&info.to_plist()).unwrap();
+        assert_eq!(copy, info);
+        assert_eq!(copy.get_other(BUNDLE_VERSION), None);
+
+        let info = copy;
+        assert_eq!(info.bundle_version.as_deref(), Some("43"));
+        assert_eq!(info.device_family, None);
+        assert_eq!(info.url_types.as_ref().unwrap().len(), 2);
+        assert_eq!(info.get_other("NSCameraUsageDescription").unwrap().as_str().unwrap(), "Scanning");
+
+        let plist = info.to_plist();
+        let url_type = plist.get("CFBundleURLTypes").unwrap().get(0).unwrap();
+        assert_eq!(url_type.get("CFBundleURLIconFile").unwrap().as_str().unwrap(), "Icon");
+    }
+
+    #[test]
+    fn key_order() {
+        let mut info = InfoPlist::from_plist(&Plist::from_xml(INFO.to_owned()).unwrap()).unwrap();
+        info.bundle_version = Some("43".to_owned());
+        info.device_family = None;
+        info.bundle_name = Some("App".to_owned());
+
+        let keys = info.to_plist().dict().unwrap().iter().map(|entry| entry.unwrap().0).collect::<Vec<String>>();
+        assert_eq!(keys, [
+            "CFBundleIdentifier", "CFBundleShortVersionString", "CFBundleVersion", "CFBundleURLTypes",
+            "UIRequiredDeviceCapabilities", "NSCameraUsageDescription", "CFBundleName"
+        ]);
+    }
+
+    #[test]
+    fn invalid_type() {
+        let plist = Plist::from_xml(INFO.replace("<string>42</string>", "<integer>42</integer>")).unwrap();
+        let error = InfoPlist::from_plist(&plist).unwrap_err();
+        assert_eq!(error, ModelError::InvalidType { key: "CFBundleVersion".to_owned(), expected: PlistNodeType::String });
+
+        let plist = Plist::from_xml(INFO.replace("<string>example</string>", "<true/>")).unwrap();
+        let error = InfoPlist::from_plist(&plist).unwrap_err();
+        assert_eq!(error.key(), Some("CFBundleURLTypes.0.CFBundleURLSchemes.0"));
+    }
+
+    #[test]
+    fn validate() {
+        let mut info = InfoPlist::default();
+        let errors = info.validate();
+        assert!(errors.contains(&ModelError::MissingKey("CFBundleIdentifier".to_owned())));
+        assert!(errors.contains(&ModelError::MissingKey("CFBundleVersion".to_owned())));
+
+        info.bundle_identifier = Some("com.example/app".to_owned());
+        info.bundle_version = Some("1.2.3.4".to_owned());
+        info.bundle_short_version = Some("1.0b1".to_owned());
+        info.minimum_os_version = Some("15.0".to_owned());
+        let keys = info.validate().iter().map(|error| error.key().unwrap().to_owned()).collect::<Vec<String>>();
+        assert_eq!(keys, ["CFBundleIdentifier", "CFBundleVersion", "CFBundleShortVersionString"]);
+    }
+}
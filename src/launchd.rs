@@ -0,0 +1,612 @@
+//! launchd job definitions, as described in launchd.plist(5).
+//!
+//! Keys without a field are kept and written back by [`LaunchdJob::to_plist`], in their
+//! original places, like [`crate::info_plist::InfoPlist`] does.
+
+use crate::Plist;
+use crate::dict::{DictGetter, PlistDict};
+use crate::model::{self, ModelError};
+use crate::plist_node_type::PlistNodeType;
+
+const PROCESS_TYPES: [&str; 4] = ["Background", "Standard", "Adaptive", "Interactive"];
+const SOCK_TYPES: [&str; 3] = ["stream", "dgram", "seqpacket"];
+const SOCK_FAMILIES: [&str; 4] = ["IPv4", "IPv6", "IPv4v6", "Unix"];
+const SOCK_PROTOCOLS: [&str; 2] = ["TCP", "UDP"];
+
+const JOB_KEYS: [&str; 24] = [
+    "Label", "Disabled", "Program", "ProgramArguments", "UserName", "GroupName", "WorkingDirectory",
+    "EnvironmentVariables", "RunAtLoad", "KeepAlive", "StartInterval", "StartCalendarInterval",
+    "WatchPaths", "QueueDirectories", "StartOnMount", "StandardInPath", "StandardOutPath",
+    "StandardErrorPath", "ThrottleInterval", "ExitTimeOut", "ProcessType", "AbandonProcessGroup",
+    "MachServices", "Sockets"
+];
+const SOCKET_KEYS: [&str; 8] = [
+    "SockType", "SockPassive", "SockNodeName", "SockServiceName", "SockFamily", "SockProtocol",
+    "SockPathName", "SockPathMode"
+];
+
+#[derive(Debug, PartialEq)]
+pub struct LaunchdJob {
+    pub label: String,
+    pub disabled: Option<bool>,
+    pub program: Option<String>,
+    pub program_arguments: Option<Vec<String>>,
+    pub user_name: Option<String>,
+    pub group_name: Option<String>,
+    pub working_directory: Option<String>,
+    pub environment_variables: Option<Vec<(String, String)>>,
+    pub run_at_load: Option<bool>,
+    pub keep_alive: Option<KeepAlive>,
+    pub start_interval: Option<u64>,
+    pub start_calendar_interval: Option<Vec<CalendarInterval>>,
+    pub watch_paths: Option<Vec<String>>,
+    pub queue_directories: Option<Vec<String>>,
+    pub start_on_mount: Option<bool>,
+    pub standard_in_path: Option<String>,
+    pub standard_out_path: Option<String>,
+    pub standard_error_path: Option<String>,
+    pub throttle_interval: Option<u64>,
+    pub exit_timeout: Option<u64>,
+    pub process_type: Option<String>,
+    pub abandon_process_group: Option<bool>,
+    pub mach_services: Option<Vec<(String, MachService)>>,
+    pub sockets: Option<Vec<(String, Vec<Socket>)>>,
+    other: model::Source
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KeepAlive {
+    Always(bool),
+    Conditions(KeepAliveConditions)
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct KeepAliveConditions {
+    pub successful_exit: Option<bool>,
+    pub network_state: Option<bool>,
+    pub crashed: Option<bool>,
+    pub after_initial_demand: Option<bool>,
+    pub path_state: Option<Vec<(String, bool)>>,
+    pub other_job_enabled: Option<Vec<(String, bool)>>
+}
+
+/// A `StartCalendarInterval` entry. Missing fields match every value, like a `*` in crontab.
+#[derive(Debug, Default, PartialEq)]
+pub struct CalendarInterval {
+    pub minute: Option<u64>,
+    pub hour: Option<u64>,
+    pub day: Option<u64>,
+    pub weekday: Option<u64>,
+    pub month: Option<u64>
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MachService {
+    Enabled(bool),
+    Options {
+        reset_at_close: Option<bool>,
+        hide_until_check_in: Option<bool>
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SocketService {
+    Name(String),
+    Port(u64)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Socket {
+    pub sock_type: Option<String>,
+    pub passive: Option<bool>,
+    pub node_name: Option<String>,
+    pub service_name: Option<SocketService>,
+    pub family: Option<String>,
+    pub protocol: Option<String>,
+    pub path_name: Option<String>,
+    pub path_mode: Option<u64>,
+    other: model::Source
+}
+
+impl LaunchdJob {
+    pub fn new(label: &str, program_arguments: Vec<String>) -> Self {
+        LaunchdJob {
+            label: label.to_owned(),
+            disabled: None,
+            program: None,
+            program_arguments: Some(program_arguments),
+            user_name: None,
+            group_name: None,
+            working_directory: None,
+            environment_variables: None,
+            run_at_load: None,
+            keep_alive: None,
+            start_interval: None,
+            start_calendar_interval: None,
+            watch_paths: None,
+            queue_directories: None,
+            start_on_mount: None,
+            standard_in_path: None,
+            standard_out_path: None,
+            standard_error_path: None,
+            throttle_interval: None,
+            exit_timeout: None,
+            process_type: None,
+            abandon_process_group: None,
+            mach_services: None,
+            sockets: None,
+            other: model::Source::new(&JOB_KEYS)
+        }
+    }
+
+    pub fn from_plist(plist: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(plist)?;
+
+        let keep_alive = match dict.get("KeepAlive") {
+            None => None,
+            Some(value) if value.node_type() == PlistNodeType::Boolean => Some(KeepAlive::Always(value.as_bool().unwrap())),
+            Some(_) => Some(KeepAlive::Conditions(KeepAliveConditions::from_dict(&model::get_dict(&dict, "KeepAlive")?.unwrap()).map_err(|error| error.within("KeepAlive"))?))
+        };
+        let start_calendar_interval = match dict.get("StartCalendarInterval") {
+            None => None,
+            Some(value) if value.node_type() == PlistNodeType::Array => model::get_items(&dict, "StartCalendarInterval", CalendarInterval::read)?,
+            Some(value) => Some(vec![CalendarInterval::read("StartCalendarInterval", &value)?])
+        };
+        let sockets = model::get_entries(&dict, "Sockets", |name, value| match value.node_type() {
+            PlistNodeType::Array => {
+                value.copy().array().unwrap().into_iter().enumerate()
+                    .map(|(index, socket)| Socket::read(&index.to_string(), &socket).map_err(|error| error.within(name)))
+                    .collect::<Result<Vec<Socket>, ModelError>>()
+            }
+            _ => Socket::read(name, value).map(|socket| vec![socket])
+        })?;
+
+        Ok(LaunchdJob {
+            label: model::require(model::get_string(&dict, "Label")?, "Label")?,
+            disabled: model::get_bool(&dict, "Disabled")?,
+            program: model::get_string(&dict, "Program")?,
+            program_arguments: model::get_strings(&dict, "ProgramArguments")?,
+            user_name: model::get_string(&dict, "UserName")?,
+            group_name: model::get_string(&dict, "GroupName")?,
+            working_directory: model::get_string(&dict, "WorkingDirectory")?,
+            environment_variables: model::get_entries(&dict, "EnvironmentVariables", model::string_value)?,
+            run_at_load: model::get_bool(&dict, "RunAtLoad")?,
+            keep_alive,
+            start_interval: model::get_uint(&dict, "StartInterval")?,
+            start_calendar_interval,
+            watch_paths: model::get_strings(&dict, "WatchPaths")?,
+            queue_directories: model::get_strings(&dict, "QueueDirectories")?,
+            start_on_mount: model::get_bool(&dict, "StartOnMount")?,
+            standard_in_path: model::get_string(&dict, "StandardInPath")?,
+            standard_out_path: model::get_string(&dict, "StandardOutPath")?,
+            standard_error_path: model::get_string(&dict, "StandardErrorPath")?,
+            throttle_interval: model::get_uint(&dict, "ThrottleInterval")?,
+            exit_timeout: model::get_uint(&dict, "ExitTimeOut")?,
+            process_type: model::get_string(&dict, "ProcessType")?,
+            abandon_process_group: model::get_bool(&dict, "AbandonProcessGroup")?,
+            mach_services: model::get_entries(&dict, "MachServices", MachService::read)?,
+            sockets,
+            other: model::Source::read(dict, &JOB_KEYS)
+        })
+    }
+
+    pub fn to_plist(&self) -> Plist {
+        let dict = self.other.dict_copy();
+        let string = |value: &Option<String>| value.as_deref().map(Plist::from);
+        let boolean = |value: &Option<bool>| value.map(Plist::from);
+        let uint = |value: &Option<u64>| value.map(Plist::from);
+
+        model::set(&dict, "Label", Some(Plist::from(self.label.as_str())));
+        model::set(&dict, "Disabled", boolean(&self.disabled));
+        model::set(&dict, "Program", string(&self.program));
+        model::set(&dict, "ProgramArguments", self.program_arguments.as_deref().map(model::strings));
+        model::set(&dict, "UserName", string(&self.user_name));
+        model::set(&dict, "GroupName", string(&self.group_name));
+        model::set(&dict, "WorkingDirectory", string(&self.working_directory));
+        model::set(&dict, "EnvironmentVariables", self.environment_variables.as_deref().map(|variables| {
+            model::entries(variables, |value| Plist::from(value.as_str()))
+        }));
+        model::set(&dict, "RunAtLoad", boolean(&self.run_at_load));
+        model::set(&dict, "KeepAlive", self.keep_alive.as_ref().map(|keep_alive| match keep_alive {
+            KeepAlive::Always(enabled) => Plist::from(*enabled),
+            KeepAlive::Conditions(conditions) => conditions.to_plist()
+        }));
+        model::set(&dict, "StartInterval", uint(&self.start_interval));
+        model::set(&dict, "StartCalendarInterval", self.start_calendar_interval.as_deref().map(|intervals| match intervals {
+            [interval] => interval.to_plist(),
+            intervals => Plist::from(intervals.iter().map(CalendarInterval::to_plist).collect::<Vec<Plist>>())
+        }));
+        model::set(&dict, "WatchPaths", self.watch_paths.as_deref().map(model::strings));
+        model::set(&dict, "QueueDirectories", self.queue_directories.as_deref().map(model::strings));
+        model::set(&dict, "StartOnMount", boolean(&self.start_on_mount));
+        model::set(&dict, "StandardInPath", string(&self.standard_in_path));
+        model::set(&dict, "StandardOutPath", string(&self.standard_out_path));
+        model::set(&dict, "StandardErrorPath", string(&self.standard_error_path));
+        model::set(&dict, "ThrottleInterval", uint(&self.throttle_interval));
+        model::set(&dict, "ExitTimeOut", uint(&self.exit_timeout));
+        model::set(&dict, "ProcessType", string(&self.process_type));
+        model::set(&dict, "AbandonProcessGroup", boolean(&self.abandon_process_group));
+        model::set(&dict, "MachServices", self.mach_services.as_deref().map(|services| model::entries(services, MachService::to_plist)));
+        model::set(&dict, "Sockets", self.sockets.as_deref().map(|sockets| model::entries(sockets, |sockets: &Vec<Socket>| match sockets.as_slice() {
+            [socket] => socket.to_plist(),
+            sockets => Plist::from(sockets.iter().map(Socket::to_plist).collect::<Vec<Plist>>())
+        })));
+
+        dict.into_plist()
+    }
+
+    /// Checks the job against the rules of launchd.plist(5), returning every problem found.
+    pub fn validate(&self) -> Vec<ModelError> {
+        let mut errors = Vec::new();
+        let mut invalid = |key: String, reason: String| errors.push(ModelError::InvalidValue { key, reason });
+
+        if self.label.is_empty() {
+            invalid("Label".to_owned(), "must not be empty".to_owned());
+        }
+        match (&self.program, &self.program_arguments) {
+            (None, None) => invalid("Program".to_owned(), "either Program or ProgramArguments is required".to_owned()),
+            (None, Some(arguments)) if arguments.is_empty() => invalid("ProgramArguments".to_owned(), "must not be empty without Program".to_owned()),
+            (Some(program), _) if program.is_empty() => invalid("Program".to_owned(), "must not be empty".to_owned()),
+            _ => {}
+        }
+        if self.start_interval == Some(0) {
+            invalid("StartInterval".to_owned(), "must be greater than zero".to_owned());
+        }
+        if let Some(process_type) = &self.process_type {
+            if !PROCESS_TYPES.contains(&process_type.as_str()) {
+                invalid("ProcessType".to_owned(), format!("{:?} is not one of {}", process_type, PROCESS_TYPES.join(", ")));
+            }
+        }
+
+        for (key, paths) in [("WatchPaths", &self.watch_paths), ("QueueDirectories", &self.queue_directories)] {
+            for (index, path) in paths.iter().flatten().enumerate() {
+                if !path.starts_with('/') {
+                    invalid(format!("{}.{}", key, index), format!("{:?} is not an absolute path", path));
+                }
+            }
+        }
+        if let Some(KeepAlive::Conditions(conditions)) = &self.keep_alive {
+            for (path, _) in conditions.path_state.iter().flatten() {
+                if !path.starts_with('/') {
+                    invalid(format!("KeepAlive.PathState.{}", path), format!("{:?} is not an absolute path", path));
+                }
+            }
+        }
+
+        for (index, interval) in self.start_calendar_interval.iter().flatten().enumerate() {
+            let ranges = [
+                ("Minute", interval.minute, 0, 59),
+                ("Hour", interval.hour, 0, 23),
+                ("Day", interval.day, 1, 31),
+                ("Weekday", interval.weekday, 0, 7),
+                ("Month", interval.month, 1, 12)
+            ];
+            for (key, value, min, max) in ranges {
+                if let Some(value) = value {
+                    if value < min || value > max {
+                        invalid(format!("StartCalendarInterval.{}.{}", index, key), format!("{} is not between {} and {}", value, min, max));
+                    }
+                }
+            }
+        }
+
+        for (name, sockets) in self.sockets.iter().flatten() {
+            for (index, socket) in sockets.iter().enumerate() {
+                let key = |field: &str| format!("Sockets.{}.{}.{}", name, index, field);
+                let choices = [
+                    ("SockType", &socket.sock_type, &SOCK_TYPES[..]),
+                    ("SockFamily", &socket.family, &SOCK_FAMILIES[..]),
+                    ("SockProtocol", &socket.protocol, &SOCK_PROTOCOLS[..])
+                ];
+                for (field, value, allowed) in choices {
+                    if let Some(value) = value {
+                        if !allowed.contains(&value.as_str()) {
+                            invalid(key(field), format!("{:?} is not one of {}", value, allowed.join(", ")));
+                        }
+                    }
+                }
+                if socket.path_name.is_some() && (socket.node_name.is_some() || socket.service_name.is_some()) {
+                    invalid(key("SockPathName"), "cannot be combined with SockNodeName or SockServiceName".to_owned());
+                }
+                if socket.path_name.is_some() && socket.family.as_deref().is_some_and(|family| family != "Unix") {
+                    invalid(key("SockFamily"), "must be Unix for a socket with SockPathName".to_owned());
+                }
+            }
+        }
+
+        errors
+    }
+
+    /// Returns a copy of a key that has no field of its own.
+    pub fn get_other(&self, key: &str) -> Option<Plist> {
+        self.other.get(key)
+    }
+
+    /// Sets a key that has no field of its own. Keys that do have a field are overwritten by
+    /// the field in [`LaunchdJob::to_plist`].
+    pub fn set_other(&mut self, key: &str, value: Option<Plist>) {
+        self.other.set(key, value);
+    }
+}
+
+impl KeepAliveConditions {
+    fn from_dict(dict: &PlistDict) -> Result<Self, ModelError> {
+        Ok(KeepAliveConditions {
+            successful_exit: model::get_bool(dict, "SuccessfulExit")?,
+            network_state: model::get_bool(dict, "NetworkState")?,
+            crashed: model::get_bool(dict, "Crashed")?,
+            after_initial_demand: model::get_bool(dict, "AfterInitialDemand")?,
+            path_state: model::get_entries(dict, "PathState", model::bool_value)?,
+            other_job_enabled: model::get_entries(dict, "OtherJobEnabled", model::bool_value)?
+        })
+    }
+
+    fn to_plist(&self) -> Plist {
+        let dict = model::new_dict();
+        model::set(&dict, "SuccessfulExit", self.successful_exit.map(Plist::from));
+        model::set(&dict, "NetworkState", self.network_state.map(Plist::from));
+        model::set(&dict, "Crashed", self.crashed.map(Plist::from));
+        model::set(&dict, "AfterInitialDemand", self.after_initial_demand.map(Plist::from));
+        model::set(&dict, "PathState", self.path_state.as_deref().map(|paths| model::entries(paths, |&state| Plist::from(state))));
+        model::set(&dict, "OtherJobEnabled", self.other_job_enabled.as_deref().map(|jobs| model::entries(jobs, |&enabled| Plist::from(enabled))));
+
+        dict.into_plist()
+    }
+}
+
+impl CalendarInterval {
+    fn read(key: &str, value: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(value).map_err(|error| error.within(key))?;
+        let field = |name: &str| model::get_uint(&dict, name).map_err(|error| error.within(key));
+
+        Ok(CalendarInterval {
+            minute: field("Minute")?,
+            hour: field("Hour")?,
+            day: field("Day")?,
+            weekday: field("Weekday")?,
+            month: field("Month")?
+        })
+    }
+
+    fn to_plist(&self) -> Plist {
+        let dict = model::new_dict();
+        model::set(&dict, "Minute", self.minute.map(Plist::from));
+        model::set(&dict, "Hour", self.hour.map(Plist::from));
+        model::set(&dict, "Day", self.day.map(Plist::from));
+        model::set(&dict, "Weekday", self.weekday.map(Plist::from));
+        model::set(&dict, "Month", self.month.map(Plist::from));
+
+        dict.into_plist()
+    }
+}
+
+impl MachService {
+    fn read(key: &str, value: &Plist) -> Result<Self, ModelError> {
+        if let Some(enabled) = value.as_bool() {
+            return Ok(Self::Enabled(enabled))
+        }
+        let dict = model::dict_copy(value).map_err(|error| error.within(key))?;
+
+        Ok(Self::Options {
+            reset_at_close: model::get_bool(&dict, "ResetAtClose").map_err(|error| error.within(key))?,
+            hide_until_check_in: model::get_bool(&dict, "HideUntilCheckIn").map_err(|error| error.within(key))?
+        })
+    }
+
+    fn to_plist(&self) -> Plist {
+        match self {
+            Self::Enabled(enabled) => Plist::from(*enabled),
+            Self::Options { reset_at_close, hide_until_check_in } => {
+                let dict = model::new_dict();
+                model::set(&dict, "ResetAtClose", reset_at_close.map(Plist::from));
+                model::set(&dict, "HideUntilCheckIn", hide_until_check_in.map(Plist::from));
+                dict.into_plist()
+            }
+        }
+    }
+}
+
+impl Socket {
+    pub fn new() -> Self {
+        Socket {
+            sock_type: None,
+            passive: None,
+            node_name: None,
+            service_name: None,
+            family: None,
+            protocol: None,
+            path_name: None,
+            path_mode: None,
+            other: model::Source::new(&SOCKET_KEYS)
+        }
+    }
+
+    fn read(key: &str, value: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(value).map_err(|error| error.within(key))?;
+        let service_name = match dict.get("SockServiceName") {
+            None => None,
+            Some(value) if value.node_type() == PlistNodeType::UInt => value.as_uint().map(SocketService::Port),
+            Some(_) => model::get_string(&dict, "SockServiceName").map_err(|error| error.within(key))?.map(SocketService::Name)
+        };
+        let string = |name: &str| model::get_string(&dict, name).map_err(|error| error.within(key));
+
+        Ok(Socket {
+            sock_type: string("SockType")?,
+            passive: model::get_bool(&dict, "SockPassive").map_err(|error| error.within(key))?,
+            node_name: string("SockNodeName")?,
+            service_name,
+            family: string("SockFamily")?,
+            protocol: string("SockProtocol")?,
+            path_name: string("SockPathName")?,
+            path_mode: model::get_uint(&dict, "SockPathMode").map_err(|error| error.within(key))?,
+            other: model::Source::read(dict, &SOCKET_KEYS)
+        })
+    }
+
+    fn to_plist(&self) -> Plist {
+        let dict = self.other.dict_copy();
+        model::set(&dict, "SockType", self.sock_type.as_deref().map(Plist::from));
+        model::set(&dict, "SockPassive", self.passive.map(Plist::from));
+        model::set(&dict, "SockNodeName", self.node_name.as_deref().map(Plist::from));
+        model::set(&dict, "SockServiceName", self.service_name.as_ref().map(|service| match service {
+            SocketService::Name(name) => Plist::from(name.as_str()),
+            SocketService::Port(port) => Plist::from(*port)
+        }));
+        model::set(&dict, "SockFamily", self.family.as_deref().map(Plist::from));
+        model::set(&dict, "SockProtocol", self.protocol.as_deref().map(Plist::from));
+        model::set(&dict, "SockPathName", self.path_name.as_deref().map(Plist::from));
+        model::set(&dict, "SockPathMode", self.path_mode.map(Plist::from));
+
+        dict.into_plist()
+    }
+}
+
+impl Default for Socket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Getter, Plist};
+    use crate::launchd::{CalendarInterval, KeepAlive, LaunchdJob, MachService, SocketService};
+    use crate::model::ModelError;
+    use crate::plist_node_type::PlistNodeType;
+
+    const AGENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>Label</key>
+	<string>com.example.agent</string>
+	<key>ProgramArguments</key>
+	<array>
+		<string>/usr/local/bin/agent</string>
+		<string>--serve</string>
+	</array>
+	<key>EnvironmentVariables</key>
+	<dict>
+		<key>PATH</key>
+		<string>/usr/bin:/bin</string>
+	</dict>
+	<key>KeepAlive</key>
+	<dict>
+		<key>SuccessfulExit</key>
+		<false/>
+		<key>PathState</key>
+		<dict>
+			<key>/var/run/agent.enabled</key>
+			<true/>
+		</dict>
+	</dict>
+	<key>StartCalendarInterval</key>
+	<dict>
+		<key>Hour</key>
+		<integer>3</integer>
+		<key>Minute</key>
+		<integer>15</integer>
+	</dict>
+	<key>MachServices</key>
+	<dict>
+		<key>com.example.agent.xpc</key>
+		<true/>
+	</dict>
+	<key>Sockets</key>
+	<dict>
+		<key>Listener</key>
+		<dict>
+			<key>SockServiceName</key>
+			<integer>8080</integer>
+			<key>SockType</key>
+			<string>stream</string>
+		</dict>
+	</dict>
+	<key>LimitLoadToSessionType</key>
+	<string>Aqua</string>
+</dict>
+</plist>
+"#;
+
+    #[test]
+    fn round_trip() {
+        let job = LaunchdJob::from_plist(&Plist::from_xml(AGENT.to_owned()).unwrap()).unwrap();
+        assert_eq!(job.label, "com.example.agent");
+        assert_eq!(job.environment_variables, Some(vec![("PATH".to_owned(), "/usr/bin:/bin".to_owned())]));
+        match &job.keep_alive {
+            Some(KeepAlive::Conditions(conditions)) => {
+                assert_eq!(conditions.successful_exit, Some(false));
+                assert_eq!(conditions.path_state, Some(vec![("/var/run/agent.enabled".to_owned(), true)]));
+            }
+            keep_alive => panic!("unexpected KeepAlive {:?}", keep_alive)
+        }
+        assert_eq!(job.start_calendar_interval, Some(vec![CalendarInterval { minute: Some(15), hour: Some(3), ..Default::default() }]));
+        assert_eq!(job.mach_services, Some(vec![("com.example.agent.xpc".to_owned(), MachService::Enabled(true))]));
+        let sockets = job.sockets.as_ref().unwrap();
+        assert_eq!(sockets[0].1[0].service_name, Some(SocketService::Port(8080)));
+        assert!(job.validate().is_empty());
+
+        let copy = LaunchdJob::from_plist(&job.to_plist()).unwrap();
+        assert_eq!(copy, job);
+        assert_eq!(copy.get_other("LimitLoadToSessionType").unwrap().as_str().unwrap(), "Aqua");
+
+        let mut job = copy;
+        job.label = "com.example.other".to_owned();
+        job.sockets.as_mut().unwrap()[0].1[0].sock_type = Some("dgram".to_owned());
+        assert_eq!(LaunchdJob::from_plist(&job.to_plist()).unwrap(), job);
+        assert_eq!(job.get_other("Label"), None);
+    }
+
+    #[test]
+    fn key_order() {
+        let mut job = LaunchdJob::from_plist(&Plist::from_xml(AGENT.to_owned()).unwrap()).unwrap();
+        job.label = "com.example.other".to_owned();
+        job.run_at_load = Some(true);
+
+        let keys = |plist: Plist| plist.dict().unwrap().iter().map(|entry| entry.unwrap().0).collect::<Vec<String>>();
+        assert_eq!(keys(job.to_plist()), [
+            "Label", "ProgramArguments", "EnvironmentVariables", "KeepAlive", "StartCalendarInterval",
+            "MachServices", "Sockets", "LimitLoadToSessionType", "RunAtLoad"
+        ]);
+        let plist = job.to_plist();
+        assert_eq!(keys(plist.get("Sockets").unwrap().get("Listener").unwrap()), ["SockServiceName", "SockType"]);
+    }
+
+    #[test]
+    fn generate() {
+        let mut job = LaunchdJob::new("com.example.backup", vec!["/usr/local/bin/backup".to_owned()]);
+        job.run_at_load = Some(true);
+        job.keep_alive = Some(KeepAlive::Always(true));
+        job.start_calendar_interval = Some(vec![
+            CalendarInterval { weekday: Some(1), hour: Some(2), ..Default::default() },
+            CalendarInterval { weekday: Some(4), hour: Some(2), ..Default::default() }
+        ]);
+
+        let plist = job.to_plist();
+        assert_eq!(plist.get("StartCalendarInterval").unwrap().node_type(), PlistNodeType::Array);
+        let copy = LaunchdJob::from_plist(&plist).unwrap();
+        assert_eq!(copy.keep_alive, job.keep_alive);
+        assert_eq!(copy.start_calendar_interval, job.start_calendar_interval);
+    }
+
+    #[test]
+    fn validate() {
+        let mut job = LaunchdJob::new("", Vec::new());
+        job.process_type = Some("Fast".to_owned());
+        job.watch_paths = Some(vec!["relative".to_owned()]);
+        job.start_calendar_interval = Some(vec![CalendarInterval { hour: Some(24), ..Default::default() }]);
+
+        let keys = job.validate().iter().map(|error| error.key().unwrap().to_owned()).collect::<Vec<String>>();
+        assert_eq!(keys, ["Label", "ProgramArguments", "ProcessType", "WatchPaths.0", "StartCalendarInterval.0.Hour"]);
+    }
+
+    #[test]
+    fn invalid_type() {
+        let plist = Plist::from_xml(AGENT.replace("<string>/usr/bin:/bin</string>", "<integer>1</integer>")).unwrap();
+        let error = LaunchdJob::from_plist(&plist).unwrap_err();
+        assert_eq!(error, ModelError::InvalidType { key: "EnvironmentVariables.PATH".to_owned(), expected: PlistNodeType::String });
+
+        let plist = Plist::from_xml(AGENT.replace("<key>Label</key>\n\t<string>com.example.agent</string>", "")).unwrap();
+        assert_eq!(LaunchdJob::from_plist(&plist).unwrap_err(), ModelError::MissingKey("Label".to_owned()));
+    }
+}
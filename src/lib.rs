@@ -1,23 +1,42 @@
 #![allow(non_upper_case_globals)]
-#![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 #![allow(unaligned_references)]
 
 extern crate core;
 
-use std::ffi::{c_void, CStr, CString};
+use std::ffi::{c_void, CString};
 use std::fmt::{Debug, Formatter};
 use std::os::raw::c_char;
 use std::ptr::null_mut;
+use plist_sys::*;
 use crate::plist_error::PlistError;
 use crate::plist_node_type::PlistNodeType;
-include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
 pub mod plist_error;
 pub mod array;
+#[cfg(feature = "tokio")]
+pub mod async_codec;
+pub mod bplist;
+pub mod codec;
+#[cfg(test)]
+mod conformance;
+pub mod defaults;
 pub mod dict;
+pub mod entitlements;
+pub mod format;
+pub mod info_plist;
+pub mod launchd;
+pub mod lockdown;
+pub mod pbxproj;
+pub mod model;
+pub mod pair_record;
+pub mod parse_options;
 pub mod value;
 pub mod plist_node_type;
+pub mod provisioning;
+pub mod strings;
+pub mod sync;
+pub mod usbmux;
 
 pub struct Plist {
     pub(crate) p: Option<plist_t>,
@@ -39,73 +58,146 @@ impl Debug for Plist {
 }
 
 impl Plist {
-    pub fn new(plist: plist_t) -> Self {
+    pub(crate) fn new(plist: plist_t) -> Self {
         Plist {
             p: Some(plist),
             rawP: None
         }
     }
 
-    pub fn new_with_weak(plist: plist_t) -> Self {
+    pub(crate) fn new_with_weak(plist: plist_t) -> Self {
         Plist {
             p: None,
             rawP: Some(plist)
         }
     }
 
+    /// Takes ownership of a node created through `plist-sys`; it is freed when the `Plist` is dropped.
+    ///
+    /// # Safety
+    ///
+    /// `plist` must be a valid, non-null libplist node that has no parent and is not freed by anyone else.
+    pub unsafe fn from_raw(plist: plist_t) -> Self {
+        Plist::new(plist)
+    }
+
+    /// Gives up ownership of the node. The caller must release it with `plist_free`.
+    ///
+    /// Nodes borrowed from a container are copied, so the returned node never has a parent.
+    pub fn into_raw(mut self) -> plist_t {
+        match self.p.take() {
+            Some(p) => p,
+            None => unsafe { plist_copy(self.as_ptr().unwrap()) }
+        }
+    }
+
     pub fn from_xml(xml: String) -> Result<Self, PlistError>  {
         let mut p: plist_t = std::ptr::null_mut();
-        let xml = CString::new(xml).unwrap();
+        let xml = CString::new(xml).map_err(|_| PlistError::Parse)?;
         let length = xml.as_bytes().len();
         let xml = xml.as_ptr();
+        #[cfg(libplist_2_3)]
         unsafe {
             PlistError::try_from(plist_from_xml(xml, length as u32, &mut p))
         }?;
+        #[cfg(not(libplist_2_3))]
+        {
+            unsafe { plist_from_xml(xml, length as u32, &mut p) };
+            non_null(p, PlistError::Parse)?;
+        }
 
         Ok(Plist::new(p))
     }
 
+    /// Binary plists are full of zero bytes, so `bin` is passed on as is rather than as a C
+    /// string.
     pub fn from_bin(bin: &[u8]) -> Result<Self, PlistError> {
         let mut p: plist_t = std::ptr::null_mut();
-        let length = bin.len();
-        let bin = CString::new(bin).unwrap();
+        let length = u32::try_from(bin.len()).map_err(|_| PlistError::InvalidArg)?;
+        #[cfg(libplist_2_3)]
         unsafe {
-            PlistError::try_from(plist_from_bin(bin.as_ptr(), length as u32, &mut p))
+            PlistError::try_from(plist_from_bin(bin.as_ptr() as *const c_char, length, &mut p))
         }?;
+        #[cfg(not(libplist_2_3))]
+        {
+            unsafe { plist_from_bin(bin.as_ptr() as *const c_char, length, &mut p) };
+            non_null(p, PlistError::Parse)?;
+        }
 
         Ok(Plist::new(p))
     }
 
+    /// Needs libplist 2.3 or newer, older versions return `PlistError::Unsupported`.
     pub fn from_json(json: String) -> Result<Self, PlistError> {
-        let length = json.len();
-        let json = CString::new(json).unwrap();
-        let json = json.as_ptr();
-        let mut plist: plist_t = null_mut();
+        #[cfg(libplist_2_3)]
+        {
+            let length = json.len();
+            let json = CString::new(json).map_err(|_| PlistError::Parse)?;
+            let json = json.as_ptr();
+            let mut plist: plist_t = null_mut();
+
+            unsafe {
+                PlistError::try_from(plist_from_json(json, length as u32, &mut plist))
+            }?;
+
+            Ok(Plist::new(plist))
+        }
+        #[cfg(not(libplist_2_3))]
+        {
+            let _ = json;
+            Err(PlistError::Unsupported)
+        }
+    }
 
-        unsafe {
-            PlistError::try_from(plist_from_json(json, length as u32, &mut plist))
-        }?;
+    /// Needs libplist 2.3 or newer, older versions return `PlistError::Unsupported`.
+    pub fn from_openstep(openstep: String) -> Result<Self, PlistError> {
+        #[cfg(libplist_2_3)]
+        {
+            let length = openstep.len();
+            let openstep = CString::new(openstep).map_err(|_| PlistError::Parse)?;
+            let openstep = openstep.as_ptr();
+            let mut plist: plist_t = null_mut();
 
-        Ok(Plist::new(plist))
+            unsafe {
+                PlistError::try_from(plist_from_openstep(openstep, length as u32, &mut plist))
+            }?;
+
+            Ok(Plist::new(plist))
+        }
+        #[cfg(not(libplist_2_3))]
+        {
+            let _ = openstep;
+            Err(PlistError::Unsupported)
+        }
     }
 
-    pub fn from_memory(data: &[i8]) -> Result<Self, PlistError> {
-        let length = data.len();
-        let data = data.as_ptr();
+    /// Parses data in any format libplist detects, binary included.
+    pub fn from_memory(data: &[u8]) -> Result<Self, PlistError> {
+        let length = u32::try_from(data.len()).map_err(|_| PlistError::InvalidArg)?;
+        let data = data.as_ptr() as *const c_char;
         let mut plist: plist_t = null_mut();
 
+        #[cfg(libplist_2_3)]
         unsafe {
-            PlistError::try_from(plist_from_memory(data, length as u32, &mut plist))
+            PlistError::try_from(plist_from_memory(data, length, &mut plist, null_mut()))
         }?;
+        #[cfg(not(libplist_2_3))]
+        {
+            unsafe { plist_from_memory(data, length, &mut plist) };
+            non_null(plist, PlistError::Parse)?;
+        }
 
         Ok(Plist::new(plist))
     }
 
-    pub fn is_binary(data: &[i8]) -> bool {
-        let length = data.len();
-        let data = data.as_ptr();
+    pub fn is_binary(data: &[u8]) -> bool {
+        let length = match u32::try_from(data.len()) {
+            Ok(length) => length,
+            Err(_) => return false
+        };
+        let data = data.as_ptr() as *const c_char;
 
-        let is_binary = unsafe { plist_is_binary(data, length as u32) };
+        let is_binary = unsafe { plist_is_binary(data, length) };
 
         is_binary == 1
     }
@@ -116,55 +208,96 @@ impl Plist {
         Plist::new(p)
     }
 
+    /// Fails with `PlistError::Format` if a string or key isn't valid UTF-8; `to_format`
+    /// writes such strings byte for byte instead.
     pub fn xml(&self) -> Result<String, PlistError> {
-        let mut xml: *mut c_char = std::ptr::null_mut();
-        let mut length = 0;
-        unsafe {
-            PlistError::try_from(plist_to_xml(self.as_ptr()?, &mut xml, &mut length))
-        }?;
+        String::from_utf8(self.xml_bytes()?).map_err(|_| PlistError::Format)
+    }
 
-        let result = unsafe { CStr::from_ptr(xml) };
-        let result = result.to_str().unwrap().to_owned();
+    pub(crate) fn xml_bytes(&self) -> Result<Vec<u8>, PlistError> {
+        let mut raw: *mut c_char = null_mut();
+        let mut length: u32 = 0;
+        #[cfg(libplist_2_3)]
         unsafe {
-            plist_mem_free(xml as *mut c_void)
-        };
+            PlistError::try_from(plist_to_xml(self.as_ptr()?, &mut raw, &mut length))
+        }?;
+        #[cfg(not(libplist_2_3))]
+        {
+            unsafe { plist_to_xml(self.as_ptr()?, &mut raw, &mut length) };
+            non_null(raw, PlistError::Format)?;
+        }
 
-        Ok(result)
+        Ok(unsafe { take_buffer(raw, length) })
     }
 
-    pub fn bin(&self) -> Result<Vec<i8>, PlistError> {
+    pub fn bin(&self) -> Result<Vec<u8>, PlistError> {
         let mut raw: *mut c_char = null_mut();
         let mut length: u32 = 0;
+        #[cfg(libplist_2_3)]
         unsafe {
             PlistError::try_from(plist_to_bin(self.as_ptr()?, &mut raw, &mut length))
         }?;
-
-        let bin = unsafe { std::slice::from_raw_parts(raw as *const i8, length as usize) };
-
-        unsafe {
-            plist_mem_free(raw as *mut c_void);
+        #[cfg(not(libplist_2_3))]
+        {
+            unsafe { plist_to_bin(self.as_ptr()?, &mut raw, &mut length) };
+            non_null(raw, PlistError::Format)?;
         }
 
-        Ok(bin.to_vec())
+        Ok(unsafe { take_buffer(raw, length) })
     }
 
+    /// Needs libplist 2.3 or newer, older versions return `PlistError::Unsupported`. Fails with
+    /// `PlistError::Format` if a string or key isn't valid UTF-8.
     pub fn json(&self, prettify: bool) -> Result<String, PlistError> {
-        let prettify = if prettify { 1 } else { 0 };
-        let mut raw: *mut c_char = null_mut();
-        let mut length: u32 = 0;
+        String::from_utf8(self.json_bytes(prettify)?).map_err(|_| PlistError::Format)
+    }
 
-        unsafe {
-            PlistError::try_from(plist_to_json(self.as_ptr()?, &mut raw, &mut length, prettify))
-        }?;
+    pub(crate) fn json_bytes(&self, prettify: bool) -> Result<Vec<u8>, PlistError> {
+        #[cfg(libplist_2_3)]
+        {
+            let prettify = if prettify { 1 } else { 0 };
+            let mut raw: *mut c_char = null_mut();
+            let mut length: u32 = 0;
 
-        let json = unsafe { CStr::from_ptr(raw) };
-        let json = json.to_str().unwrap();
+            unsafe {
+                PlistError::try_from(plist_to_json(self.as_ptr()?, &mut raw, &mut length, prettify))
+            }?;
 
-        unsafe {
-            plist_mem_free(raw as *mut c_void);
+            Ok(unsafe { take_buffer(raw, length) })
+        }
+        #[cfg(not(libplist_2_3))]
+        {
+            let _ = prettify;
+            self.as_ptr()?;
+            Err(PlistError::Unsupported)
         }
+    }
+
+    /// Needs libplist 2.3 or newer, older versions return `PlistError::Unsupported`. Fails with
+    /// `PlistError::Format` if a string or key isn't valid UTF-8.
+    pub fn openstep(&self, prettify: bool) -> Result<String, PlistError> {
+        String::from_utf8(self.openstep_bytes(prettify)?).map_err(|_| PlistError::Format)
+    }
+
+    pub(crate) fn openstep_bytes(&self, prettify: bool) -> Result<Vec<u8>, PlistError> {
+        #[cfg(libplist_2_3)]
+        {
+            let prettify = if prettify { 1 } else { 0 };
+            let mut raw: *mut c_char = null_mut();
+            let mut length: u32 = 0;
+
+            unsafe {
+                PlistError::try_from(plist_to_openstep(self.as_ptr()?, &mut raw, &mut length, prettify))
+            }?;
 
-        Ok(json.to_owned())
+            Ok(unsafe { take_buffer(raw, length) })
+        }
+        #[cfg(not(libplist_2_3))]
+        {
+            let _ = prettify;
+            self.as_ptr()?;
+            Err(PlistError::Unsupported)
+        }
     }
 
     pub fn node_type(&self) -> PlistNodeType {
@@ -176,7 +309,7 @@ impl Plist {
         }
     }
 
-    pub fn as_ptr(&self) -> Result<plist_t, PlistError> {
+    pub(crate) fn as_ptr(&self) -> Result<plist_t, PlistError> {
         match self.p {
             Some(p) => Ok(p),
             None => match self.rawP {
@@ -186,7 +319,7 @@ impl Plist {
         }
     }
 
-    pub fn unowned_ptr(&mut self) {
+    pub(crate) fn unowned_ptr(&mut self) {
         if let Some(p) = self.p {
             self.rawP = Some(p);
             self.p = None;
@@ -194,13 +327,87 @@ impl Plist {
     }
 }
 
+impl PartialEq for Plist {
+    fn eq(&self, other: &Self) -> bool {
+        let (lhs, rhs) = match (self.as_ptr(), other.as_ptr()) {
+            (Ok(lhs), Ok(rhs)) => (lhs, rhs),
+            _ => return false
+        };
+
+        let node_type = self.node_type();
+        if node_type != other.node_type() {
+            return false
+        }
+
+        match node_type {
+            PlistNodeType::Array => {
+                let length = unsafe { plist_array_get_size(lhs) };
+                if length != unsafe { plist_array_get_size(rhs) } {
+                    return false
+                }
+
+                (0..length as usize).all(|index| self.get(index) == other.get(index))
+            }
+            PlistNodeType::Dictionary => {
+                if unsafe { plist_dict_get_size(lhs) != plist_dict_get_size(rhs) } {
+                    return false
+                }
+
+                // Look keys up by their bytes, which needn't be UTF-8.
+                let mut entries = Plist::new_with_weak(lhs).dict().unwrap().iter();
+                while let Some((key, value)) = entries.next_bytes() {
+                    let key = CString::new(key).unwrap();
+                    let item = unsafe { plist_dict_get_item(rhs, key.as_ptr()) };
+                    if item.is_null() || Plist::new_with_weak(item) != value {
+                        return false
+                    }
+                }
+
+                true
+            }
+            PlistNodeType::Null | PlistNodeType::None => true,
+            _ => unsafe { plist_compare_node_value(lhs, rhs) == 1 }
+        }
+    }
+}
+
+/// Copies a buffer returned by one of libplist's writers and frees it.
+unsafe fn take_buffer(raw: *mut c_char, length: u32) -> Vec<u8> {
+    let data = std::slice::from_raw_parts(raw as *const u8, length as usize).to_vec();
+    plist_mem_free(raw as *mut c_void);
+
+    data
+}
+
+/// libplist 2.2's readers and writers return nothing, so a null result is the only sign that
+/// they failed.
+#[cfg(not(libplist_2_3))]
+fn non_null<T>(result: *mut T, error: PlistError) -> Result<(), PlistError> {
+    if result.is_null() {
+        return Err(error)
+    }
+
+    Ok(())
+}
+
+/// libplist 2.2 has no `plist_mem_free`; what it hands out is allocated with `malloc`.
+#[cfg(not(libplist_2_3))]
+pub(crate) unsafe fn plist_mem_free(ptr: *mut c_void) {
+    extern "C" {
+        fn free(ptr: *mut c_void);
+    }
+
+    free(ptr)
+}
+
 trait Getter<T> {
     fn get(&self, index: T) -> Option<Self> where Self: Sized;
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Plist;
+    use std::collections::HashMap;
+    use crate::{Getter, Plist};
     use crate::dict::DictGetter;
 
     #[test]
@@ -236,4 +443,44 @@ mod tests {
         let plist = Plist::from_xml(xml.to_string()).unwrap();
         assert_eq!(plist.dict().unwrap().get("test1").unwrap().as_str().unwrap(), "foo");
     }
+
+    #[test]
+    fn bin_fixtures() {
+        let info = include_bytes!("../tests/fixtures/binary/info_plist.plist");
+        assert!(Plist::is_binary(info));
+        for plist in [Plist::from_bin(info).unwrap(), Plist::from_memory(info).unwrap()] {
+            assert_eq!(plist.get("CFBundleIdentifier").unwrap().as_str().unwrap(), "com.example.app");
+            assert_eq!(plist.get("UIDeviceFamily").unwrap().get(1usize).unwrap().as_uint().unwrap(), 2);
+        }
+
+        let plist = Plist::from_bin(include_bytes!("../tests/fixtures/binary/data.plist")).unwrap();
+        assert_eq!(plist.get("Zeros").unwrap().as_data().unwrap(), vec![0; 16]);
+        let bytes = plist.get("Bytes").unwrap().as_data().unwrap();
+        assert_eq!(bytes.into_iter().map(|byte| byte as u8).collect::<Vec<u8>>(), (0..=255).collect::<Vec<u8>>());
+        assert_eq!(plist.get("Name").unwrap().as_str().unwrap(), "caf\u{e9} \u{1f4f1}");
+        assert_eq!(plist.get("Count").unwrap().as_uint().unwrap(), 0);
+        assert_eq!(plist.get("When").unwrap().as_date().unwrap(), (0, 0));
+
+        assert!(!Plist::is_binary(b"<plist><true/></plist>"));
+        assert!(Plist::from_bin(&info[..info.len() - 1]).is_err());
+        assert!(Plist::from_bin(b"bplist00\0\0\0").is_err());
+    }
+
+    #[test]
+    fn bin_round_trip() {
+        let zeros: &[i8] = &[0, 0, 1, 0];
+        let plist = Plist::from(HashMap::from([
+            ("data", Plist::from(zeros)),
+            ("string", Plist::from("\u{1f4f1} and text")),
+            ("array", Plist::from(vec![Plist::from(0), Plist::from(-1i64 as u64), Plist::from(0.5), Plist::from(false)])),
+            ("empty", Plist::from(HashMap::new())),
+        ]));
+
+        let bin = plist.bin().unwrap();
+        assert!(bin.starts_with(b"bplist00"));
+        assert!(bin.contains(&0));
+        assert_eq!(Plist::from_bin(&bin).unwrap(), plist);
+        assert_eq!(Plist::from_memory(&bin).unwrap(), plist);
+        assert_eq!(Plist::from_bin(&Plist::from_bin(&bin).unwrap().bin().unwrap()).unwrap().bin().unwrap(), bin);
+    }
 }
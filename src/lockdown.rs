@@ -0,0 +1,448 @@
+//! A lockdownd client. Every request is a dictionary naming the `Request` and the client's
+//! `Label`; the reply echoes the request name and carries an `Error` string on failure.
+//!
+//! The transport is any byte stream: a usbmux connection to [`LOCKDOWN_PORT`], a TLS stream
+//! after `StartSession` enables SSL, or a fake in tests.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use crate::Plist;
+use crate::codec::PlistStream;
+use crate::dict::{DictGetter, DictSetter, PlistDict};
+use crate::model::{self, ModelError};
+use crate::plist_error::PlistError;
+use crate::plist_node_type::PlistNodeType;
+
+pub const LOCKDOWN_PORT: u16 = 62078;
+/// The `Type` a genuine lockdownd answers `QueryType` with.
+pub const SERVICE_TYPE: &str = "com.apple.mobile.lockdown";
+
+#[derive(Debug)]
+pub enum LockdownError {
+    Plist(PlistError),
+    Model(ModelError),
+    /// The `Error` lockdownd replied with, e.g. `InvalidHostID` or `PasswordProtected`.
+    Lockdown(String),
+    /// The reply was for a different request than the one sent.
+    UnexpectedReply { expected: String, found: Option<String> }
+}
+
+impl Display for LockdownError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Plist(error) => write!(f, "{}", error),
+            Self::Model(error) => write!(f, "Invalid reply: {}", error),
+            Self::Lockdown(error) => write!(f, "lockdownd returned {}", error),
+            Self::UnexpectedReply { expected, found } => match found {
+                Some(found) => write!(f, "Expected a reply to {}, got one to {}", expected, found),
+                None => write!(f, "Expected a reply to {}", expected)
+            }
+        }
+    }
+}
+
+impl Error for LockdownError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Plist(error) => Some(error),
+            Self::Model(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<PlistError> for LockdownError {
+    fn from(error: PlistError) -> Self {
+        Self::Plist(error)
+    }
+}
+
+impl From<ModelError> for LockdownError {
+    fn from(error: ModelError) -> Self {
+        Self::Model(error)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    QueryType,
+    /// Without a key the whole domain is returned; without a domain the global one is used.
+    GetValue { domain: Option<String>, key: Option<String> },
+    SetValue { domain: Option<String>, key: String, value: Plist },
+    RemoveValue { domain: Option<String>, key: String },
+    StartSession { host_id: String, system_buid: String },
+    StopSession { session_id: String },
+    StartService { service: String, escrow_bag: Option<Vec<u8>> },
+    /// `pair_record` holds the certificates and identifiers of the host being paired.
+    Pair { pair_record: Plist },
+    ValidatePair { pair_record: Plist }
+}
+
+impl Request {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::QueryType => "QueryType",
+            Self::GetValue { .. } => "GetValue",
+            Self::SetValue { .. } => "SetValue",
+            Self::RemoveValue { .. } => "RemoveValue",
+            Self::StartSession { .. } => "StartSession",
+            Self::StopSession { .. } => "StopSession",
+            Self::StartService { .. } => "StartService",
+            Self::Pair { .. } => "Pair",
+            Self::ValidatePair { .. } => "ValidatePair"
+        }
+    }
+
+    pub fn to_plist(&self, label: &str) -> Plist {
+        let dict = model::new_dict();
+        dict.set("Label", Plist::from(label));
+        dict.set("Request", Plist::from(self.name()));
+
+        let string = |value: &Option<String>| value.as_deref().map(Plist::from);
+        match self {
+            Self::QueryType => {}
+            Self::GetValue { domain, key } => {
+                model::set(&dict, "Domain", string(domain));
+                model::set(&dict, "Key", string(key));
+            }
+            Self::SetValue { domain, key, value } => {
+                model::set(&dict, "Domain", string(domain));
+                dict.set("Key", Plist::from(key.as_str()));
+                dict.set("Value", value.copy());
+            }
+            Self::RemoveValue { domain, key } => {
+                model::set(&dict, "Domain", string(domain));
+                dict.set("Key", Plist::from(key.as_str()));
+            }
+            Self::StartSession { host_id, system_buid } => {
+                dict.set("HostID", Plist::from(host_id.as_str()));
+                dict.set("SystemBUID", Plist::from(system_buid.as_str()));
+            }
+            Self::StopSession { session_id } => dict.set("SessionID", Plist::from(session_id.as_str())),
+            Self::StartService { service, escrow_bag } => {
+                dict.set("Service", Plist::from(service.as_str()));
+                let escrow_bag = escrow_bag.as_ref().map(|data| data.iter().map(|&byte| byte as i8).collect::<Vec<i8>>());
+                model::set(&dict, "EscrowBag", escrow_bag.as_deref().map(Plist::from));
+            }
+            Self::Pair { pair_record } | Self::ValidatePair { pair_record } => {
+                dict.set("PairRecord", pair_record.copy());
+                dict.set("ProtocolVersion", Plist::from("2"));
+                if let Self::Pair { .. } = self {
+                    let options = model::new_dict();
+                    options.set("ExtendedPairingErrors", Plist::from(true));
+                    dict.set("PairingOptions", options.into_plist());
+                }
+            }
+        }
+
+        dict.into_plist()
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Session {
+    pub session_id: String,
+    /// Whether the rest of the connection must be wrapped in TLS using the pair record.
+    pub enable_ssl: bool
+}
+
+impl Session {
+    pub fn from_dict(dict: &PlistDict) -> Result<Self, ModelError> {
+        Ok(Session {
+            session_id: model::require(model::get_string(dict, "SessionID")?, "SessionID")?,
+            enable_ssl: model::get_bool(dict, "EnableSessionSSL")?.unwrap_or(false)
+        })
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Service {
+    pub service: String,
+    /// The device port to connect to, through usbmux or the network.
+    pub port: u16,
+    pub enable_ssl: bool
+}
+
+impl Service {
+    pub fn from_dict(dict: &PlistDict) -> Result<Self, ModelError> {
+        let port = model::require(model::get_uint(dict, "Port")?, "Port")?;
+
+        Ok(Service {
+            service: model::require(model::get_string(dict, "Service")?, "Service")?,
+            port: u16::try_from(port).map_err(|_| ModelError::InvalidValue {
+                key: "Port".to_owned(),
+                reason: format!("{} is not a port", port)
+            })?,
+            enable_ssl: model::get_bool(dict, "EnableServiceSSL")?.unwrap_or(false)
+        })
+    }
+}
+
+pub struct LockdownClient<T> {
+    stream: PlistStream<T>,
+    label: String
+}
+
+impl<T: Read + Write> LockdownClient<T> {
+    pub fn new(transport: T) -> Self {
+        LockdownClient {
+            stream: PlistStream::new(transport),
+            label: env!("CARGO_PKG_NAME").to_owned()
+        }
+    }
+
+    /// The `Label` sent with every request, shown in the device's logs.
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = label.to_owned();
+        self
+    }
+
+    pub fn query_type(&mut self) -> Result<String, LockdownError> {
+        let reply = self.request(&Request::QueryType)?;
+
+        Ok(model::require(model::get_string(&reply, "Type")?, "Type")?)
+    }
+
+    pub fn get_value(&mut self, domain: Option<&str>, key: Option<&str>) -> Result<Plist, LockdownError> {
+        let reply = self.request(&Request::GetValue {
+            domain: domain.map(str::to_owned),
+            key: key.map(str::to_owned)
+        })?;
+
+        Ok(model::require(reply.get("Value").map(|value| value.copy()), "Value")?)
+    }
+
+    pub fn set_value(&mut self, domain: Option<&str>, key: &str, value: Plist) -> Result<(), LockdownError> {
+        self.request(&Request::SetValue {
+            domain: domain.map(str::to_owned),
+            key: key.to_owned(),
+            value
+        })?;
+
+        Ok(())
+    }
+
+    pub fn remove_value(&mut self, domain: Option<&str>, key: &str) -> Result<(), LockdownError> {
+        self.request(&Request::RemoveValue {
+            domain: domain.map(str::to_owned),
+            key: key.to_owned()
+        })?;
+
+        Ok(())
+    }
+
+    pub fn start_session(&mut self, host_id: &str, system_buid: &str) -> Result<Session, LockdownError> {
+        let reply = self.request(&Request::StartSession {
+            host_id: host_id.to_owned(),
+            system_buid: system_buid.to_owned()
+        })?;
+
+        Ok(Session::from_dict(&reply)?)
+    }
+
+    pub fn stop_session(&mut self, session_id: &str) -> Result<(), LockdownError> {
+        self.request(&Request::StopSession { session_id: session_id.to_owned() })?;
+
+        Ok(())
+    }
+
+    pub fn start_service(&mut self, service: &str, escrow_bag: Option<&[u8]>) -> Result<Service, LockdownError> {
+        let reply = self.request(&Request::StartService {
+            service: service.to_owned(),
+            escrow_bag: escrow_bag.map(<[u8]>::to_vec)
+        })?;
+
+        Ok(Service::from_dict(&reply)?)
+    }
+
+    /// Pairs with the device and returns the escrow bag it hands out, if any.
+    pub fn pair(&mut self, pair_record: &Plist) -> Result<Option<Vec<u8>>, LockdownError> {
+        let reply = self.request(&Request::Pair { pair_record: pair_record.copy() })?;
+        let escrow_bag = model::get(&reply, "EscrowBag", PlistNodeType::Data)?;
+
+        Ok(escrow_bag.and_then(|data| data.as_data()).map(|data| data.into_iter().map(|byte| byte as u8).collect()))
+    }
+
+    pub fn validate_pair(&mut self, pair_record: &Plist) -> Result<(), LockdownError> {
+        self.request(&Request::ValidatePair { pair_record: pair_record.copy() })?;
+
+        Ok(())
+    }
+
+    /// Sends `request` and returns the reply, after checking it answers `request` and carries
+    /// no `Error`.
+    pub fn request(&mut self, request: &Request) -> Result<PlistDict, LockdownError> {
+        let reply = self.stream.request(&request.to_plist(&self.label))?;
+        let reply = model::dict_copy(&reply)?;
+
+        if let Some(error) = model::get_string(&reply, "Error")? {
+            return Err(LockdownError::Lockdown(error))
+        }
+        let name = model::get_string(&reply, "Request")?;
+        if name.as_deref() != Some(request.name()) {
+            return Err(LockdownError::UnexpectedReply { expected: request.name().to_owned(), found: name })
+        }
+
+        Ok(reply)
+    }
+
+    pub fn get_ref(&self) -> &T {
+        self.stream.get_ref()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.stream.get_mut()
+    }
+
+    /// Returns the transport, e.g. to wrap it in TLS once a session enables SSL.
+    pub fn into_inner(self) -> T {
+        self.stream.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, VecDeque};
+    use std::io::{self, Read, Write};
+    use crate::{Getter, Plist};
+    use crate::lockdown::{LockdownClient, LockdownError, Service, Session, SERVICE_TYPE};
+
+    /// An in-memory lockdownd that answers each request with the next reply of its script,
+    /// after checking the request is the one the script expects.
+    struct FakeLockdownd {
+        script: VecDeque<(&'static str, Plist)>,
+        received: Vec<Plist>,
+        input: Vec<u8>,
+        output: VecDeque<u8>
+    }
+
+    impl FakeLockdownd {
+        fn new(script: Vec<(&'static str, Plist)>) -> Self {
+            FakeLockdownd {
+                script: script.into(),
+                received: vec![],
+                input: vec![],
+                output: VecDeque::new()
+            }
+        }
+
+        fn reply(request: &str, entries: Vec<(&str, Plist)>) -> Plist {
+            let mut entries = HashMap::from_iter(entries);
+            entries.insert("Request", Plist::from(request));
+            Plist::from(entries)
+        }
+    }
+
+    impl Write for FakeLockdownd {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.input.extend_from_slice(data);
+
+            while self.input.len() >= 4 {
+                let length = u32::from_be_bytes([self.input[0], self.input[1], self.input[2], self.input[3]]) as usize;
+                if self.input.len() < 4 + length {
+                    break
+                }
+                let message = self.input.drain(..4 + length).skip(4).collect::<Vec<u8>>();
+                let request = Plist::from_memory(&message).unwrap();
+
+                let (expected, reply) = self.script.pop_front().expect("unscripted request");
+                assert_eq!(request.get("Request").unwrap().as_str().unwrap(), expected);
+                let reply = reply.xml().unwrap().into_bytes();
+                self.output.extend((reply.len() as u32).to_be_bytes());
+                self.output.extend(reply);
+                self.received.push(request);
+            }
+
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for FakeLockdownd {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            self.output.read(buffer)
+        }
+    }
+
+    #[test]
+    fn values() {
+        let fake = FakeLockdownd::new(vec![
+            ("QueryType", FakeLockdownd::reply("QueryType", vec![("Type", Plist::from(SERVICE_TYPE))])),
+            ("GetValue", FakeLockdownd::reply("GetValue", vec![("Key", Plist::from("ProductVersion")), ("Value", Plist::from("17.4"))])),
+            ("SetValue", FakeLockdownd::reply("SetValue", vec![])),
+            ("RemoveValue", FakeLockdownd::reply("RemoveValue", vec![])),
+        ]);
+        let mut client = LockdownClient::new(fake).label("tests");
+
+        assert_eq!(client.query_type().unwrap(), SERVICE_TYPE);
+        assert_eq!(client.get_value(None, Some("ProductVersion")).unwrap().as_str().unwrap(), "17.4");
+        client.set_value(Some("com.apple.mobile.wireless_lockdown"), "EnableWifiConnections", Plist::from(true)).unwrap();
+        client.remove_value(Some("com.apple.mobile.wireless_lockdown"), "EnableWifiConnections").unwrap();
+
+        let fake = client.into_inner();
+        assert!(fake.script.is_empty());
+        let request = &fake.received[1];
+        assert_eq!(request.get("Label").unwrap().as_str().unwrap(), "tests");
+        assert_eq!(request.get("Key").unwrap().as_str().unwrap(), "ProductVersion");
+        assert!(request.get("Domain").is_none());
+        assert!(fake.received[2].get("Value").unwrap().as_bool().unwrap());
+    }
+
+    #[test]
+    fn sessions() {
+        let fake = FakeLockdownd::new(vec![
+            ("StartSession", FakeLockdownd::reply("StartSession", vec![("SessionID", Plist::from("4A1D")), ("EnableSessionSSL", Plist::from(true))])),
+            ("StartService", FakeLockdownd::reply("StartService", vec![("Service", Plist::from("com.apple.afc")), ("Port", Plist::from(49152u64))])),
+            ("StopSession", FakeLockdownd::reply("StopSession", vec![])),
+        ]);
+        let mut client = LockdownClient::new(fake);
+
+        let session = client.start_session("HOST-ID", "SYSTEM-BUID").unwrap();
+        assert_eq!(session, Session { session_id: "4A1D".to_owned(), enable_ssl: true });
+        let service = client.start_service("com.apple.afc", Some(b"bag")).unwrap();
+        assert_eq!(service, Service { service: "com.apple.afc".to_owned(), port: 49152, enable_ssl: false });
+        client.stop_session(&session.session_id).unwrap();
+
+        let fake = client.into_inner();
+        assert_eq!(fake.received[0].get("HostID").unwrap().as_str().unwrap(), "HOST-ID");
+        assert_eq!(fake.received[1].get("EscrowBag").unwrap().as_data().unwrap(), vec![b'b' as i8, b'a' as i8, b'g' as i8]);
+        assert_eq!(fake.received[2].get("SessionID").unwrap().as_str().unwrap(), "4A1D");
+    }
+
+    #[test]
+    fn pairing() {
+        let escrow_bag: &[i8] = &[1, 2, 3];
+        let fake = FakeLockdownd::new(vec![
+            ("Pair", FakeLockdownd::reply("Pair", vec![("EscrowBag", Plist::from(escrow_bag))])),
+            ("ValidatePair", FakeLockdownd::reply("ValidatePair", vec![])),
+        ]);
+        let mut client = LockdownClient::new(fake);
+        let pair_record = Plist::from(HashMap::from([("HostID", Plist::from("HOST-ID"))]));
+
+        assert_eq!(client.pair(&pair_record).unwrap(), Some(vec![1, 2, 3]));
+        client.validate_pair(&pair_record).unwrap();
+
+        let fake = client.into_inner();
+        assert_eq!(fake.received[0].get("PairRecord").unwrap().get("HostID").unwrap().as_str().unwrap(), "HOST-ID");
+        assert!(fake.received[0].get("PairingOptions").unwrap().get("ExtendedPairingErrors").unwrap().as_bool().unwrap());
+        assert!(fake.received[1].get("PairingOptions").is_none());
+    }
+
+    #[test]
+    fn errors() {
+        let fake = FakeLockdownd::new(vec![
+            ("StartSession", FakeLockdownd::reply("StartSession", vec![("Error", Plist::from("InvalidHostID"))])),
+            ("GetValue", FakeLockdownd::reply("QueryType", vec![])),
+            ("GetValue", FakeLockdownd::reply("GetValue", vec![])),
+        ]);
+        let mut client = LockdownClient::new(fake);
+
+        assert!(matches!(client.start_session("HOST-ID", "BUID"), Err(LockdownError::Lockdown(error)) if error == "InvalidHostID"));
+        assert!(matches!(client.get_value(None, None), Err(LockdownError::UnexpectedReply { found: Some(found), .. }) if found == "QueryType"));
+        assert!(matches!(client.get_value(None, None), Err(LockdownError::Model(_))));
+    }
+}
@@ -0,0 +1,249 @@
+//! Shared plumbing for the typed models built on top of `PlistDict`.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use crate::Plist;
+use crate::array::PlistArray;
+use crate::dict::{DictGetter, DictRemove, DictSetter, InvalidKey, PlistDict};
+use crate::plist_node_type::PlistNodeType;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum ModelError {
+    NotDictionary,
+    MissingKey(String),
+    InvalidType { key: String, expected: PlistNodeType },
+    InvalidValue { key: String, reason: String }
+}
+
+impl ModelError {
+    /// Prefixes the key with the path of the container it was read from.
+    pub(crate) fn within(self, parent: &str) -> Self {
+        let join = |key: String| if key.is_empty() { parent.to_owned() } else { format!("{}.{}", parent, key) };
+        match self {
+            Self::MissingKey(key) => Self::MissingKey(join(key)),
+            Self::InvalidType { key, expected } => Self::InvalidType { key: join(key), expected },
+            Self::InvalidValue { key, reason } => Self::InvalidValue { key: join(key), reason },
+            Self::NotDictionary => Self::InvalidType { key: parent.to_owned(), expected: PlistNodeType::Dictionary }
+        }
+    }
+
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            Self::NotDictionary => None,
+            Self::MissingKey(key) | Self::InvalidType { key, .. } | Self::InvalidValue { key, .. } => Some(key)
+        }
+    }
+}
+
+impl Display for ModelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotDictionary => write!(f, "The root is not a dictionary"),
+            Self::MissingKey(key) => write!(f, "Missing required key {}", key),
+            Self::InvalidType { key, expected } => write!(f, "{} is not of type {:?}", key, expected),
+            Self::InvalidValue { key, reason } => write!(f, "Invalid value for {}: {}", key, reason)
+        }
+    }
+}
+
+impl Error for ModelError {}
+
+pub(crate) fn new_dict() -> PlistDict {
+    Plist::from(HashMap::new()).dict().unwrap()
+}
+
+/// Returns a dictionary view that edits `plist` in place.
+pub(crate) fn view(plist: &Plist) -> Option<PlistDict> {
+    Plist::new_with_weak(plist.as_ptr().ok()?).dict()
+}
+
+/// Copies `plist` into a dictionary that can be edited without touching the original.
+pub(crate) fn dict_copy(plist: &Plist) -> Result<PlistDict, ModelError> {
+    plist.copy().dict().ok_or(ModelError::NotDictionary)
+}
+
+pub(crate) fn get(dict: &PlistDict, key: &str, expected: PlistNodeType) -> Result<Option<Plist>, ModelError> {
+    match dict.get(key) {
+        None => Ok(None),
+        Some(value) if value.node_type() == expected => Ok(Some(value)),
+        Some(_) => Err(ModelError::InvalidType { key: key.to_owned(), expected })
+    }
+}
+
+pub(crate) fn get_string(dict: &PlistDict, key: &str) -> Result<Option<String>, ModelError> {
+    get(dict, key, PlistNodeType::String)?.map(|value| string_value(key, &value)).transpose()
+}
+
+pub(crate) fn get_bool(dict: &PlistDict, key: &str) -> Result<Option<bool>, ModelError> {
+    Ok(get(dict, key, PlistNodeType::Boolean)?.and_then(|value| value.as_bool()))
+}
+
+pub(crate) fn get_uint(dict: &PlistDict, key: &str) -> Result<Option<u64>, ModelError> {
+    Ok(get(dict, key, PlistNodeType::UInt)?.and_then(|value| value.as_uint()))
+}
+
+pub(crate) fn get_date(dict: &PlistDict, key: &str) -> Result<Option<(i32, i32)>, ModelError> {
+    Ok(get(dict, key, PlistNodeType::Date)?.and_then(|value| value.as_date()))
+}
+
+pub(crate) fn get_data(dict: &PlistDict, key: &str) -> Result<Option<Vec<u8>>, ModelError> {
+    Ok(get(dict, key, PlistNodeType::Data)?.and_then(|value| value.as_data()).map(|data| data.into_iter().map(|byte| byte as u8).collect()))
+}
+
+pub(crate) fn get_array(dict: &PlistDict, key: &str) -> Result<Option<PlistArray>, ModelError> {
+    Ok(get(dict, key, PlistNodeType::Array)?.and_then(|value| value.array()))
+}
+
+pub(crate) fn get_dict(dict: &PlistDict, key: &str) -> Result<Option<PlistDict>, ModelError> {
+    Ok(get(dict, key, PlistNodeType::Dictionary)?.and_then(|value| value.dict()))
+}
+
+/// Reads every item of an array with `read`, which is given the item's index for error paths.
+pub(crate) fn get_items<T, F>(dict: &PlistDict, key: &str, read: F) -> Result<Option<Vec<T>>, ModelError>
+where F: Fn(&str, &Plist) -> Result<T, ModelError> {
+    let array = match get_array(dict, key)? {
+        Some(array) => array,
+        None => return Ok(None)
+    };
+
+    array.into_iter().enumerate()
+        .map(|(index, item)| read(&index.to_string(), &item).map_err(|error| error.within(key)))
+        .collect::<Result<Vec<T>, ModelError>>()
+        .map(Some)
+}
+
+/// Reads every entry of a dictionary with `read`, keeping the order of the entries.
+pub(crate) fn get_entries<T, F>(dict: &PlistDict, key: &str, read: F) -> Result<Option<Vec<(String, T)>>, ModelError>
+where F: Fn(&str, &Plist) -> Result<T, ModelError> {
+    let entries = match get_dict(dict, key)? {
+        Some(entries) => entries,
+        None => return Ok(None)
+    };
+
+    entries.iter()
+        .map(|entry| {
+            let (name, value) = entry.map_err(|error| invalid_key(error).within(key))?;
+            match read(&name, &value) {
+                Ok(value) => Ok((name, value)),
+                Err(error) => Err(error.within(key))
+            }
+        })
+        .collect::<Result<Vec<(String, T)>, ModelError>>()
+        .map(Some)
+}
+
+/// A model can't name a key that isn't UTF-8 without changing it, so reading one is an error.
+pub(crate) fn invalid_key(error: InvalidKey) -> ModelError {
+    ModelError::InvalidValue { key: String::from_utf8_lossy(&error.key).into_owned(), reason: "the key is not valid UTF-8".to_owned() }
+}
+
+/// Reads an array whose items must all be strings.
+pub(crate) fn get_strings(dict: &PlistDict, key: &str) -> Result<Option<Vec<String>>, ModelError> {
+    get_items(dict, key, string_value)
+}
+
+pub(crate) fn string_value(key: &str, value: &Plist) -> Result<String, ModelError> {
+    if value.node_type() != PlistNodeType::String {
+        return Err(ModelError::InvalidType { key: key.to_owned(), expected: PlistNodeType::String })
+    }
+
+    value.as_str().ok_or_else(|| ModelError::InvalidValue { key: key.to_owned(), reason: "not valid UTF-8".to_owned() })
+}
+
+pub(crate) fn bool_value(key: &str, value: &Plist) -> Result<bool, ModelError> {
+    value.as_bool().ok_or_else(|| ModelError::InvalidType { key: key.to_owned(), expected: PlistNodeType::Boolean })
+}
+
+pub(crate) fn require<T>(value: Option<T>, key: &str) -> Result<T, ModelError> {
+    value.ok_or_else(|| ModelError::MissingKey(key.to_owned()))
+}
+
+/// Stores `value` under `key`, or removes the key when there is no value.
+pub(crate) fn set(dict: &PlistDict, key: &str, value: Option<Plist>) {
+    match value {
+        Some(value) => dict.set(key, value),
+        None => if dict.get(key).is_some() {
+            dict.remove(key)
+        }
+    }
+}
+
+/// Drops the keys that have a field of their own, leaving what a model keeps to write back.
+pub(crate) fn other(dict: PlistDict, known: &[&str]) -> Plist {
+    for key in known {
+        set(&dict, key, None);
+    }
+
+    dict.into_plist()
+}
+
+/// The dictionary a model was read from, kept whole so that writing the model back leaves
+/// every key where it was. The values of the `known` keys, which have fields of their own, are
+/// stale: they are hidden here and replaced in place by whatever the fields hold.
+pub(crate) struct Source {
+    dict: Plist,
+    known: &'static [&'static str]
+}
+
+impl Source {
+    pub(crate) fn new(known: &'static [&'static str]) -> Self {
+        Source { dict: new_dict().into_plist(), known }
+    }
+
+    pub(crate) fn read(dict: PlistDict, known: &'static [&'static str]) -> Self {
+        Source { dict: dict.into_plist(), known }
+    }
+
+    /// Returns a copy of a key that has no field of its own.
+    pub(crate) fn get(&self, key: &str) -> Option<Plist> {
+        if self.known.contains(&key) {
+            return None
+        }
+
+        view(&self.dict)?.get(key).map(|value| value.copy())
+    }
+
+    pub(crate) fn set(&mut self, key: &str, value: Option<Plist>) {
+        set(&view(&self.dict).unwrap(), key, value);
+    }
+
+    /// A copy to write the fields into: `set` replaces a value without moving its key.
+    pub(crate) fn dict_copy(&self) -> PlistDict {
+        dict_copy(&self.dict).unwrap()
+    }
+
+    /// The keys without a field of their own.
+    fn other(&self) -> Plist {
+        other(self.dict_copy(), self.known)
+    }
+}
+
+impl PartialEq for Source {
+    fn eq(&self, other: &Self) -> bool {
+        self.other() == other.other()
+    }
+}
+
+impl Debug for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.other(), f)
+    }
+}
+
+pub(crate) fn data(value: &[u8]) -> Plist {
+    Plist::from(value.iter().map(|&byte| byte as i8).collect::<Vec<i8>>().as_slice())
+}
+
+pub(crate) fn strings(values: &[String]) -> Plist {
+    Plist::from(values.iter().map(|value| Plist::from(value.as_str())).collect::<Vec<Plist>>())
+}
+
+pub(crate) fn entries<T, F>(values: &[(String, T)], write: F) -> Plist where F: Fn(&T) -> Plist {
+    let dict = new_dict();
+    for (key, value) in values {
+        dict.set(key.as_str(), write(value));
+    }
+
+    dict.into_plist()
+}
@@ -0,0 +1,370 @@
+//! Pairing records: the identities a host and a device exchanged when the user tapped "Trust".
+//! Certificates and keys are stored as `Data` holding PEM text.
+//!
+//! [`PairRecordStore`] keeps them as `<udid>.plist` files like usbmuxd does in
+//! `/var/lib/lockdown` on Linux and `/var/db/lockdown` on macOS.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::{Getter, Plist};
+use crate::dict::DictSetter;
+use crate::format::PlistFormat;
+use crate::model::{self, ModelError};
+use crate::plist_error::PlistError;
+
+const EXTENSION: &str = "plist";
+const PEM_PREFIX: &[u8] = b"-----BEGIN ";
+/// Where usbmuxd keeps its own `SystemBUID`; not a pair record.
+const SYSTEM_CONFIGURATION: &str = "SystemConfiguration";
+
+const KEYS: [&str; 9] = [
+    "HostID", "SystemBUID", "HostCertificate", "HostPrivateKey", "DeviceCertificate", "RootCertificate",
+    "RootPrivateKey", "EscrowBag", "WiFiMACAddress"
+];
+
+#[derive(Debug)]
+pub enum PairRecordError {
+    Plist(PlistError),
+    Model(ModelError)
+}
+
+impl Display for PairRecordError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Plist(error) => write!(f, "{}", error),
+            Self::Model(error) => write!(f, "Invalid pair record: {}", error)
+        }
+    }
+}
+
+impl Error for PairRecordError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Plist(error) => Some(error),
+            Self::Model(error) => Some(error)
+        }
+    }
+}
+
+impl From<PlistError> for PairRecordError {
+    fn from(error: PlistError) -> Self {
+        Self::Plist(error)
+    }
+}
+
+impl From<ModelError> for PairRecordError {
+    fn from(error: ModelError) -> Self {
+        Self::Model(error)
+    }
+}
+
+impl From<std::io::Error> for PairRecordError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Plist(PlistError::Io(error))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PairRecord {
+    pub host_id: String,
+    pub system_buid: String,
+    pub host_certificate: Vec<u8>,
+    pub host_private_key: Option<Vec<u8>>,
+    pub device_certificate: Vec<u8>,
+    pub root_certificate: Vec<u8>,
+    pub root_private_key: Option<Vec<u8>>,
+    /// Handed out by the device on pairing; lets services start while it is locked.
+    pub escrow_bag: Option<Vec<u8>>,
+    pub wifi_mac_address: Option<String>,
+    other: Plist
+}
+
+impl PairRecord {
+    pub fn parse(data: &[u8]) -> Result<Self, PairRecordError> {
+        Ok(Self::from_plist(&Plist::from_memory(data)?)?)
+    }
+
+    pub fn from_plist(plist: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(plist)?;
+        let pem = |key: &str| -> Result<Option<Vec<u8>>, ModelError> {
+            match model::get_data(&dict, key)? {
+                Some(data) if !data.trim_ascii_start().starts_with(PEM_PREFIX) => Err(ModelError::InvalidValue {
+                    key: key.to_owned(),
+                    reason: "not PEM encoded".to_owned()
+                }),
+                data => Ok(data)
+            }
+        };
+
+        Ok(PairRecord {
+            host_id: model::require(model::get_string(&dict, "HostID")?, "HostID")?,
+            system_buid: model::require(model::get_string(&dict, "SystemBUID")?, "SystemBUID")?,
+            host_certificate: model::require(pem("HostCertificate")?, "HostCertificate")?,
+            host_private_key: pem("HostPrivateKey")?,
+            device_certificate: model::require(pem("DeviceCertificate")?, "DeviceCertificate")?,
+            root_certificate: model::require(pem("RootCertificate")?, "RootCertificate")?,
+            root_private_key: pem("RootPrivateKey")?,
+            escrow_bag: model::get_data(&dict, "EscrowBag")?,
+            wifi_mac_address: model::get_string(&dict, "WiFiMACAddress")?,
+            other: model::other(dict, &KEYS)
+        })
+    }
+
+    pub fn to_plist(&self) -> Plist {
+        let dict = model::dict_copy(&self.other).unwrap();
+        dict.set("DeviceCertificate", model::data(&self.device_certificate));
+        model::set(&dict, "EscrowBag", self.escrow_bag.as_deref().map(model::data));
+        dict.set("HostCertificate", model::data(&self.host_certificate));
+        dict.set("HostID", Plist::from(self.host_id.as_str()));
+        model::set(&dict, "HostPrivateKey", self.host_private_key.as_deref().map(model::data));
+        dict.set("RootCertificate", model::data(&self.root_certificate));
+        model::set(&dict, "RootPrivateKey", self.root_private_key.as_deref().map(model::data));
+        dict.set("SystemBUID", Plist::from(self.system_buid.as_str()));
+        model::set(&dict, "WiFiMACAddress", self.wifi_mac_address.as_deref().map(Plist::from));
+
+        dict.into_plist()
+    }
+
+    /// The record as sent in lockdownd's `Pair` and `ValidatePair` requests: certificates and
+    /// identifiers only, never the private keys.
+    pub fn to_pairing_plist(&self) -> Plist {
+        let dict = model::new_dict();
+        dict.set("DeviceCertificate", model::data(&self.device_certificate));
+        dict.set("HostCertificate", model::data(&self.host_certificate));
+        dict.set("HostID", Plist::from(self.host_id.as_str()));
+        dict.set("RootCertificate", model::data(&self.root_certificate));
+        dict.set("SystemBUID", Plist::from(self.system_buid.as_str()));
+
+        dict.into_plist()
+    }
+
+    /// Returns a copy of a key that has no field of its own, such as the `UDID` usbmuxd adds.
+    pub fn get_other(&self, key: &str) -> Option<Plist> {
+        self.other.get(key).map(|value| value.copy())
+    }
+}
+
+pub struct PairRecordStore {
+    dir: PathBuf
+}
+
+impl PairRecordStore {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        PairRecordStore {
+            dir: dir.into()
+        }
+    }
+
+    /// The directory usbmuxd uses on this platform.
+    pub fn system() -> Self {
+        if cfg!(target_os = "macos") {
+            Self::new("/var/db/lockdown")
+        } else {
+            Self::new("/var/lib/lockdown")
+        }
+    }
+
+    pub fn path(&self, udid: &str) -> Result<PathBuf, PlistError> {
+        if udid.is_empty() || udid == SYSTEM_CONFIGURATION || !udid.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(PlistError::InvalidArg)
+        }
+
+        Ok(self.dir.join(format!("{}.{}", udid, EXTENSION)))
+    }
+
+    /// The UDIDs of every stored record, sorted.
+    pub fn udids(&self) -> Result<Vec<String>, PlistError> {
+        let mut udids = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == EXTENSION) {
+                if let Some(udid) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    if self.path(udid).is_ok() {
+                        udids.push(udid.to_owned());
+                    }
+                }
+            }
+        }
+        udids.sort();
+
+        Ok(udids)
+    }
+
+    /// Returns the record for `udid`, or `None` if the device was never paired.
+    pub fn read(&self, udid: &str) -> Result<Option<PairRecord>, PairRecordError> {
+        match fs::read(self.path(udid)?) {
+            Ok(data) => Ok(Some(PairRecord::parse(&data)?)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into())
+        }
+    }
+
+    /// Stores the record for `udid`. The file is replaced atomically, so concurrent readers see
+    /// either the old record or the new one, and is only readable by its owner. The directory is
+    /// synced too, so the new record survives a crash once this returns.
+    pub fn write(&self, udid: &str, record: &PairRecord) -> Result<(), PlistError> {
+        let path = self.path(udid)?;
+        let data = record.to_plist().to_format(PlistFormat::Xml, false)?;
+        fs::create_dir_all(&self.dir)?;
+
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let temp = self.dir.join(format!(".{}.{}-{}.tmp", udid, std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+        let result = write_file(&temp, &data).and_then(|_| fs::rename(&temp, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result?;
+
+        #[cfg(unix)]
+        fs::File::open(&self.dir)?.sync_all()?;
+
+        Ok(())
+    }
+
+    /// Removes the record for `udid` and returns whether there was one.
+    pub fn delete(&self, udid: &str) -> Result<bool, PlistError> {
+        match fs::remove_file(self.path(udid)?) {
+            Ok(()) => Ok(true),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(error.into())
+        }
+    }
+
+    /// The host's `SystemBUID` from usbmuxd's `SystemConfiguration.plist`, if there is one.
+    pub fn system_buid(&self) -> Result<Option<String>, PairRecordError> {
+        let data = match fs::read(self.dir.join(format!("{}.{}", SYSTEM_CONFIGURATION, EXTENSION))) {
+            Ok(data) => data,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into())
+        };
+        let dict = model::dict_copy(&Plist::from_memory(&data)?)?;
+
+        Ok(model::get_string(&dict, "SystemBUID")?)
+    }
+}
+
+fn write_file(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::{Getter, Plist};
+    use crate::model::ModelError;
+    use crate::pair_record::{PairRecord, PairRecordError, PairRecordStore};
+    use crate::plist_error::PlistError;
+
+    const UDID: &str = "00008030-001A35E80C41802E";
+
+    fn pem(label: &str) -> String {
+        format!("-----BEGIN {}-----\nTUlJQg==\n-----END {}-----\n", label, label)
+    }
+
+    fn record_xml() -> String {
+        let data = |label: &str| pem(label).bytes().map(|byte| byte as i8).collect::<Vec<i8>>();
+        let plist = Plist::from(std::collections::HashMap::from([
+            ("HostID", Plist::from("5AA9DE3D-1D1E-4E8D-9B5C-2C7E7B0D6F21")),
+            ("SystemBUID", Plist::from("1C4BFB26-6F5E-4E5B-9C9B-2AF2A1E3A5C9")),
+            ("HostCertificate", Plist::from(data("CERTIFICATE").as_slice())),
+            ("HostPrivateKey", Plist::from(data("RSA PRIVATE KEY").as_slice())),
+            ("DeviceCertificate", Plist::from(data("CERTIFICATE").as_slice())),
+            ("RootCertificate", Plist::from(data("CERTIFICATE").as_slice())),
+            ("RootPrivateKey", Plist::from(data("RSA PRIVATE KEY").as_slice())),
+            ("EscrowBag", Plist::from([1i8, 2, 3].as_slice())),
+            ("WiFiMACAddress", Plist::from("a4:83:e7:00:11:22")),
+            ("UDID", Plist::from(UDID)),
+        ]));
+
+        plist.xml().unwrap()
+    }
+
+    fn temp_dir() -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        std::env::temp_dir().join(format!(
+            "plist-rs-lockdown-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ))
+    }
+
+    #[test]
+    fn parse() {
+        let record = PairRecord::parse(record_xml().as_bytes()).unwrap();
+        assert_eq!(record.host_id, "5AA9DE3D-1D1E-4E8D-9B5C-2C7E7B0D6F21");
+        assert_eq!(record.host_private_key.as_deref(), Some(pem("RSA PRIVATE KEY").as_bytes()));
+        assert_eq!(record.escrow_bag, Some(vec![1, 2, 3]));
+        assert_eq!(record.wifi_mac_address.as_deref(), Some("a4:83:e7:00:11:22"));
+        assert_eq!(record.get_other("UDID").unwrap().as_str().unwrap(), UDID);
+        assert_eq!(PairRecord::from_plist(&record.to_plist()).unwrap(), record);
+        assert_eq!(record.get_other("HostID"), None);
+
+        let mut edited = PairRecord::parse(record_xml().as_bytes()).unwrap();
+        edited.host_id = "8F1B0C4E-2A6D-4B3F-9E7A-5C1D2E3F4A5B".to_owned();
+        edited.escrow_bag = None;
+        assert_eq!(PairRecord::from_plist(&edited.to_plist()).unwrap(), edited);
+
+        let pairing = record.to_pairing_plist();
+        assert!(pairing.get("HostCertificate").is_some());
+        assert!(pairing.get("HostPrivateKey").is_none());
+        assert!(pairing.get("EscrowBag").is_none());
+
+        let xml = record_xml().replace("<key>HostID</key>", "<key>Unused</key>");
+        assert!(matches!(PairRecord::parse(xml.as_bytes()), Err(PairRecordError::Model(ModelError::MissingKey(key))) if key == "HostID"));
+        let xml = record_xml().replace(&base64(&pem("CERTIFICATE")), "AAEC");
+        assert!(matches!(PairRecord::parse(xml.as_bytes()), Err(PairRecordError::Model(ModelError::InvalidValue { .. }))));
+    }
+
+    fn base64(text: &str) -> String {
+        let xml = Plist::from(text.bytes().map(|byte| byte as i8).collect::<Vec<i8>>().as_slice()).xml().unwrap();
+        let start = xml.find("<data>").unwrap() + "<data>".len();
+        let end = xml.find("</data>").unwrap();
+
+        xml[start..end].split_whitespace().collect()
+    }
+
+    #[test]
+    fn store() {
+        let dir = temp_dir();
+        let store = PairRecordStore::new(&dir);
+        assert!(store.read(UDID).unwrap().is_none());
+
+        let record = PairRecord::parse(record_xml().as_bytes()).unwrap();
+        store.write(UDID, &record).unwrap();
+        store.write("0123456789abcdef0123456789abcdef01234567", &record).unwrap();
+        fs::write(dir.join("SystemConfiguration.plist"), Plist::from(std::collections::HashMap::from([
+            ("SystemBUID", Plist::from("1C4BFB26-6F5E-4E5B-9C9B-2AF2A1E3A5C9")),
+        ])).xml().unwrap()).unwrap();
+
+        assert_eq!(store.read(UDID).unwrap().unwrap(), record);
+        assert_eq!(store.udids().unwrap(), vec![UDID.to_owned(), "0123456789abcdef0123456789abcdef01234567".to_owned()]);
+        assert_eq!(store.system_buid().unwrap().unwrap(), "1C4BFB26-6F5E-4E5B-9C9B-2AF2A1E3A5C9");
+        assert!(fs::read_dir(&dir).unwrap().all(|entry| !entry.unwrap().file_name().to_string_lossy().ends_with(".tmp")));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(store.path(UDID).unwrap()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        assert!(store.delete(UDID).unwrap());
+        assert!(!store.delete(UDID).unwrap());
+        assert!(matches!(store.path("../etc/passwd"), Err(PlistError::InvalidArg)));
+        assert!(matches!(store.path("SystemConfiguration"), Err(PlistError::InvalidArg)));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,500 @@
+//! Limits for parsing untrusted input.
+//!
+//! libplist has no limits of its own, so they are enforced in two steps: a cheap scan of the
+//! input rejects oversized input, deep nesting, and for the text formats too many objects or
+//! overlong strings and data, before libplist sees them. A walk of the parsed tree then checks
+//! every limit exactly. Beyond its size, binary input is only scanned in strict mode.
+
+use std::fmt::{Display, Formatter};
+use crate::{Getter, Plist, plist_array_get_size, plist_get_data_ptr, plist_get_string_ptr};
+use crate::bplist;
+use crate::format::PlistFormat;
+use crate::plist_error::PlistError;
+use crate::plist_node_type::PlistNodeType;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Limit {
+    /// Arrays and dictionaries nested in each other.
+    Depth,
+    /// Values and dictionary keys.
+    Objects,
+    /// Bytes in a string or dictionary key.
+    StringLength,
+    /// Bytes in a data value.
+    DataLength,
+    /// Bytes of input.
+    Size
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Depth => "nesting depth",
+            Self::Objects => "object count",
+            Self::StringLength => "string length",
+            Self::DataLength => "data length",
+            Self::Size => "input size"
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ParseOptions {
+    max_depth: usize,
+    max_objects: usize,
+    max_string_length: usize,
+    max_data_length: usize,
+    max_size: usize,
+    strict: bool
+}
+
+impl Default for ParseOptions {
+    /// Generous for any real plist: 256 levels, a million objects, 16 MiB strings and 64 MiB of
+    /// data or input, with binary input validated strictly.
+    fn default() -> Self {
+        ParseOptions {
+            max_depth: 256,
+            max_objects: 1 << 20,
+            max_string_length: 16 << 20,
+            max_data_length: 64 << 20,
+            max_size: 64 << 20,
+            strict: true
+        }
+    }
+}
+
+impl ParseOptions {
+    /// No limits besides the 4 GiB libplist can address, and no strict validation.
+    pub fn unlimited() -> Self {
+        ParseOptions {
+            max_depth: usize::MAX,
+            max_objects: usize::MAX,
+            max_string_length: usize::MAX,
+            max_data_length: usize::MAX,
+            max_size: u32::MAX as usize,
+            strict: false
+        }
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn max_objects(mut self, max_objects: usize) -> Self {
+        self.max_objects = max_objects;
+        self
+    }
+
+    pub fn max_string_length(mut self, max_string_length: usize) -> Self {
+        self.max_string_length = max_string_length;
+        self
+    }
+
+    pub fn max_data_length(mut self, max_data_length: usize) -> Self {
+        self.max_data_length = max_data_length;
+        self
+    }
+
+    /// Capped at the 4 GiB libplist can address.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size.min(u32::MAX as usize);
+        self
+    }
+
+    /// Whether binary input is checked by [`bplist::validate`](crate::bplist::validate) before
+    /// libplist parses it, which also applies the limits to the tree as expanded from shared
+    /// references.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Checks an already parsed plist against the limits other than the input size.
+    pub fn check(&self, plist: &Plist) -> Result<(), PlistError> {
+        let mut objects = 0;
+
+        self.check_node(plist, 0, &mut objects)
+    }
+
+    fn check_node(&self, plist: &Plist, depth: usize, objects: &mut usize) -> Result<(), PlistError> {
+        *objects += 1;
+        self.check_limit(Limit::Objects, *objects)?;
+
+        let p = plist.as_ptr()?;
+        let mut length = 0u64;
+        match plist.node_type() {
+            PlistNodeType::Array => {
+                self.check_limit(Limit::Depth, depth + 1)?;
+                let size = unsafe { plist_array_get_size(p) };
+                for index in 0..size as usize {
+                    self.check_node(&plist.get(index).ok_or(PlistError::Unknown)?, depth + 1, objects)?;
+                }
+            }
+            PlistNodeType::Dictionary => {
+                self.check_limit(Limit::Depth, depth + 1)?;
+                for (key, value) in Plist::new_with_weak(p).dict().ok_or(PlistError::Unknown)?.iter().bytes() {
+                    *objects += 1;
+                    self.check_limit(Limit::Objects, *objects)?;
+                    self.check_limit(Limit::StringLength, key.len())?;
+                    self.check_node(&value, depth + 1, objects)?;
+                }
+            }
+            PlistNodeType::String => {
+                unsafe { plist_get_string_ptr(p, &mut length) };
+                self.check_limit(Limit::StringLength, length as usize)?;
+            }
+            PlistNodeType::Data => {
+                unsafe { plist_get_data_ptr(p, &mut length) };
+                self.check_limit(Limit::DataLength, length as usize)?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn check_limit(&self, limit: Limit, value: usize) -> Result<(), PlistError> {
+        let max = match limit {
+            Limit::Depth => self.max_depth,
+            Limit::Objects => self.max_objects,
+            Limit::StringLength => self.max_string_length,
+            Limit::DataLength => self.max_data_length,
+            Limit::Size => self.max_size
+        };
+
+        if value > max {
+            return Err(PlistError::LimitExceeded { limit, max })
+        }
+
+        Ok(())
+    }
+
+    /// Rejects input that is certain to exceed a limit, without parsing it. For the text
+    /// formats the scan counts objects and measures strings and data too, always erring on the
+    /// low side, so libplist never builds a tree that breaks a limit the input already shows.
+    fn scan(&self, data: &[u8], format: PlistFormat) -> Result<(), PlistError> {
+        self.check_limit(Limit::Size, data.len())?;
+
+        match format {
+            // The trailer's object count includes objects the tree never references, so the
+            // exact count is left to `check`, or to `bplist::validate` in strict mode.
+            PlistFormat::Binary => match self.strict {
+                true => bplist::validate(data, self),
+                false => Ok(())
+            },
+            PlistFormat::Xml => self.scan_xml(data),
+            PlistFormat::Json | PlistFormat::OpenStep => self.scan_brackets(data, format)
+        }
+    }
+
+    fn count_object(&self, objects: &mut usize) -> Result<(), PlistError> {
+        *objects += 1;
+        self.check_limit(Limit::Objects, *objects)
+    }
+
+    fn scan_xml(&self, data: &[u8]) -> Result<(), PlistError> {
+        let mut depth = 0usize;
+        let mut objects = 0usize;
+        let mut rest = data;
+
+        while let Some(start) = rest.iter().position(|&byte| byte == b'<') {
+            rest = &rest[start..];
+            let end = if rest.starts_with(b"<!--") {
+                find(rest, b"-->")
+            } else if rest.starts_with(b"<![CDATA[") {
+                find(rest, b"]]>")
+            } else {
+                find(rest, b">")
+            };
+            let Some(end) = end else { break };
+            let tag = &rest[..end - 1];
+            rest = &rest[end..];
+
+            if tag.starts_with(b"</") || tag.starts_with(b"<!") || tag.starts_with(b"<?") {
+                if tag.starts_with(b"</dict") || tag.starts_with(b"</array") {
+                    depth = depth.saturating_sub(1);
+                }
+                continue
+            }
+
+            let Some(name) = XML_VALUES.iter().find(|name| is_element(tag, name)) else { continue };
+            self.count_object(&mut objects)?;
+            if tag.ends_with(b"/") {
+                continue
+            }
+
+            let content = &rest[..rest.iter().position(|&byte| byte == b'<').unwrap_or(rest.len())];
+            match *name {
+                b"<dict" | b"<array" => {
+                    depth += 1;
+                    self.check_limit(Limit::Depth, depth)?;
+                }
+                // Every entity stands for at least one byte.
+                b"<string" | b"<key" => {
+                    let entities = content.iter().filter(|&&byte| byte == b'&').count();
+                    let entity_bytes = content.split(|&byte| byte == b'&').skip(1)
+                        .map(|part| part.iter().position(|&byte| byte == b';').unwrap_or(0))
+                        .sum::<usize>();
+                    self.check_limit(Limit::StringLength, content.len() - entity_bytes - entities)?;
+                }
+                b"<data" => {
+                    let digits = content.iter().filter(|&&byte| byte.is_ascii_alphanumeric() || byte == b'+' || byte == b'/').count();
+                    self.check_limit(Limit::DataLength, digits * 3 / 4)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Scans JSON and OpenStep, which both nest with brackets and quote strings. Unquoted
+    /// tokens are read whole, so comments only start between them: OpenStep's unquoted strings
+    /// may contain `/`.
+    fn scan_brackets(&self, data: &[u8], format: PlistFormat) -> Result<(), PlistError> {
+        let delimiters: &[u8] = match format {
+            PlistFormat::Json => b"{}[],:\"",
+            _ => b"{}()<>=;,\"'"
+        };
+        let mut depth = 0usize;
+        let mut objects = 0usize;
+        let mut index = 0;
+
+        while index < data.len() {
+            match data[index] {
+                quote @ (b'"' | b'\'') => {
+                    index += 1;
+                    let mut length = 0;
+                    while index < data.len() && data[index] != quote {
+                        index += if data[index] == b'\\' { escape_length(&data[index..]) } else { 1 };
+                        length += 1;
+                    }
+                    self.count_object(&mut objects)?;
+                    self.check_limit(Limit::StringLength, length)?;
+                }
+                b'/' if data.get(index + 1) == Some(&b'/') => {
+                    index += find(&data[index..], b"\n").unwrap_or(data.len() - index);
+                    continue
+                }
+                b'/' if data.get(index + 1) == Some(&b'*') => {
+                    index += find(&data[index..], b"*/").unwrap_or(data.len() - index);
+                    continue
+                }
+                b'<' => {
+                    let length = data[index..].iter().position(|&byte| byte == b'>').unwrap_or(data.len() - index);
+                    let digits = data[index..index + length].iter().filter(|byte| byte.is_ascii_hexdigit()).count();
+                    self.count_object(&mut objects)?;
+                    self.check_limit(Limit::DataLength, digits / 2)?;
+                    index += length;
+                }
+                b'{' | b'[' | b'(' => {
+                    depth += 1;
+                    self.check_limit(Limit::Depth, depth)?;
+                    self.count_object(&mut objects)?;
+                }
+                b'}' | b']' | b')' => depth = depth.saturating_sub(1),
+                byte if byte.is_ascii_whitespace() || delimiters.contains(&byte) => {}
+                _ => {
+                    let length = data[index..].iter()
+                        .position(|byte| byte.is_ascii_whitespace() || delimiters.contains(byte))
+                        .unwrap_or(data.len() - index);
+                    self.count_object(&mut objects)?;
+                    // JSON's bare tokens are numbers and literals, OpenStep's are strings.
+                    if format == PlistFormat::OpenStep {
+                        self.check_limit(Limit::StringLength, length)?;
+                    }
+                    index += length;
+                    continue
+                }
+            }
+            index += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// The XML elements that each make one value or key.
+const XML_VALUES: [&[u8]; 10] = [b"<dict", b"<array", b"<key", b"<string", b"<integer", b"<real", b"<true", b"<false", b"<date", b"<data"];
+
+/// Input bytes taken by the escape sequence at the start of `data`, which decodes to at least
+/// one byte: the backslash, the escaped character and any code point digits after it.
+fn escape_length(data: &[u8]) -> usize {
+    let digits = match data.get(1) {
+        Some(b'u' | b'U') => data[2..].iter().take(4).take_while(|byte| byte.is_ascii_hexdigit()).count(),
+        Some(b'0'..=b'7') => data[2..].iter().take(2).take_while(|byte| (b'0'..=b'7').contains(*byte)).count(),
+        _ => 0
+    };
+
+    (2 + digits).min(data.len())
+}
+
+/// The index just past the first `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle).map(|start| start + needle.len())
+}
+
+/// Whether `tag` opens the element `name`, e.g. `<dict>` but not `<dictionary>`.
+fn is_element(tag: &[u8], name: &[u8]) -> bool {
+    tag.strip_prefix(name).is_some_and(|rest| rest.first().is_none_or(|&byte| byte == b'/' || byte.is_ascii_whitespace()))
+}
+
+impl Plist {
+    pub fn from_xml_with(xml: String, options: &ParseOptions) -> Result<Self, PlistError> {
+        options.scan(xml.as_bytes(), PlistFormat::Xml)?;
+        let plist = Self::from_xml(xml)?;
+        options.check(&plist)?;
+
+        Ok(plist)
+    }
+
+    pub fn from_bin_with(bin: &[u8], options: &ParseOptions) -> Result<Self, PlistError> {
+        options.scan(bin, PlistFormat::Binary)?;
+        let plist = Self::from_bin(bin)?;
+        options.check(&plist)?;
+
+        Ok(plist)
+    }
+
+    pub fn from_json_with(json: String, options: &ParseOptions) -> Result<Self, PlistError> {
+        options.scan(json.as_bytes(), PlistFormat::Json)?;
+        let plist = Self::from_json(json)?;
+        options.check(&plist)?;
+
+        Ok(plist)
+    }
+
+    pub fn from_openstep_with(openstep: String, options: &ParseOptions) -> Result<Self, PlistError> {
+        options.scan(openstep.as_bytes(), PlistFormat::OpenStep)?;
+        let plist = Self::from_openstep(openstep)?;
+        options.check(&plist)?;
+
+        Ok(plist)
+    }
+
+    pub fn from_memory_with(data: &[u8], options: &ParseOptions) -> Result<Self, PlistError> {
+        options.scan(data, PlistFormat::detect(data))?;
+        let plist = Self::from_memory(data)?;
+        options.check(&plist)?;
+
+        Ok(plist)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Plist;
+    use crate::parse_options::{Limit, ParseOptions};
+    use crate::plist_error::PlistError;
+
+    fn nested_xml(depth: usize) -> String {
+        format!("<plist version=\"1.0\">{}{}</plist>", "<array>".repeat(depth), "</array>".repeat(depth))
+    }
+
+    fn exceeded(result: Result<Plist, PlistError>) -> Option<Limit> {
+        match result {
+            Err(PlistError::LimitExceeded { limit, .. }) => Some(limit),
+            _ => None
+        }
+    }
+
+    #[test]
+    fn depth() {
+        let options = ParseOptions::default().max_depth(8);
+        assert!(Plist::from_xml_with(nested_xml(8), &options).is_ok());
+        assert_eq!(exceeded(Plist::from_xml_with(nested_xml(9), &options)), Some(Limit::Depth));
+        let commented = nested_xml(8).replacen("<array>", "<array><!-- <array><array> -->", 1);
+        assert!(Plist::from_xml_with(commented, &options).is_ok());
+
+        let json = format!("{}{}", "[".repeat(9), "]".repeat(9));
+        assert_eq!(exceeded(Plist::from_json_with(json, &options)), Some(Limit::Depth));
+        #[cfg(libplist_2_3)]
+        {
+            let json = format!(r#"["{}"]"#, "[".repeat(20));
+            assert!(Plist::from_json_with(json, &options).is_ok());
+        }
+
+        // `//` inside an unquoted string doesn't start a comment.
+        let openstep = format!("(a//,{}{})", "(".repeat(9), ")".repeat(9));
+        assert_eq!(exceeded(Plist::from_openstep_with(openstep, &options)), Some(Limit::Depth));
+        #[cfg(libplist_2_3)]
+        {
+            let openstep = format!("(a, // {}\n{}{})", "(".repeat(9), "(".repeat(7), ")".repeat(7));
+            assert!(Plist::from_openstep_with(openstep, &options).is_ok());
+        }
+
+        // Far deeper than libplist's recursive parsers survive; rejected before parsing.
+        let json = format!("{}{}", "[".repeat(1_000_000), "]".repeat(1_000_000));
+        assert_eq!(exceeded(Plist::from_json_with(json, &ParseOptions::default())), Some(Limit::Depth));
+    }
+
+    #[test]
+    fn objects() {
+        let json = format!("[{}]", vec!["1"; 100].join(","));
+        #[cfg(libplist_2_3)]
+        assert!(Plist::from_json_with(json.clone(), &ParseOptions::default().max_objects(101)).is_ok());
+        assert_eq!(exceeded(Plist::from_json_with(json, &ParseOptions::default().max_objects(100))), Some(Limit::Objects));
+
+        let json = r#"{"a": 1, "b": 2}"#.to_owned();
+        assert_eq!(exceeded(Plist::from_json_with(json, &ParseOptions::default().max_objects(4))), Some(Limit::Objects));
+
+        let bin = Plist::from((0..100).map(Plist::from).collect::<Vec<Plist>>()).bin().unwrap();
+        assert!(Plist::from_bin_with(&bin, &ParseOptions::default().max_objects(101)).is_ok());
+        assert_eq!(exceeded(Plist::from_bin_with(&bin, &ParseOptions::default().max_objects(100))), Some(Limit::Objects));
+
+        let mut bin = vec![b'b', b'p', b'l', b'i', b's', b't', b'0', b'0', 0x08];
+        bin.extend_from_slice(&[0; 32]);
+        let length = bin.len();
+        bin[length - 24..length - 16].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(Plist::from_bin_with(&bin, &ParseOptions::default()).is_err());
+    }
+
+    #[test]
+    fn lengths() {
+        let options = ParseOptions::default().max_string_length(4).max_data_length(2).max_size(64);
+
+        let xml = "<plist><dict><key>key</key><string>abcd</string></dict></plist>".to_owned();
+        assert!(Plist::from_xml_with(xml, &options).is_ok());
+        let xml = "<plist><dict><key>keys</key><data>AAEC</data></dict></plist>".to_owned();
+        assert_eq!(exceeded(Plist::from_xml_with(xml, &options)), Some(Limit::DataLength));
+        let json = r#"{"long key": 1}"#.to_owned();
+        assert_eq!(exceeded(Plist::from_json_with(json, &options)), Some(Limit::StringLength));
+
+        let data = format!("[\"{}\"]", "a".repeat(100)).into_bytes();
+        assert_eq!(exceeded(Plist::from_memory_with(&data, &options)), Some(Limit::Size));
+        assert_eq!(exceeded(Plist::from_memory_with(&data, &options.clone().max_size(1024))), Some(Limit::StringLength));
+        assert!(Plist::from_memory_with(&data, &ParseOptions::unlimited()).is_ok());
+    }
+
+    #[test]
+    fn text_scan() {
+        // Truncated, so only the scan can tell these apart from parse errors.
+        let options = ParseOptions::default().max_objects(3).max_string_length(4).max_data_length(2);
+        let json = r#"[1, "a", true, "#.to_owned();
+        assert_eq!(exceeded(Plist::from_json_with(json, &options)), Some(Limit::Objects));
+        let json = r#"["a\u00e9\"cde"#.to_owned();
+        assert_eq!(exceeded(Plist::from_json_with(json, &options)), Some(Limit::StringLength));
+        let openstep = "(abc/def, ".to_owned();
+        assert_eq!(exceeded(Plist::from_openstep_with(openstep, &options)), Some(Limit::StringLength));
+        let openstep = "(<0001 0203>, ".to_owned();
+        assert_eq!(exceeded(Plist::from_openstep_with(openstep, &options)), Some(Limit::DataLength));
+        let xml = "<plist><array><string>abcde</string>".to_owned();
+        assert_eq!(exceeded(Plist::from_xml_with(xml, &options)), Some(Limit::StringLength));
+        let xml = "<plist><array><true/><false/><integer>1</integer>".to_owned();
+        assert_eq!(exceeded(Plist::from_xml_with(xml, &options)), Some(Limit::Objects));
+
+        // Escapes and entities count as the bytes they stand for.
+        let xml = "<plist><string>&lt;&amp;&#x41;d</string></plist>".to_owned();
+        assert!(Plist::from_xml_with(xml, &options).is_ok());
+        #[cfg(libplist_2_3)]
+        {
+            let json = r#"["\u00e9\n"]"#.to_owned();
+            assert!(Plist::from_json_with(json, &options).is_ok());
+            let openstep = "(\"a\\nb\", <0001>)".to_owned();
+            assert!(Plist::from_openstep_with(openstep, &options).is_ok());
+        }
+    }
+}
@@ -0,0 +1,1045 @@
+//! Xcode project files (`project.pbxproj`).
+//!
+//! The file is an OpenStep plist, parsed with [`Plist::from_openstep`], holding a flat `objects`
+//! dictionary keyed by 24 digit hexadecimal IDs. Writing doesn't go through libplist:
+//! [`PbxProject::to_text`] reproduces Xcode's own layout, with one section per `isa`, sorted
+//! keys and `/* name */` annotations after object IDs, so a file that is read and written back
+//! unchanged comes out the same.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::Plist;
+use crate::dict::{DictGetter, DictSetter, PlistDict};
+use crate::model::{self, ModelError};
+use crate::plist_error::PlistError;
+use crate::plist_node_type::PlistNodeType;
+
+const PROJECT_FILE: &str = "project.pbxproj";
+const PROJECT_EXTENSION: &str = "xcodeproj";
+const PROJECT_LIST_COMMENT: &str = "Build configuration list for PBXProject \"";
+
+/// Objects Xcode writes on a single line.
+const INLINE_ISAS: [&str; 2] = ["PBXBuildFile", "PBXFileReference"];
+/// Keys holding IDs that Xcode leaves without a comment: objects in other projects, and the
+/// tested target in `TargetAttributes`.
+const UNCOMMENTED_ID_KEYS: [&str; 2] = ["remoteGlobalIDString", "TestTargetID"];
+
+#[derive(Debug)]
+pub enum PbxprojError {
+    Plist(PlistError),
+    Model(ModelError),
+    NoSuchObject(String)
+}
+
+impl Display for PbxprojError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Plist(error) => write!(f, "{}", error),
+            Self::Model(error) => write!(f, "{}", error),
+            Self::NoSuchObject(id) => write!(f, "No object with ID {}", id)
+        }
+    }
+}
+
+impl Error for PbxprojError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Plist(error) => Some(error),
+            Self::Model(error) => Some(error),
+            Self::NoSuchObject(_) => None
+        }
+    }
+}
+
+impl From<PlistError> for PbxprojError {
+    fn from(error: PlistError) -> Self {
+        Self::Plist(error)
+    }
+}
+
+impl From<ModelError> for PbxprojError {
+    fn from(error: ModelError) -> Self {
+        Self::Model(error)
+    }
+}
+
+impl From<std::io::Error> for PbxprojError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Plist(PlistError::Io(error))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildSetting {
+    Value(String),
+    List(Vec<String>)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Project {
+    pub id: String,
+    pub main_group: String,
+    pub product_ref_group: Option<String>,
+    pub targets: Vec<String>,
+    pub build_configuration_list: String
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Target {
+    pub id: String,
+    pub isa: String,
+    pub name: String,
+    pub product_name: Option<String>,
+    pub product_type: Option<String>,
+    pub build_phases: Vec<String>,
+    pub dependencies: Vec<String>,
+    pub build_configuration_list: String
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FileReference {
+    pub id: String,
+    pub name: Option<String>,
+    pub path: Option<String>,
+    pub source_tree: Option<String>,
+    pub last_known_file_type: Option<String>,
+    pub explicit_file_type: Option<String>
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Group {
+    pub id: String,
+    pub isa: String,
+    pub name: Option<String>,
+    pub path: Option<String>,
+    pub source_tree: Option<String>,
+    pub children: Vec<String>
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BuildConfiguration {
+    pub id: String,
+    pub name: String,
+    pub base_configuration_reference: Option<String>,
+    pub build_settings: Vec<(String, BuildSetting)>
+}
+
+pub struct PbxProject {
+    name: String,
+    root: Plist
+}
+
+impl PbxProject {
+    /// Parses the contents of a `project.pbxproj`. The project's name isn't stored in the file
+    /// itself, so it is taken from the existing comments when they have it.
+    pub fn parse(text: &str) -> Result<Self, PbxprojError> {
+        let name = text.find(PROJECT_LIST_COMMENT)
+            .map(|start| &text[start + PROJECT_LIST_COMMENT.len()..])
+            .and_then(|rest| rest.find('"').map(|end| rest[..end].to_owned()))
+            .unwrap_or_default();
+
+        Self::from_plist(&name, Plist::from_openstep(text.to_owned())?)
+    }
+
+    pub fn from_plist(name: &str, root: Plist) -> Result<Self, PbxprojError> {
+        let dict = model::view(&root).ok_or(ModelError::NotDictionary)?;
+        let objects = model::require(model::get_dict(&dict, "objects")?, "objects")?;
+        if let Some(error) = objects.iter().find_map(Result::err) {
+            return Err(model::invalid_key(error).within("objects").into())
+        }
+        let root_object = model::require(model::get_string(&dict, "rootObject")?, "rootObject")?;
+
+        let project = PbxProject {
+            name: name.to_owned(),
+            root
+        };
+        project.object_of(&root_object, &["PBXProject"])?;
+
+        Ok(project)
+    }
+
+    /// Reads `<name>.xcodeproj/project.pbxproj`, given either the bundle or the file inside it.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, PbxprojError> {
+        let (bundle, file) = Self::paths(path.as_ref());
+        let mut project = Self::parse(&fs::read_to_string(file)?)?;
+        if project.name.is_empty() {
+            if let Some(name) = bundle.file_stem().and_then(|stem| stem.to_str()) {
+                project.name = name.to_owned();
+            }
+        }
+
+        Ok(project)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), PbxprojError> {
+        let (_, file) = Self::paths(path.as_ref());
+        fs::write(file, self.to_text()?)?;
+
+        Ok(())
+    }
+
+    fn paths(path: &Path) -> (PathBuf, PathBuf) {
+        if path.extension().is_some_and(|extension| extension == PROJECT_EXTENSION) {
+            (path.to_owned(), path.join(PROJECT_FILE))
+        } else {
+            (path.parent().map(Path::to_owned).unwrap_or_default(), path.to_owned())
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+
+    pub fn as_plist(&self) -> &Plist {
+        &self.root
+    }
+
+    pub fn root_object_id(&self) -> String {
+        model::get_string(&self.root_dict(), "rootObject").ok().flatten().unwrap_or_default()
+    }
+
+    /// IDs that aren't valid UTF-8, which only edits through `as_plist` can add, are left out.
+    pub fn object_ids(&self) -> Vec<String> {
+        self.objects().iter().flatten().map(|(id, _)| id).collect()
+    }
+
+    pub fn isa(&self, id: &str) -> Option<String> {
+        model::get_string(&self.object(id).ok()?, "isa").ok().flatten()
+    }
+
+    /// Returns a copy of the raw object with `id`.
+    pub fn get_object(&self, id: &str) -> Option<Plist> {
+        self.objects().get(id).map(|object| object.copy())
+    }
+
+    /// Returns the IDs of all objects of the given `isa`, sorted like Xcode sorts them.
+    pub fn objects_of(&self, isa: &str) -> Vec<String> {
+        let mut ids = self.objects().iter().flatten()
+            .filter(|(_, object)| object.get_string("isa").as_deref() == Some(isa))
+            .map(|(id, _)| id)
+            .collect::<Vec<String>>();
+        ids.sort();
+
+        ids
+    }
+
+    pub fn project(&self) -> Result<Project, PbxprojError> {
+        let id = self.root_object_id();
+        let object = self.object_of(&id, &["PBXProject"])?;
+        let read = || -> Result<Project, ModelError> {
+            Ok(Project {
+                id: id.clone(),
+                main_group: model::require(model::get_string(&object, "mainGroup")?, "mainGroup")?,
+                product_ref_group: model::get_string(&object, "productRefGroup")?,
+                targets: model::get_strings(&object, "targets")?.unwrap_or_default(),
+                build_configuration_list: model::require(model::get_string(&object, "buildConfigurationList")?, "buildConfigurationList")?
+            })
+        };
+
+        read().map_err(|error| PbxprojError::Model(error.within(&id)))
+    }
+
+    pub fn targets(&self) -> Result<Vec<Target>, PbxprojError> {
+        self.project()?.targets.iter().map(|id| self.target(id)).collect()
+    }
+
+    pub fn target(&self, id: &str) -> Result<Target, PbxprojError> {
+        let object = self.object_of(id, &["PBXNativeTarget", "PBXAggregateTarget", "PBXLegacyTarget"])?;
+        let read = || -> Result<Target, ModelError> {
+            Ok(Target {
+                id: id.to_owned(),
+                isa: model::require(model::get_string(&object, "isa")?, "isa")?,
+                name: model::require(model::get_string(&object, "name")?, "name")?,
+                product_name: model::get_string(&object, "productName")?,
+                product_type: model::get_string(&object, "productType")?,
+                build_phases: model::get_strings(&object, "buildPhases")?.unwrap_or_default(),
+                dependencies: model::get_strings(&object, "dependencies")?.unwrap_or_default(),
+                build_configuration_list: model::require(model::get_string(&object, "buildConfigurationList")?, "buildConfigurationList")?
+            })
+        };
+
+        read().map_err(|error| PbxprojError::Model(error.within(id)))
+    }
+
+    pub fn target_named(&self, name: &str) -> Result<Option<Target>, PbxprojError> {
+        Ok(self.targets()?.into_iter().find(|target| target.name == name))
+    }
+
+    pub fn file_reference(&self, id: &str) -> Result<FileReference, PbxprojError> {
+        let object = self.object_of(id, &["PBXFileReference"])?;
+        let string = |key: &str| model::get_string(&object, key).map_err(|error| PbxprojError::Model(error.within(id)));
+
+        Ok(FileReference {
+            id: id.to_owned(),
+            name: string("name")?,
+            path: string("path")?,
+            source_tree: string("sourceTree")?,
+            last_known_file_type: string("lastKnownFileType")?,
+            explicit_file_type: string("explicitFileType")?
+        })
+    }
+
+    pub fn group(&self, id: &str) -> Result<Group, PbxprojError> {
+        let object = self.object_of(id, &["PBXGroup", "PBXVariantGroup", "XCVersionGroup"])?;
+        let read = || -> Result<Group, ModelError> {
+            Ok(Group {
+                id: id.to_owned(),
+                isa: model::require(model::get_string(&object, "isa")?, "isa")?,
+                name: model::get_string(&object, "name")?,
+                path: model::get_string(&object, "path")?,
+                source_tree: model::get_string(&object, "sourceTree")?,
+                children: model::get_strings(&object, "children")?.unwrap_or_default()
+            })
+        };
+
+        read().map_err(|error| PbxprojError::Model(error.within(id)))
+    }
+
+    pub fn build_configuration(&self, id: &str) -> Result<BuildConfiguration, PbxprojError> {
+        let object = self.object_of(id, &["XCBuildConfiguration"])?;
+        let read = || -> Result<BuildConfiguration, ModelError> {
+            Ok(BuildConfiguration {
+                id: id.to_owned(),
+                name: model::require(model::get_string(&object, "name")?, "name")?,
+                base_configuration_reference: model::get_string(&object, "baseConfigurationReference")?,
+                build_settings: model::get_entries(&object, "buildSettings", build_setting)?.unwrap_or_default()
+            })
+        };
+
+        read().map_err(|error| PbxprojError::Model(error.within(id)))
+    }
+
+    /// Returns the configurations of a target or, for the `PBXProject` ID, of the project.
+    pub fn build_configurations(&self, id: &str) -> Result<Vec<BuildConfiguration>, PbxprojError> {
+        let list_id = model::get_string(&self.object(id)?, "buildConfigurationList")?
+            .ok_or_else(|| ModelError::MissingKey("buildConfigurationList".to_owned()).within(id))?;
+        let list = self.object_of(&list_id, &["XCConfigurationList"])?;
+        let ids = model::get_strings(&list, "buildConfigurations")
+            .map_err(|error| error.within(&list_id))?
+            .unwrap_or_default();
+
+        ids.iter().map(|id| self.build_configuration(id)).collect()
+    }
+
+    /// Sets or, with `None`, removes a build setting of one configuration.
+    pub fn set_build_setting(&self, configuration_id: &str, key: &str, value: Option<BuildSetting>) -> Result<(), PbxprojError> {
+        let object = self.object_of(configuration_id, &["XCBuildConfiguration"])?;
+        let settings = match model::get_dict(&object, "buildSettings").map_err(|error| error.within(configuration_id))? {
+            Some(settings) => settings,
+            None => {
+                object.set("buildSettings", model::new_dict().into_plist());
+                object.get("buildSettings").and_then(|settings| settings.dict()).unwrap()
+            }
+        };
+        model::set(&settings, key, value.map(|value| match value {
+            BuildSetting::Value(value) => Plist::from(value),
+            BuildSetting::List(values) => model::strings(&values)
+        }));
+
+        Ok(())
+    }
+
+    /// Sets a build setting in every configuration of a target or of the project.
+    pub fn set_build_setting_all(&self, id: &str, key: &str, value: Option<BuildSetting>) -> Result<(), PbxprojError> {
+        for configuration in self.build_configurations(id)? {
+            self.set_build_setting(&configuration.id, key, value.clone())?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds a group named `path` inside the group `parent_id` and returns its ID.
+    pub fn add_group(&self, parent_id: &str, path: &str) -> Result<String, PbxprojError> {
+        self.group(parent_id)?;
+        let id = self.generate_id(&["PBXGroup", parent_id, path]);
+        self.add_object(&id, &[
+            ("isa", Plist::from("PBXGroup")),
+            ("children", Plist::from(Vec::new())),
+            ("path", Plist::from(path)),
+            ("sourceTree", Plist::from("<group>"))
+        ]);
+        self.append(parent_id, "children", &id)?;
+
+        Ok(id)
+    }
+
+    /// Adds a file reference for `path`, relative to the group `group_id`, and returns its ID.
+    pub fn add_file(&self, group_id: &str, path: &str) -> Result<String, PbxprojError> {
+        self.group(group_id)?;
+        let id = self.generate_id(&["PBXFileReference", group_id, path]);
+        let mut fields = vec![("isa", Plist::from("PBXFileReference"))];
+        if let Some(file_type) = file_type(path) {
+            fields.push(("lastKnownFileType", Plist::from(file_type)));
+        }
+        fields.push(("path", Plist::from(path)));
+        fields.push(("sourceTree", Plist::from("<group>")));
+        self.add_object(&id, &fields);
+        self.append(group_id, "children", &id)?;
+
+        Ok(id)
+    }
+
+    /// Adds a file to a target's build phase of the given `isa` (for example
+    /// `PBXSourcesBuildPhase`), creating the phase if the target has none, and returns the ID of
+    /// the new `PBXBuildFile`.
+    pub fn add_to_build_phase(&self, target_id: &str, phase_isa: &str, file_id: &str) -> Result<String, PbxprojError> {
+        let target = self.target(target_id)?;
+        self.object(file_id)?;
+
+        let phase_id = match target.build_phases.iter().find(|id| self.isa(id).as_deref() == Some(phase_isa)) {
+            Some(id) => id.to_owned(),
+            None => {
+                let id = self.generate_id(&[phase_isa, target_id]);
+                self.add_object(&id, &[
+                    ("isa", Plist::from(phase_isa)),
+                    ("buildActionMask", Plist::from("2147483647")),
+                    ("files", Plist::from(Vec::new())),
+                    ("runOnlyForDeploymentPostprocessing", Plist::from("0"))
+                ]);
+                self.append(target_id, "buildPhases", &id)?;
+                id
+            }
+        };
+
+        let id = self.generate_id(&["PBXBuildFile", &phase_id, file_id]);
+        self.add_object(&id, &[
+            ("isa", Plist::from("PBXBuildFile")),
+            ("fileRef", Plist::from(file_id))
+        ]);
+        self.append(&phase_id, "files", &id)?;
+
+        Ok(id)
+    }
+
+    fn root_dict(&self) -> PlistDict {
+        model::view(&self.root).unwrap()
+    }
+
+    fn objects(&self) -> PlistDict {
+        self.root_dict().get("objects").and_then(|objects| objects.dict()).unwrap()
+    }
+
+    fn object(&self, id: &str) -> Result<PlistDict, PbxprojError> {
+        self.objects().get(id)
+            .and_then(|object| object.dict())
+            .ok_or_else(|| PbxprojError::NoSuchObject(id.to_owned()))
+    }
+
+    fn object_of(&self, id: &str, isas: &[&str]) -> Result<PlistDict, PbxprojError> {
+        let object = self.object(id)?;
+        match model::get_string(&object, "isa").map_err(|error| error.within(id))? {
+            Some(isa) if isas.contains(&isa.as_str()) => Ok(object),
+            Some(isa) => Err(PbxprojError::Model(ModelError::InvalidValue {
+                key: format!("{}.isa", id),
+                reason: format!("expected {} but found {}", isas.join(" or "), isa)
+            })),
+            None => Err(PbxprojError::Model(ModelError::MissingKey(format!("{}.isa", id))))
+        }
+    }
+
+    fn add_object(&self, id: &str, fields: &[(&str, Plist)]) {
+        let object = model::new_dict();
+        for (key, value) in fields {
+            object.set(*key, value.copy());
+        }
+        self.objects().set(id, object.into_plist());
+    }
+
+    fn append(&self, id: &str, key: &str, item: &str) -> Result<(), PbxprojError> {
+        let object = self.object(id)?;
+        match model::get_array(&object, key).map_err(|error| error.within(id))? {
+            Some(array) => array.append(Plist::from(item)),
+            None => object.set(key, Plist::from(vec![Plist::from(item)]))
+        }
+
+        Ok(())
+    }
+
+    /// Derives a 24 digit ID from `seed`, like Xcode's IDs but stable for the same edits, on
+    /// every platform and Rust release.
+    fn generate_id(&self, seed: &[&str]) -> String {
+        let objects = self.objects();
+        for attempt in 0u64.. {
+            let half = |salt: u8| {
+                let mut bytes = Vec::new();
+                for part in seed {
+                    bytes.extend_from_slice(part.as_bytes());
+                    bytes.push(0);
+                }
+                bytes.extend_from_slice(&attempt.to_le_bytes());
+                bytes.push(salt);
+                fnv1a(&bytes)
+            };
+            let id = format!("{:016X}{:08X}", half(0), half(1) as u32);
+            if objects.get(id.as_str()).is_none() {
+                return id
+            }
+        }
+
+        unreachable!()
+    }
+}
+
+/// Formats like [`PbxProject::to_text`]; a project that can't be written makes `to_string` panic.
+impl Display for PbxProject {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_text().map_err(|_| std::fmt::Error)?)
+    }
+}
+
+impl PbxProject {
+    /// The project in Xcode's layout. Numbers and booleans are written as the strings Xcode
+    /// uses for them; dates, UIDs and nulls have no OpenStep form and are an error.
+    pub fn to_text(&self) -> Result<String, PbxprojError> {
+        let writer = Writer {
+            comments: self.comments(),
+            out: String::new()
+        };
+
+        Ok(writer.write(&self.root_dict())?)
+    }
+
+    /// The annotations Xcode writes after each object ID.
+    fn comments(&self) -> HashMap<String, String> {
+        // The writer rejects IDs that aren't UTF-8, so they need no comment.
+        let objects = self.objects().iter().flatten().collect::<Vec<(String, Plist)>>();
+        let mut names = HashMap::new();
+        let mut phases = HashMap::new();
+        let mut owners = HashMap::new();
+
+        for (id, object) in &objects {
+            let isa = object.get_string("isa").unwrap_or_default();
+            let name = object.get_string("name");
+            let name = match isa.as_str() {
+                "PBXProject" => Some("Project object".to_owned()),
+                "PBXTargetDependency" | "PBXContainerItemProxy" => Some(isa.clone()),
+                "XCSwiftPackageProductDependency" => object.get_string("productName"),
+                "XCRemoteSwiftPackageReference" => object.get_string("repositoryURL").map(|url| {
+                    let repository = url.trim_end_matches('/').rsplit('/').next().unwrap_or_default().trim_end_matches(".git").to_owned();
+                    format!("XCRemoteSwiftPackageReference \"{}\"", repository)
+                }),
+                "PBXFileReference" | "PBXGroup" | "PBXVariantGroup" | "PBXReferenceProxy" | "XCVersionGroup" => name.or_else(|| object.get_string("path")),
+                isa if isa.ends_with("BuildPhase") => name.or_else(|| Some(default_phase_name(isa).to_owned())),
+                _ => name
+            };
+            if isa.ends_with("BuildPhase") {
+                for file in object.get_strings("files") {
+                    phases.insert(file, name.clone().unwrap_or_default());
+                }
+            }
+            if let Some(list) = object.get_string("buildConfigurationList") {
+                let owner = if isa == "PBXProject" { self.name.clone() } else { object.get_string("name").unwrap_or_default() };
+                owners.insert(list, format!("Build configuration list for {} \"{}\"", isa, owner));
+            }
+            if let Some(name) = name {
+                names.insert(id.clone(), name);
+            }
+        }
+
+        for (id, object) in &objects {
+            match object.get_string("isa").as_deref() {
+                Some("PBXBuildFile") => {
+                    let file = object.get_string("fileRef")
+                        .and_then(|file| names.get(&file).cloned())
+                        .or_else(|| object.get_string("productRef").and_then(|product| names.get(&product).cloned()))
+                        .unwrap_or_else(|| "(null)".to_owned());
+                    let phase = phases.get(id).cloned().unwrap_or_default();
+                    names.insert(id.clone(), format!("{} in {}", file, phase));
+                }
+                Some("XCConfigurationList") => {
+                    if let Some(owner) = owners.remove(id) {
+                        names.insert(id.clone(), owner);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        names
+    }
+}
+
+struct Writer {
+    comments: HashMap<String, String>,
+    out: String
+}
+
+impl Writer {
+    fn write(mut self, root: &PlistDict) -> Result<String, ModelError> {
+        self.out.push_str("// !$*UTF8*$!\n{\n");
+        for (key, value) in sorted(root)? {
+            self.out.push('\t');
+            self.out.push_str(&quote(&key));
+            self.out.push_str(" = ");
+            if key == "objects" {
+                self.write_objects(&value)
+            } else {
+                self.write_value(&key, &value, 1, false)
+            }.map_err(|error| error.within(&key))?;
+            self.out.push_str(";\n");
+        }
+        self.out.push_str("}\n");
+
+        Ok(self.out)
+    }
+
+    fn write_objects(&mut self, objects: &Plist) -> Result<(), ModelError> {
+        let mut sections: Vec<(String, Vec<(String, Plist)>)> = Vec::new();
+        for entry in model::view(objects).unwrap().iter() {
+            let (id, object) = entry.map_err(model::invalid_key)?;
+            let isa = object.get_string("isa").unwrap_or_default();
+            match sections.iter_mut().find(|(name, _)| *name == isa) {
+                Some((_, section)) => section.push((id, object)),
+                None => sections.push((isa, vec![(id, object)]))
+            }
+        }
+        sections.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+
+        self.out.push_str("{\n");
+        for (isa, mut section) in sections {
+            section.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+            self.out.push_str(&format!("\n/* Begin {} section */\n", isa));
+            for (id, object) in section {
+                self.out.push_str("\t\t");
+                self.write_string(&id, false);
+                self.out.push_str(" = ");
+                self.write_value("", &object, 2, INLINE_ISAS.contains(&isa.as_str())).map_err(|error| error.within(&id))?;
+                self.out.push_str(";\n");
+            }
+            self.out.push_str(&format!("/* End {} section */\n", isa));
+        }
+        self.out.push_str("\t}");
+
+        Ok(())
+    }
+
+    fn write_value(&mut self, key: &str, value: &Plist, indent: usize, inline: bool) -> Result<(), ModelError> {
+        match value.node_type() {
+            PlistNodeType::Dictionary => {
+                let dict = model::view(value).unwrap();
+                self.out.push('{');
+                if !inline {
+                    self.out.push('\n');
+                }
+                for (key, value) in sorted(&dict)? {
+                    if !inline {
+                        self.out.push_str(&"\t".repeat(indent + 1));
+                    }
+                    self.out.push_str(&quote(&key));
+                    self.out.push_str(" = ");
+                    self.write_value(&key, &value, indent + 1, inline).map_err(|error| error.within(&key))?;
+                    self.out.push_str(if inline { "; " } else { ";\n" });
+                }
+                if !inline {
+                    self.out.push_str(&"\t".repeat(indent));
+                }
+                self.out.push('}');
+            }
+            PlistNodeType::Array => {
+                self.out.push('(');
+                if !inline {
+                    self.out.push('\n');
+                }
+                for (index, item) in (0..).map_while(|index: usize| value.get_index(index)).enumerate() {
+                    if !inline {
+                        self.out.push_str(&"\t".repeat(indent + 1));
+                    }
+                    self.write_value(key, &item, indent + 1, inline).map_err(|error| error.within(&index.to_string()))?;
+                    self.out.push_str(if inline { ", " } else { ",\n" });
+                }
+                if !inline {
+                    self.out.push_str(&"\t".repeat(indent));
+                }
+                self.out.push(')');
+            }
+            PlistNodeType::String | PlistNodeType::Key => {
+                let string = value.as_str_lossy().unwrap_or_default();
+                self.write_string(&string, UNCOMMENTED_ID_KEYS.contains(&key));
+            }
+            PlistNodeType::UInt => match value.as_int() {
+                Some(int) => self.out.push_str(&quote(&int.to_string())),
+                None => self.out.push_str(&value.as_uint().unwrap().to_string())
+            },
+            PlistNodeType::Real => self.out.push_str(&quote(&value.as_real().unwrap().to_string())),
+            PlistNodeType::Boolean => self.out.push_str(if value.as_bool().unwrap() { "YES" } else { "NO" }),
+            PlistNodeType::Data => {
+                let data = value.as_data().unwrap();
+                self.out.push('<');
+                self.out.extend(data.iter().map(|byte| format!("{:02x}", *byte as u8)));
+                self.out.push('>');
+            }
+            node_type => return Err(ModelError::InvalidValue {
+                key: String::new(),
+                reason: format!("{:?} can't be written to a project file", node_type)
+            })
+        }
+
+        Ok(())
+    }
+
+    fn write_string(&mut self, string: &str, skip_comment: bool) {
+        self.out.push_str(&quote(string));
+        if !skip_comment {
+            if let Some(comment) = self.comments.get(string) {
+                self.out.push_str(&format!(" /* {} */", comment));
+            }
+        }
+    }
+}
+
+/// The entries of a dictionary in Xcode's order: `isa` first, then sorted by key.
+fn sorted(dict: &PlistDict) -> Result<Vec<(String, Plist)>, ModelError> {
+    let mut entries = dict.iter()
+        .map(|entry| entry.map_err(model::invalid_key))
+        .collect::<Result<Vec<(String, Plist)>, ModelError>>()?;
+    entries.sort_by(|(lhs, _), (rhs, _)| (lhs != "isa", lhs).cmp(&(rhs != "isa", rhs)));
+
+    Ok(entries)
+}
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher`, its output is fixed, so generated IDs don't change
+/// between builds.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+fn quote(string: &str) -> String {
+    let bare = !string.is_empty()
+        && string.chars().all(|c| c.is_ascii_alphanumeric() || "_$/:.".contains(c))
+        && !string.contains("//")
+        && !string.contains("___");
+    if bare {
+        return string.to_owned()
+    }
+
+    let mut quoted = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+fn default_phase_name(isa: &str) -> &str {
+    match isa {
+        "PBXSourcesBuildPhase" => "Sources",
+        "PBXFrameworksBuildPhase" => "Frameworks",
+        "PBXResourcesBuildPhase" => "Resources",
+        "PBXHeadersBuildPhase" => "Headers",
+        "PBXCopyFilesBuildPhase" => "CopyFiles",
+        "PBXShellScriptBuildPhase" => "ShellScript",
+        "PBXRezBuildPhase" => "Rez",
+        isa => isa
+    }
+}
+
+fn file_type(path: &str) -> Option<&'static str> {
+    let extension = path.rsplit_once('.')?.1;
+    let file_type = match extension {
+        "swift" => "sourcecode.swift",
+        "m" => "sourcecode.c.objc",
+        "mm" => "sourcecode.cpp.objcpp",
+        "c" => "sourcecode.c.c",
+        "cpp" | "cc" | "cxx" => "sourcecode.cpp.cpp",
+        "h" => "sourcecode.c.h",
+        "hpp" => "sourcecode.cpp.h",
+        "metal" => "sourcecode.metal",
+        "plist" => "text.plist.xml",
+        "strings" => "text.plist.strings",
+        "stringsdict" => "text.plist.stringsdict",
+        "json" => "text.json",
+        "entitlements" => "text.plist.entitlements",
+        "xcconfig" => "text.xcconfig",
+        "storyboard" => "file.storyboard",
+        "xib" => "file.xib",
+        "xcassets" => "folder.assetcatalog",
+        "png" => "image.png",
+        "framework" => "wrapper.framework",
+        "xcframework" => "wrapper.xcframework",
+        "a" => "archive.ar",
+        "dylib" => "compiled.mach-o.dylib",
+        "app" => "wrapper.application",
+        _ => return None
+    };
+
+    Some(file_type)
+}
+
+fn build_setting(key: &str, value: &Plist) -> Result<BuildSetting, ModelError> {
+    match value.node_type() {
+        PlistNodeType::Array => (0..).map_while(|index: usize| value.get_index(index))
+            .enumerate()
+            .map(|(index, item)| model::string_value(&format!("{}.{}", key, index), &item))
+            .collect::<Result<Vec<String>, ModelError>>()
+            .map(BuildSetting::List),
+        _ => model::string_value(key, value).map(BuildSetting::Value)
+    }
+}
+
+/// Lookups on raw objects that treat anything missing or mistyped as absent, for annotations.
+trait ObjectExt {
+    fn get_string(&self, key: &str) -> Option<String>;
+    fn get_strings(&self, key: &str) -> Vec<String>;
+    fn get_index(&self, index: usize) -> Option<Plist>;
+}
+
+impl ObjectExt for Plist {
+    fn get_string(&self, key: &str) -> Option<String> {
+        crate::Getter::get(self, key).and_then(|value| value.as_str())
+    }
+
+    fn get_strings(&self, key: &str) -> Vec<String> {
+        crate::Getter::get(self, key)
+            .map(|array| (0..).map_while(|index: usize| array.get_index(index)).filter_map(|item| item.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    fn get_index(&self, index: usize) -> Option<Plist> {
+        if self.node_type() != PlistNodeType::Array {
+            return None
+        }
+
+        crate::Getter::get(self, index)
+    }
+}
+
+#[cfg(all(test, libplist_2_3))]
+mod tests {
+    use crate::{Getter, Plist};
+    use crate::dict::DictSetter;
+    use crate::model::ModelError;
+    use crate::pbxproj::{BuildSetting, PbxProject, PbxprojError};
+
+    const PROJECT: &str = r#"// !$*UTF8*$!
+{
+	archiveVersion = 1;
+	classes = {
+	};
+	objectVersion = 56;
+	objects = {
+
+/* Begin PBXBuildFile section */
+		1A0000000000000000000001 /* main.swift in Sources */ = {isa = PBXBuildFile; fileRef = 1A0000000000000000000002 /* main.swift */; };
+/* End PBXBuildFile section */
+
+/* Begin PBXFileReference section */
+		1A0000000000000000000002 /* main.swift */ = {isa = PBXFileReference; lastKnownFileType = sourcecode.swift; path = main.swift; sourceTree = "<group>"; };
+		1A0000000000000000000003 /* Example */ = {isa = PBXFileReference; explicitFileType = "compiled.mach-o.executable"; includeInIndex = 0; path = Example; sourceTree = BUILT_PRODUCTS_DIR; };
+/* End PBXFileReference section */
+
+/* Begin PBXGroup section */
+		1A0000000000000000000010 = {
+			isa = PBXGroup;
+			children = (
+				1A0000000000000000000011 /* Sources */,
+				1A0000000000000000000012 /* Products */,
+			);
+			sourceTree = "<group>";
+		};
+		1A0000000000000000000011 /* Sources */ = {
+			isa = PBXGroup;
+			children = (
+				1A0000000000000000000002 /* main.swift */,
+			);
+			path = Sources;
+			sourceTree = "<group>";
+		};
+		1A0000000000000000000012 /* Products */ = {
+			isa = PBXGroup;
+			children = (
+				1A0000000000000000000003 /* Example */,
+			);
+			name = Products;
+			sourceTree = "<group>";
+		};
+/* End PBXGroup section */
+
+/* Begin PBXNativeTarget section */
+		1A0000000000000000000020 /* Example */ = {
+			isa = PBXNativeTarget;
+			buildConfigurationList = 1A0000000000000000000040 /* Build configuration list for PBXNativeTarget "Example" */;
+			buildPhases = (
+				1A0000000000000000000030 /* Sources */,
+			);
+			buildRules = (
+			);
+			dependencies = (
+			);
+			name = Example;
+			productName = Example;
+			productReference = 1A0000000000000000000003 /* Example */;
+			productType = "com.apple.product-type.tool";
+		};
+/* End PBXNativeTarget section */
+
+/* Begin PBXProject section */
+		1A0000000000000000000050 /* Project object */ = {
+			isa = PBXProject;
+			attributes = {
+				BuildIndependentTargetsInParallel = 1;
+				LastUpgradeCheck = 1500;
+			};
+			buildConfigurationList = 1A0000000000000000000041 /* Build configuration list for PBXProject "Example" */;
+			compatibilityVersion = "Xcode 14.0";
+			developmentRegion = en;
+			hasScannedForEncodings = 0;
+			knownRegions = (
+				en,
+				Base,
+			);
+			mainGroup = 1A0000000000000000000010;
+			productRefGroup = 1A0000000000000000000012 /* Products */;
+			projectDirPath = "";
+			projectRoot = "";
+			targets = (
+				1A0000000000000000000020 /* Example */,
+			);
+		};
+/* End PBXProject section */
+
+/* Begin PBXSourcesBuildPhase section */
+		1A0000000000000000000030 /* Sources */ = {
+			isa = PBXSourcesBuildPhase;
+			buildActionMask = 2147483647;
+			files = (
+				1A0000000000000000000001 /* main.swift in Sources */,
+			);
+			runOnlyForDeploymentPostprocessing = 0;
+		};
+/* End PBXSourcesBuildPhase section */
+
+/* Begin XCBuildConfiguration section */
+		1A0000000000000000000060 /* Debug */ = {
+			isa = XCBuildConfiguration;
+			buildSettings = {
+				OTHER_SWIFT_FLAGS = (
+					"-D",
+					DEBUG,
+				);
+				PRODUCT_NAME = "$(TARGET_NAME)";
+				SWIFT_VERSION = 5.0;
+			};
+			name = Debug;
+		};
+		1A0000000000000000000061 /* Debug */ = {
+			isa = XCBuildConfiguration;
+			buildSettings = {
+				MACOSX_DEPLOYMENT_TARGET = 13.0;
+				SDKROOT = macosx;
+			};
+			name = Debug;
+		};
+/* End XCBuildConfiguration section */
+
+/* Begin XCConfigurationList section */
+		1A0000000000000000000040 /* Build configuration list for PBXNativeTarget "Example" */ = {
+			isa = XCConfigurationList;
+			buildConfigurations = (
+				1A0000000000000000000060 /* Debug */,
+			);
+			defaultConfigurationIsVisible = 0;
+			defaultConfigurationName = Debug;
+		};
+		1A0000000000000000000041 /* Build configuration list for PBXProject "Example" */ = {
+			isa = XCConfigurationList;
+			buildConfigurations = (
+				1A0000000000000000000061 /* Debug */,
+			);
+			defaultConfigurationIsVisible = 0;
+			defaultConfigurationName = Debug;
+		};
+/* End XCConfigurationList section */
+	};
+	rootObject = 1A0000000000000000000050 /* Project object */;
+}
+"#;
+
+    #[test]
+    fn round_trip() {
+        let project = PbxProject::parse(PROJECT).unwrap();
+        assert_eq!(project.name(), "Example");
+        assert_eq!(project.to_string(), PROJECT);
+    }
+
+    #[test]
+    fn lookup() {
+        let project = PbxProject::parse(PROJECT).unwrap();
+        assert_eq!(project.project().unwrap().main_group, "1A0000000000000000000010");
+
+        let target = project.target_named("Example").unwrap().unwrap();
+        assert_eq!(target.product_type.as_deref(), Some("com.apple.product-type.tool"));
+        assert_eq!(project.file_reference("1A0000000000000000000002").unwrap().path.as_deref(), Some("main.swift"));
+        assert_eq!(project.group("1A0000000000000000000011").unwrap().children, ["1A0000000000000000000002"]);
+        assert_eq!(project.objects_of("PBXFileReference").len(), 2);
+        assert!(project.file_reference("1A0000000000000000000010").is_err());
+        assert!(project.group("0000").is_err());
+
+        let configurations = project.build_configurations(&target.id).unwrap();
+        assert_eq!(configurations[0].build_settings[0], ("OTHER_SWIFT_FLAGS".to_owned(), BuildSetting::List(vec!["-D".to_owned(), "DEBUG".to_owned()])));
+    }
+
+    #[test]
+    fn edit() {
+        let project = PbxProject::parse(PROJECT).unwrap();
+        let target = project.target_named("Example").unwrap().unwrap();
+
+        project.set_build_setting_all(&target.id, "SWIFT_VERSION", Some(BuildSetting::Value("6.0".to_owned()))).unwrap();
+        project.set_build_setting("1A0000000000000000000060", "OTHER_SWIFT_FLAGS", None).unwrap();
+
+        let group = project.add_group("1A0000000000000000000011", "Models").unwrap();
+        let file = project.add_file(&group, "User.swift").unwrap();
+        let build_file = project.add_to_build_phase(&target.id, "PBXSourcesBuildPhase", &file).unwrap();
+
+        let text = project.to_text().unwrap();
+        assert!(text.contains("SWIFT_VERSION = 6.0;"));
+        assert!(!text.contains("OTHER_SWIFT_FLAGS"));
+        assert!(text.contains(&format!("{} /* User.swift in Sources */ = {{isa = PBXBuildFile; fileRef = {} /* User.swift */; }};", build_file, file)));
+        assert!(text.contains(&format!("{} /* User.swift */ = {{isa = PBXFileReference; lastKnownFileType = sourcecode.swift; path = User.swift; sourceTree = \"<group>\"; }};", file)));
+
+        let project = PbxProject::parse(&text).unwrap();
+        assert_eq!(project.group(&group).unwrap().children, [file]);
+        assert_eq!(project.group("1A0000000000000000000011").unwrap().children.len(), 2);
+        assert_eq!(project.to_string(), text);
+    }
+
+    #[test]
+    fn scalars() {
+        let project = PbxProject::parse(PROJECT).unwrap();
+        let settings = project.as_plist().get("objects").unwrap().get("1A0000000000000000000060").unwrap().get("buildSettings").unwrap().dict().unwrap();
+        settings.set("ENABLE_TESTABILITY", Plist::from(true));
+        settings.set("CURRENT_PROJECT_VERSION", Plist::from(3u64));
+        settings.set("SIGNATURE", Plist::from([1i8, -1].as_slice()));
+
+        let text = project.to_text().unwrap();
+        assert!(text.contains("ENABLE_TESTABILITY = YES;"));
+        assert!(text.contains("CURRENT_PROJECT_VERSION = 3;"));
+        assert!(text.contains("SIGNATURE = <01ff>;"));
+
+        settings.set("BUILD_DATE", Plist::from_date(0, 0));
+        match project.to_text() {
+            Err(PbxprojError::Model(ModelError::InvalidValue { key, .. })) => assert_eq!(key, "objects.1A0000000000000000000060.buildSettings.BUILD_DATE"),
+            result => panic!("unexpected {:?}", result)
+        }
+    }
+
+    #[test]
+    fn fnv1a() {
+        assert_eq!(super::fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(super::fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(super::fnv1a(b"foobar"), 0x85944171f73967e8);
+    }
+}
@@ -1,6 +1,9 @@
 use std::error::Error;
 use std::fmt::{Display, Formatter};
-use crate::{plist_err_t, plist_err_t_PLIST_ERR_FORMAT, plist_err_t_PLIST_ERR_INVALID_ARG, plist_err_t_PLIST_ERR_NO_MEM, plist_err_t_PLIST_ERR_PARSE, plist_err_t_PLIST_ERR_UNKNOWN};
+use std::io;
+#[cfg(libplist_2_3)]
+use crate::{plist_err_t, plist_err_t_PLIST_ERR_FORMAT, plist_err_t_PLIST_ERR_INVALID_ARG, plist_err_t_PLIST_ERR_IO, plist_err_t_PLIST_ERR_NO_MEM, plist_err_t_PLIST_ERR_PARSE, plist_err_t_PLIST_ERR_SUCCESS};
+use crate::parse_options::Limit;
 
 #[derive(Debug)]
 pub enum PlistError {
@@ -9,9 +12,14 @@ pub enum PlistError {
     Parse,
     NoMemory,
     Unknown,
-    Dealloc
+    Dealloc,
+    Unsupported,
+    Io(io::Error),
+    /// The input exceeded a limit of its `ParseOptions`.
+    LimitExceeded { limit: Limit, max: usize }
 }
 
+#[cfg(libplist_2_3)]
 impl PlistError {
     pub fn try_from(error: plist_err_t) -> Result<(), Self> {
         match Self::from(error) {
@@ -21,6 +29,7 @@ impl PlistError {
     }
 }
 
+#[cfg(libplist_2_3)]
 impl PlistError {
     fn from(error: plist_err_t) -> Option<Self> {
         let error = match error {
@@ -28,8 +37,9 @@ impl PlistError {
             plist_err_t_PLIST_ERR_FORMAT => Self::Format,
             plist_err_t_PLIST_ERR_PARSE => Self::Parse,
             plist_err_t_PLIST_ERR_NO_MEM => Self::NoMemory,
-            plist_err_t_PLIST_ERR_UNKNOWN => Self::Unknown,
-            _ => return None
+            plist_err_t_PLIST_ERR_IO => Self::Io(io::Error::other("libplist I/O error")),
+            plist_err_t_PLIST_ERR_SUCCESS => return None,
+            _ => Self::Unknown
         };
 
         Some(error)
@@ -44,11 +54,40 @@ impl Display for PlistError {
             Self::Parse => "Parse failed",
             Self::NoMemory => "No memory",
             Self::Dealloc => "Dealloc plist_t",
-            Self::Unknown => "Unknown"
+            Self::Unknown => "Unknown",
+            Self::Unsupported => "Not supported by the linked libplist",
+            Self::Io(error) => return write!(f, "{}", error),
+            Self::LimitExceeded { limit, max } => return write!(f, "The {} exceeds the limit of {}", limit, max)
         };
 
         write!(f, "{}", s)
     }
 }
 
-impl Error for PlistError {}
+impl Error for PlistError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for PlistError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+#[cfg(all(test, libplist_2_3))]
+mod tests {
+    use crate::plist_error::PlistError;
+
+    #[test]
+    fn unknown_errors() {
+        // Codes without a variant must not be mistaken for success.
+        assert!(PlistError::try_from(0).is_ok());
+        assert!(matches!(PlistError::try_from(-5), Err(PlistError::Io(_))));
+        assert!(matches!(PlistError::try_from(-100), Err(PlistError::Unknown)));
+    }
+}
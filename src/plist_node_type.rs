@@ -1,6 +1,11 @@
-use crate::{plist_type, plist_type_PLIST_ARRAY, plist_type_PLIST_BOOLEAN, plist_type_PLIST_DATA, plist_type_PLIST_DATE, plist_type_PLIST_DICT, plist_type_PLIST_KEY, plist_type_PLIST_NONE, plist_type_PLIST_NULL, plist_type_PLIST_REAL, plist_type_PLIST_STRING, plist_type_PLIST_UID, plist_type_PLIST_UINT};
+use crate::{plist_type, plist_type_PLIST_ARRAY, plist_type_PLIST_BOOLEAN, plist_type_PLIST_DATA, plist_type_PLIST_DATE, plist_type_PLIST_DICT, plist_type_PLIST_KEY, plist_type_PLIST_NONE, plist_type_PLIST_REAL, plist_type_PLIST_STRING, plist_type_PLIST_UID};
+#[cfg(not(libplist_2_3))]
+use crate::plist_type_PLIST_UINT;
+// libplist 2.3 renamed PLIST_UINT to PLIST_INT and kept the old name only as a macro.
+#[cfg(libplist_2_3)]
+use crate::{plist_type_PLIST_INT as plist_type_PLIST_UINT, plist_type_PLIST_NULL};
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
 pub enum PlistNodeType {
     Boolean,
     UInt,
@@ -29,6 +34,7 @@ impl PlistNodeType {
             plist_type_PLIST_DATA => Self::Data,
             plist_type_PLIST_KEY => Self::Key,
             plist_type_PLIST_UID => Self::UID,
+            #[cfg(libplist_2_3)]
             plist_type_PLIST_NULL => Self::Null,
             plist_type_PLIST_NONE => Self::None,
             _ => panic!("Unexpected plist_type")
@@ -0,0 +1,385 @@
+//! Provisioning profiles (`.mobileprovision`, `.provisionprofile`).
+//!
+//! A profile is a CMS `SignedData` envelope whose encapsulated content is an XML plist. The
+//! envelope is walked with a small DER reader, so no crypto library is needed; the signature is
+//! not verified.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::Plist;
+use crate::entitlements::Entitlements;
+use crate::model::{self, ModelError};
+use crate::plist_error::PlistError;
+use crate::plist_node_type::PlistNodeType;
+use crate::value::MAC_EPOCH;
+
+// 1.2.840.113549.1.7.2 and 1.2.840.113549.1.7.1
+const SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x01];
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_CONTEXT_0: u8 = 0xa0;
+const CONSTRUCTED: u8 = 0x20;
+/// How deep indefinite length elements may nest before the envelope is rejected.
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug)]
+pub enum ProfileError {
+    Cms(&'static str),
+    Plist(PlistError),
+    Model(ModelError)
+}
+
+impl Display for ProfileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cms(reason) => write!(f, "Invalid CMS envelope: {}", reason),
+            Self::Plist(error) => write!(f, "Invalid payload: {}", error),
+            Self::Model(error) => write!(f, "Invalid payload: {}", error)
+        }
+    }
+}
+
+impl Error for ProfileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Cms(_) => None,
+            Self::Plist(error) => Some(error),
+            Self::Model(error) => Some(error)
+        }
+    }
+}
+
+impl From<PlistError> for ProfileError {
+    fn from(error: PlistError) -> Self {
+        Self::Plist(error)
+    }
+}
+
+impl From<ModelError> for ProfileError {
+    fn from(error: ModelError) -> Self {
+        Self::Model(error)
+    }
+}
+
+#[derive(Debug)]
+pub struct ProvisioningProfile {
+    pub uuid: String,
+    pub name: Option<String>,
+    pub app_id_name: Option<String>,
+    pub team_identifiers: Vec<String>,
+    pub team_name: Option<String>,
+    pub platforms: Vec<String>,
+    pub creation_date: Option<SystemTime>,
+    pub expiration_date: SystemTime,
+    pub entitlements: Entitlements,
+    pub provisioned_devices: Vec<String>,
+    pub provisions_all_devices: bool,
+    /// The DER encoded certificates allowed to sign with this profile.
+    pub developer_certificates: Vec<Vec<u8>>,
+    plist: Plist
+}
+
+impl ProvisioningProfile {
+    /// Parses a profile from the raw CMS envelope.
+    pub fn parse(data: &[u8]) -> Result<Self, ProfileError> {
+        let payload = extract_payload(data)?;
+
+        Self::from_plist(Plist::from_memory(&payload)?)
+    }
+
+    /// Reads the typed fields of an already extracted payload.
+    pub fn from_plist(plist: Plist) -> Result<Self, ProfileError> {
+        let dict = model::dict_copy(&plist)?;
+
+        let developer_certificates = match model::get_array(&dict, "DeveloperCertificates")? {
+            Some(array) => array.into_iter().enumerate()
+                .map(|(index, item)| item.as_data().ok_or_else(|| ModelError::InvalidType {
+                    key: format!("DeveloperCertificates.{}", index),
+                    expected: PlistNodeType::Data
+                }))
+                .map(|data| data.map(|data| data.into_iter().map(|byte| byte as u8).collect()))
+                .collect::<Result<Vec<Vec<u8>>, ModelError>>()?,
+            None => Vec::new()
+        };
+        let entitlements = match model::get(&dict, "Entitlements", PlistNodeType::Dictionary)? {
+            Some(entitlements) => Entitlements::from_plist(&entitlements).map_err(|error| error.within("Entitlements"))?,
+            None => Entitlements::from_plist(&model::new_dict().into_plist())?
+        };
+
+        Ok(ProvisioningProfile {
+            uuid: model::require(model::get_string(&dict, "UUID")?, "UUID")?,
+            name: model::get_string(&dict, "Name")?,
+            app_id_name: model::get_string(&dict, "AppIDName")?,
+            team_identifiers: model::get_strings(&dict, "TeamIdentifier")?.unwrap_or_default(),
+            team_name: model::get_string(&dict, "TeamName")?,
+            platforms: model::get_strings(&dict, "Platform")?.unwrap_or_default(),
+            creation_date: model::get_date(&dict, "CreationDate")?.map(to_system_time),
+            expiration_date: to_system_time(model::require(model::get_date(&dict, "ExpirationDate")?, "ExpirationDate")?),
+            entitlements,
+            provisioned_devices: model::get_strings(&dict, "ProvisionedDevices")?.unwrap_or_default(),
+            provisions_all_devices: model::get_bool(&dict, "ProvisionsAllDevices")?.unwrap_or(false),
+            developer_certificates,
+            plist
+        })
+    }
+
+    /// The whole payload, including keys without a typed field.
+    pub fn plist(&self) -> &Plist {
+        &self.plist
+    }
+
+    pub fn is_expired_at(&self, time: SystemTime) -> bool {
+        time >= self.expiration_date
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(SystemTime::now())
+    }
+
+    /// Whether an app with this profile may be installed on the device with `udid`.
+    pub fn allows_device(&self, udid: &str) -> bool {
+        self.provisions_all_devices || self.provisioned_devices.iter().any(|device| device.eq_ignore_ascii_case(udid))
+    }
+}
+
+/// Returns the content encapsulated in a CMS `SignedData` envelope.
+pub fn extract_payload(data: &[u8]) -> Result<Vec<u8>, ProfileError> {
+    let (content_info, _) = Tlv::read(data)?;
+    let mut content_info = content_info.expect(TAG_SEQUENCE)?.children();
+    content_info.next_tlv()?.expect_oid(SIGNED_DATA)?;
+    let signed_data = content_info.next_tlv()?.expect(TAG_CONTEXT_0)?.children().next_tlv()?;
+
+    let mut signed_data = signed_data.expect(TAG_SEQUENCE)?.children();
+    signed_data.next_tlv()?.expect(TAG_INTEGER)?;
+    signed_data.next_tlv()?.expect(TAG_SET)?;
+    let mut encap = signed_data.next_tlv()?.expect(TAG_SEQUENCE)?.children();
+    encap.next_tlv()?.expect_oid(DATA)?;
+    if encap.is_empty() {
+        return Err(ProfileError::Cms("detached content"))
+    }
+    let content = encap.next_tlv()?.expect(TAG_CONTEXT_0)?.children().next_tlv()?;
+
+    content.octets()
+}
+
+fn to_system_time((sec, usec): (i32, i32)) -> SystemTime {
+    let since_mac_epoch = Duration::from_secs(sec.unsigned_abs() as u64) + Duration::from_micros(usec.unsigned_abs() as u64);
+    let mac_epoch = UNIX_EPOCH + Duration::from_secs(MAC_EPOCH as u64);
+
+    if sec < 0 {
+        mac_epoch - since_mac_epoch
+    } else {
+        mac_epoch + since_mac_epoch
+    }
+}
+
+struct Tlv<'a> {
+    tag: u8,
+    content: &'a [u8]
+}
+
+struct Children<'a>(&'a [u8]);
+
+impl<'a> Tlv<'a> {
+    /// Reads one BER element, returning it and the bytes that follow. Indefinite lengths are
+    /// accepted since some signing tools emit them.
+    fn read(data: &'a [u8]) -> Result<(Self, &'a [u8]), ProfileError> {
+        Self::read_nested(data, 0)
+    }
+
+    fn read_nested(data: &'a [u8], depth: usize) -> Result<(Self, &'a [u8]), ProfileError> {
+        let (&tag, rest) = data.split_first().ok_or(ProfileError::Cms("unexpected end of data"))?;
+        if tag & 0x1f == 0x1f {
+            return Err(ProfileError::Cms("unsupported high tag number"))
+        }
+        let (&first, rest) = rest.split_first().ok_or(ProfileError::Cms("unexpected end of data"))?;
+
+        if first == 0x80 {
+            if tag & CONSTRUCTED == 0 {
+                return Err(ProfileError::Cms("indefinite length on a primitive element"))
+            }
+            if depth == MAX_DEPTH {
+                return Err(ProfileError::Cms("elements nested too deeply"))
+            }
+            let mut remaining = rest;
+            loop {
+                if remaining.starts_with(&[0, 0]) {
+                    let content = &rest[..rest.len() - remaining.len()];
+                    return Ok((Tlv { tag, content }, &remaining[2..]))
+                }
+                let (_, next) = Self::read_nested(remaining, depth + 1)?;
+                remaining = next;
+            }
+        }
+
+        let (length, rest) = if first < 0x80 {
+            (first as usize, rest)
+        } else {
+            let count = (first & 0x7f) as usize;
+            if count > std::mem::size_of::<usize>() || rest.len() < count {
+                return Err(ProfileError::Cms("invalid length"))
+            }
+            let length = rest[..count].iter().fold(0usize, |length, &byte| length << 8 | byte as usize);
+            (length, &rest[count..])
+        };
+        if rest.len() < length {
+            return Err(ProfileError::Cms("length exceeds the data"))
+        }
+
+        Ok((Tlv { tag, content: &rest[..length] }, &rest[length..]))
+    }
+
+    fn expect(self, tag: u8) -> Result<Self, ProfileError> {
+        if self.tag != tag {
+            return Err(ProfileError::Cms("unexpected element"))
+        }
+
+        Ok(self)
+    }
+
+    fn expect_oid(self, oid: &[u8]) -> Result<(), ProfileError> {
+        if self.expect(TAG_OID)?.content != oid {
+            return Err(ProfileError::Cms("unexpected content type"))
+        }
+
+        Ok(())
+    }
+
+    fn children(&self) -> Children<'a> {
+        Children(self.content)
+    }
+
+    /// The bytes of an OCTET STRING, joining the segments of a constructed one.
+    fn octets(self) -> Result<Vec<u8>, ProfileError> {
+        match self.tag {
+            TAG_OCTET_STRING => Ok(self.content.to_vec()),
+            tag if tag == TAG_OCTET_STRING | CONSTRUCTED => {
+                let mut data = Vec::new();
+                let mut children = self.children();
+                while !children.is_empty() {
+                    data.extend_from_slice(children.next_tlv()?.expect(TAG_OCTET_STRING)?.content);
+                }
+                Ok(data)
+            }
+            _ => Err(ProfileError::Cms("content is not an octet string"))
+        }
+    }
+}
+
+impl<'a> Children<'a> {
+    fn next_tlv(&mut self) -> Result<Tlv<'a>, ProfileError> {
+        let (tlv, rest) = Tlv::read(self.0)?;
+        self.0 = rest;
+
+        Ok(tlv)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+    use crate::provisioning::{extract_payload, ProvisioningProfile, DATA, SIGNED_DATA};
+
+    const PAYLOAD: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>AppIDName</key>
+	<string>Example</string>
+	<key>CreationDate</key>
+	<date>2024-01-01T00:00:00Z</date>
+	<key>DeveloperCertificates</key>
+	<array>
+		<data>MIIBAA==</data>
+	</array>
+	<key>Entitlements</key>
+	<dict>
+		<key>application-identifier</key>
+		<string>ABCDE12345.com.example.app</string>
+		<key>get-task-allow</key>
+		<true/>
+	</dict>
+	<key>ExpirationDate</key>
+	<date>2030-01-01T00:00:00Z</date>
+	<key>Name</key>
+	<string>Example Development</string>
+	<key>ProvisionedDevices</key>
+	<array>
+		<string>00008030-001A2B3C4D5E6F70</string>
+	</array>
+	<key>TeamIdentifier</key>
+	<array>
+		<string>ABCDE12345</string>
+	</array>
+	<key>UUID</key>
+	<string>0b5d7c3a-6a4f-4e52-9d3e-2f1c0a9b8e7d</string>
+</dict>
+</plist>
+"#;
+
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut data = vec![tag];
+        match content.len() {
+            length if length < 0x80 => data.push(length as u8),
+            length if length < 0x100 => data.extend([0x81, length as u8]),
+            length => data.extend([0x82, (length >> 8) as u8, length as u8])
+        }
+        data.extend_from_slice(content);
+
+        data
+    }
+
+    fn envelope(content: Vec<u8>) -> Vec<u8> {
+        let encap = der(0x30, &[der(0x06, DATA), der(0xa0, &content)].concat());
+        let signed_data = der(0x30, &[der(0x02, &[1]), der(0x31, &[]), encap, der(0x31, &[])].concat());
+
+        der(0x30, &[der(0x06, SIGNED_DATA), der(0xa0, &signed_data)].concat())
+    }
+
+    #[test]
+    fn parse() {
+        let data = envelope(der(0x04, PAYLOAD.as_bytes()));
+        let profile = ProvisioningProfile::parse(&data).unwrap();
+
+        assert_eq!(profile.uuid, "0b5d7c3a-6a4f-4e52-9d3e-2f1c0a9b8e7d");
+        assert_eq!(profile.team_identifiers, ["ABCDE12345"]);
+        assert_eq!(profile.expiration_date, UNIX_EPOCH + Duration::from_secs(1_893_456_000));
+        assert_eq!(profile.creation_date, Some(UNIX_EPOCH + Duration::from_secs(1_704_067_200)));
+        assert_eq!(profile.developer_certificates, [vec![0x30, 0x82, 0x01, 0x00]]);
+        assert_eq!(profile.entitlements.application_identifier().as_deref(), Some("ABCDE12345.com.example.app"));
+        assert!(profile.allows_device("00008030-001a2b3c4d5e6f70"));
+        assert!(!profile.is_expired_at(UNIX_EPOCH + Duration::from_secs(1_800_000_000)));
+        assert!(profile.is_expired_at(UNIX_EPOCH + Duration::from_secs(1_900_000_000)));
+    }
+
+    #[test]
+    fn constructed_indefinite_content() {
+        let (head, tail) = PAYLOAD.as_bytes().split_at(100);
+        let mut content = vec![0x24, 0x80];
+        content.extend(der(0x04, head));
+        content.extend(der(0x04, tail));
+        content.extend([0, 0]);
+
+        assert_eq!(extract_payload(&envelope(content)).unwrap(), PAYLOAD.as_bytes());
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(extract_payload(&[]).is_err());
+        assert!(extract_payload(PAYLOAD.as_bytes()).is_err());
+        let data = envelope(der(0x04, PAYLOAD.as_bytes()));
+        assert!(extract_payload(&data[..data.len() / 2]).is_err());
+        assert!(extract_payload(&[0x30, 0x84, 0xff, 0xff, 0xff, 0xff]).is_err());
+        assert!(extract_payload(&[0x30, 0x80].repeat(10_000)).is_err());
+    }
+}
@@ -0,0 +1,638 @@
+//! Localization files: `.strings` tables and `.stringsdict` plural rules.
+//!
+//! `.strings` files are parsed here rather than with [`Plist::from_openstep`] so that the
+//! comment above each entry, which carries the note for translators, survives a round trip. They
+//! are usually UTF-16 with a byte order mark, but UTF-8 is accepted too; [`StringsFile::encode`]
+//! writes back whichever encoding was read.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use crate::Plist;
+use crate::dict::DictSetter;
+use crate::model::{self, ModelError};
+use crate::plist_error::PlistError;
+
+const FORMAT_KEY: &str = "NSStringLocalizedFormatKey";
+const SPEC_TYPE_KEY: &str = "NSStringFormatSpecTypeKey";
+const VALUE_TYPE_KEY: &str = "NSStringFormatValueTypeKey";
+const PLURAL_RULE_TYPE: &str = "NSStringPluralRuleType";
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct StringsError {
+    pub line: usize,
+    pub reason: String
+}
+
+impl Display for StringsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl Error for StringsError {}
+
+#[derive(Debug)]
+pub enum StringsDictError {
+    Plist(PlistError),
+    Model(ModelError)
+}
+
+impl Display for StringsDictError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Plist(error) => write!(f, "{}", error),
+            Self::Model(error) => write!(f, "Invalid stringsdict: {}", error)
+        }
+    }
+}
+
+impl Error for StringsDictError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Plist(error) => Some(error),
+            Self::Model(error) => Some(error)
+        }
+    }
+}
+
+impl From<PlistError> for StringsDictError {
+    fn from(error: PlistError) -> Self {
+        Self::Plist(error)
+    }
+}
+
+impl From<ModelError> for StringsDictError {
+    fn from(error: ModelError) -> Self {
+        Self::Model(error)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StringsEntry {
+    pub key: String,
+    pub value: String,
+    pub comment: Option<String>
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StringsFile {
+    pub entries: Vec<StringsEntry>,
+    pub encoding: Encoding
+}
+
+impl StringsFile {
+    pub fn new() -> Self {
+        StringsFile {
+            entries: Vec::new(),
+            encoding: Encoding::Utf16Le
+        }
+    }
+
+    /// Decodes and parses a file, detecting the encoding from the byte order mark.
+    pub fn parse(data: &[u8]) -> Result<Self, StringsError> {
+        let (text, encoding) = decode(data)?;
+        let mut file = Self::parse_str(&text)?;
+        file.encoding = encoding;
+
+        Ok(file)
+    }
+
+    pub fn parse_str(text: &str) -> Result<Self, StringsError> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            position: 0,
+            line: 1
+        };
+        let mut entries = Vec::new();
+
+        loop {
+            let comment = parser.skip_whitespace()?;
+            if parser.peek().is_none() {
+                break
+            }
+            let key = parser.string()?;
+            parser.skip_whitespace()?;
+            let value = match parser.peek() {
+                Some('=') => {
+                    parser.position += 1;
+                    parser.skip_whitespace()?;
+                    let value = parser.string()?;
+                    parser.skip_whitespace()?;
+                    value
+                }
+                _ => key.clone()
+            };
+            parser.expect(';')?;
+            entries.push(StringsEntry { key, value, comment });
+        }
+
+        Ok(StringsFile {
+            entries,
+            encoding: Encoding::Utf8
+        })
+    }
+
+    /// Serializes the table in `self.encoding`, with a byte order mark for UTF-16.
+    pub fn encode(&self) -> Vec<u8> {
+        let text = self.to_string();
+        match self.encoding {
+            Encoding::Utf8 => text.into_bytes(),
+            Encoding::Utf16Le => [0xfeffu16].into_iter().chain(text.encode_utf16()).flat_map(u16::to_le_bytes).collect(),
+            Encoding::Utf16Be => [0xfeffu16].into_iter().chain(text.encode_utf16()).flat_map(u16::to_be_bytes).collect()
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|entry| entry.key == key).map(|entry| entry.value.as_str())
+    }
+
+    /// Replaces the value of `key`, keeping its comment, or appends a new entry.
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.entries.iter_mut().find(|entry| entry.key == key) {
+            Some(entry) => entry.value = value.to_owned(),
+            None => self.entries.push(StringsEntry {
+                key: key.to_owned(),
+                value: value.to_owned(),
+                comment: None
+            })
+        }
+    }
+
+    /// A dictionary from keys to values; comments are dropped.
+    pub fn to_plist(&self) -> Plist {
+        let dict = model::new_dict();
+        for entry in &self.entries {
+            dict.set(entry.key.as_str(), Plist::from(entry.value.as_str()));
+        }
+
+        dict.into_plist()
+    }
+
+    pub fn from_plist(plist: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(plist)?;
+        let entries = dict.iter()
+            .map(|entry| {
+                let (key, value) = entry.map_err(model::invalid_key)?;
+                model::string_value(&key, &value).map(|value| StringsEntry { key, value, comment: None })
+            })
+            .collect::<Result<Vec<StringsEntry>, ModelError>>()?;
+
+        Ok(StringsFile {
+            entries,
+            encoding: Encoding::Utf16Le
+        })
+    }
+}
+
+impl Default for StringsFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for StringsFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (index, entry) in self.entries.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            if let Some(comment) = &entry.comment {
+                writeln!(f, "/* {} */", comment)?;
+            }
+            writeln!(f, "{} = {};", quote(&entry.key), quote(&entry.value))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Errors are reported on the line of the first byte that couldn't be decoded.
+fn decode(data: &[u8]) -> Result<(String, Encoding), StringsError> {
+    let invalid = |decoded: &str, reason: &str| StringsError {
+        line: decoded.matches('\n').count() + 1,
+        reason: reason.to_owned()
+    };
+    let utf16 = |data: &[u8], read: fn([u8; 2]) -> u16| {
+        let pairs = data.chunks_exact(2);
+        let odd = !pairs.remainder().is_empty();
+        let mut text = String::new();
+        for c in char::decode_utf16(pairs.map(|pair| read([pair[0], pair[1]]))) {
+            match c {
+                Ok(c) => text.push(c),
+                Err(_) => return Err(invalid(&text, "invalid UTF-16"))
+            }
+        }
+        if odd {
+            return Err(invalid(&text, "odd number of bytes in UTF-16 data"))
+        }
+
+        Ok(text)
+    };
+
+    if let Some(data) = data.strip_prefix(b"\xff\xfe") {
+        Ok((utf16(data, u16::from_le_bytes)?, Encoding::Utf16Le))
+    } else if let Some(data) = data.strip_prefix(b"\xfe\xff") {
+        Ok((utf16(data, u16::from_be_bytes)?, Encoding::Utf16Be))
+    } else {
+        let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+        let text = String::from_utf8(data.to_vec()).map_err(|error| {
+            let valid = &data[..error.utf8_error().valid_up_to()];
+            invalid(std::str::from_utf8(valid).unwrap(), "invalid UTF-8")
+        })?;
+        Ok((text, Encoding::Utf8))
+    }
+}
+
+fn quote(string: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            _ => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    line: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+
+        Some(c)
+    }
+
+    fn error(&self, reason: &str) -> StringsError {
+        StringsError {
+            line: self.line,
+            reason: reason.to_owned()
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), StringsError> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(self.error(&format!("expected '{}' but found '{}'", expected, c))),
+            None => Err(self.error(&format!("expected '{}' but found the end of the file", expected)))
+        }
+    }
+
+    /// Skips whitespace and comments, returning the text of the last block comment.
+    fn skip_whitespace(&mut self) -> Result<Option<String>, StringsError> {
+        let mut comment = None;
+        loop {
+            match (self.peek(), self.chars.get(self.position + 1)) {
+                (Some(c), _) if c.is_whitespace() => {
+                    self.next();
+                }
+                (Some('/'), Some('*')) => {
+                    self.position += 2;
+                    let start = self.position;
+                    loop {
+                        match self.next() {
+                            Some('*') if self.peek() == Some('/') => break,
+                            Some(_) => {}
+                            None => return Err(self.error("unterminated comment"))
+                        }
+                    }
+                    let text = self.chars[start..self.position - 1].iter().collect::<String>();
+                    self.position += 1;
+                    comment = Some(text.trim().to_owned());
+                }
+                (Some('/'), Some('/')) => {
+                    while !matches!(self.next(), Some('\n') | None) {}
+                }
+                _ => return Ok(comment)
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, StringsError> {
+        if self.peek() != Some('"') {
+            let start = self.position;
+            while self.peek().is_some_and(|c| c.is_alphanumeric() || "_$+/:.-".contains(c)) {
+                self.position += 1;
+            }
+            if start == self.position {
+                return Err(self.error("expected a string"))
+            }
+            return Ok(self.chars[start..self.position].iter().collect())
+        }
+
+        self.position += 1;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.next() {
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some('r') => string.push('\r'),
+                    Some('U') | Some('u') => {
+                        let hex = (0..4).filter_map(|_| self.next()).collect::<String>();
+                        let unit = u16::from_str_radix(&hex, 16).map_err(|_| self.error("invalid \\U escape"))?;
+                        let c = if (0xd800..0xdc00).contains(&unit) && self.chars.get(self.position..self.position + 2) == Some(&['\\', 'U']) {
+                            self.position += 2;
+                            let hex = (0..4).filter_map(|_| self.next()).collect::<String>();
+                            let low = u16::from_str_radix(&hex, 16).map_err(|_| self.error("invalid \\U escape"))?;
+                            char::decode_utf16([unit, low]).next().and_then(Result::ok)
+                        } else {
+                            char::from_u32(unit as u32)
+                        };
+                        string.push(c.ok_or_else(|| self.error("invalid \\U escape"))?);
+                    }
+                    Some(c) => string.push(c),
+                    None => return Err(self.error("unterminated string"))
+                },
+                Some(c) => string.push(c),
+                None => return Err(self.error("unterminated string"))
+            }
+        }
+    }
+}
+
+/// A `.stringsdict` file: one entry per localized format string.
+#[derive(Debug, PartialEq)]
+pub struct StringsDict {
+    pub entries: Vec<(String, PluralEntry)>
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PluralEntry {
+    /// The format with `%#@variable@` placeholders, `NSStringLocalizedFormatKey`.
+    pub format: String,
+    pub variables: Vec<(String, PluralRule)>
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PluralRule {
+    /// The printf format of the number, `NSStringFormatValueTypeKey`, for example `d`.
+    pub value_type: Option<String>,
+    pub zero: Option<String>,
+    pub one: Option<String>,
+    pub two: Option<String>,
+    pub few: Option<String>,
+    pub many: Option<String>,
+    pub other: String
+}
+
+impl StringsDict {
+    /// Parses a `.stringsdict` file, which is an XML plist.
+    pub fn parse(data: &[u8]) -> Result<Self, StringsDictError> {
+        let plist = Plist::from_memory(data)?;
+
+        Ok(Self::from_plist(&plist)?)
+    }
+
+    pub fn from_plist(plist: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(plist)?;
+        let entries = dict.iter()
+            .map(|entry| {
+                let (key, value) = entry.map_err(model::invalid_key)?;
+                PluralEntry::read(&value).map(|entry| (key.clone(), entry)).map_err(|error| error.within(&key))
+            })
+            .collect::<Result<Vec<(String, PluralEntry)>, ModelError>>()?;
+
+        Ok(StringsDict { entries })
+    }
+
+    pub fn to_plist(&self) -> Plist {
+        model::entries(&self.entries, PluralEntry::to_plist)
+    }
+
+    pub fn xml(&self) -> Result<String, PlistError> {
+        self.to_plist().xml()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&PluralEntry> {
+        self.entries.iter().find(|(name, _)| name == key).map(|(_, entry)| entry)
+    }
+
+    /// Checks that every `%#@variable@` placeholder has a rule, returning every problem found.
+    pub fn validate(&self) -> Vec<ModelError> {
+        let mut errors = Vec::new();
+        for (key, entry) in &self.entries {
+            for variable in placeholders(&entry.format) {
+                if !entry.variables.iter().any(|(name, _)| *name == variable) {
+                    errors.push(ModelError::InvalidValue {
+                        key: format!("{}.{}", key, FORMAT_KEY),
+                        reason: format!("no rule for the variable {}", variable)
+                    });
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+impl PluralEntry {
+    fn read(value: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(value)?;
+        let format = model::require(model::get_string(&dict, FORMAT_KEY)?, FORMAT_KEY)?;
+        let variables = dict.iter()
+            .filter(|entry| !matches!(entry, Ok((key, _)) if key == FORMAT_KEY))
+            .map(|entry| {
+                let (key, value) = entry.map_err(model::invalid_key)?;
+                PluralRule::read(&value).map(|rule| (key.clone(), rule)).map_err(|error| error.within(&key))
+            })
+            .collect::<Result<Vec<(String, PluralRule)>, ModelError>>()?;
+
+        Ok(PluralEntry { format, variables })
+    }
+
+    fn to_plist(&self) -> Plist {
+        let dict = model::new_dict();
+        dict.set(FORMAT_KEY, Plist::from(self.format.as_str()));
+        for (name, rule) in &self.variables {
+            dict.set(name.as_str(), rule.to_plist());
+        }
+
+        dict.into_plist()
+    }
+}
+
+impl PluralRule {
+    fn read(value: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(value)?;
+        match model::get_string(&dict, SPEC_TYPE_KEY)? {
+            Some(spec_type) if spec_type == PLURAL_RULE_TYPE => {}
+            Some(spec_type) => return Err(ModelError::InvalidValue {
+                key: SPEC_TYPE_KEY.to_owned(),
+                reason: format!("{:?} is not {}", spec_type, PLURAL_RULE_TYPE)
+            }),
+            None => return Err(ModelError::MissingKey(SPEC_TYPE_KEY.to_owned()))
+        }
+
+        Ok(PluralRule {
+            value_type: model::get_string(&dict, VALUE_TYPE_KEY)?,
+            zero: model::get_string(&dict, "zero")?,
+            one: model::get_string(&dict, "one")?,
+            two: model::get_string(&dict, "two")?,
+            few: model::get_string(&dict, "few")?,
+            many: model::get_string(&dict, "many")?,
+            other: model::require(model::get_string(&dict, "other")?, "other")?
+        })
+    }
+
+    fn to_plist(&self) -> Plist {
+        let dict = model::new_dict();
+        dict.set(SPEC_TYPE_KEY, Plist::from(PLURAL_RULE_TYPE));
+        let categories = [
+            (VALUE_TYPE_KEY, &self.value_type),
+            ("zero", &self.zero),
+            ("one", &self.one),
+            ("two", &self.two),
+            ("few", &self.few),
+            ("many", &self.many)
+        ];
+        for (key, value) in categories {
+            model::set(&dict, key, value.as_deref().map(Plist::from));
+        }
+        dict.set("other", Plist::from(self.other.as_str()));
+
+        dict.into_plist()
+    }
+}
+
+/// The variable names used as `%#@name@` in a format.
+fn placeholders(format: &str) -> Vec<String> {
+    format.split("%#@").skip(1)
+        .filter_map(|rest| rest.split_once('@').map(|(name, _)| name.to_owned()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Plist;
+    use crate::model::ModelError;
+    use crate::strings::{Encoding, PluralRule, StringsDict, StringsDictError, StringsFile};
+
+    const STRINGS: &str = r#"/* Title of the main window */
+"title" = "Hello, \"world\"";
+
+// Not a translator comment
+"lines" = "one\ntwo";
+
+/* A key without a value */
+"OK";
+
+unquoted = "caf\U00e9 \UD83D\UDE00";
+"#;
+
+    #[test]
+    fn parse_strings() {
+        let file = StringsFile::parse_str(STRINGS).unwrap();
+        assert_eq!(file.entries.len(), 4);
+        assert_eq!(file.entries[0].comment.as_deref(), Some("Title of the main window"));
+        assert_eq!(file.get("title"), Some("Hello, \"world\""));
+        assert_eq!(file.entries[1].comment, None);
+        assert_eq!(file.get("lines"), Some("one\ntwo"));
+        assert_eq!(file.get("OK"), Some("OK"));
+        assert_eq!(file.get("unquoted"), Some("café 😀"));
+    }
+
+    #[test]
+    fn utf16_round_trip() {
+        let mut file = StringsFile::parse_str(STRINGS).unwrap();
+        file.encoding = Encoding::Utf16Le;
+        file.set("title", "Bonjour");
+
+        let data = file.encode();
+        assert_eq!(&data[..2], b"\xff\xfe");
+        let copy = StringsFile::parse(&data).unwrap();
+        assert_eq!(copy, file);
+
+        file.encoding = Encoding::Utf16Be;
+        assert_eq!(StringsFile::parse(&file.encode()).unwrap(), file);
+
+        let plist = file.to_plist();
+        assert_eq!(StringsFile::from_plist(&plist).unwrap().get("lines"), Some("one\ntwo"));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(StringsFile::parse_str("\"a\" = \"b\"\n\"c\" = \"d\";").unwrap_err().line, 2);
+        assert!(StringsFile::parse_str("\"a\" = \"b;").is_err());
+        assert!(StringsFile::parse_str("/* open").is_err());
+        assert_eq!(StringsFile::parse(b"\xff\xfe\x00").unwrap_err().line, 1);
+        assert_eq!(StringsFile::parse(b"\"a\" = \"b\";\n\"c\" = \"\xff\";").unwrap_err().line, 2);
+        assert_eq!(StringsFile::parse(b"\xfe\xff\x00\n\x00\n\xdc\x00").unwrap_err().line, 3);
+
+        let plist = Plist::from_memory(b"<plist><dict><key>caf\xe9</key><string>coffee</string></dict></plist>").unwrap();
+        assert_eq!(StringsFile::from_plist(&plist).unwrap_err().key(), Some("caf\u{fffd}"));
+    }
+
+    const STRINGSDICT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>%d files</key>
+	<dict>
+		<key>NSStringLocalizedFormatKey</key>
+		<string>%#@files@</string>
+		<key>files</key>
+		<dict>
+			<key>NSStringFormatSpecTypeKey</key>
+			<string>NSStringPluralRuleType</string>
+			<key>NSStringFormatValueTypeKey</key>
+			<string>d</string>
+			<key>one</key>
+			<string>%d file</string>
+			<key>other</key>
+			<string>%d files</string>
+		</dict>
+	</dict>
+</dict>
+</plist>
+"#;
+
+    #[test]
+    fn stringsdict() {
+        let dict = StringsDict::parse(STRINGSDICT.as_bytes()).unwrap();
+        let entry = dict.get("%d files").unwrap();
+        assert_eq!(entry.format, "%#@files@");
+        assert_eq!(entry.variables[0].1, PluralRule {
+            value_type: Some("d".to_owned()),
+            one: Some("%d file".to_owned()),
+            other: "%d files".to_owned(),
+            ..Default::default()
+        });
+        assert!(dict.validate().is_empty());
+
+        let copy = StringsDict::from_plist(&Plist::from_xml(dict.xml().unwrap()).unwrap()).unwrap();
+        assert_eq!(copy, dict);
+
+        let broken = StringsDict::parse(STRINGSDICT.replace("<string>%#@files@</string>", "<string>%#@count@</string>").as_bytes()).unwrap();
+        assert_eq!(broken.validate().len(), 1);
+        let error = StringsDict::parse(STRINGSDICT.replace("<key>other</key>", "<key>many</key>").as_bytes()).unwrap_err();
+        assert!(matches!(error, StringsDictError::Model(ModelError::MissingKey(key)) if key == "%d files.files.other"));
+        assert!(matches!(StringsDict::parse(b"<plist>"), Err(StringsDictError::Plist(_))));
+    }
+}
@@ -0,0 +1,309 @@
+//! Moving and sharing plists between threads.
+//!
+//! `Plist`, `PlistDict` and `PlistArray` are neither `Send` nor `Sync`: libplist does no locking,
+//! and nodes returned by `get` or the iterators point into their parent's tree.
+//!
+//! Both are built from a deep copy, so handles still pointing into the original tree never alias
+//! the copy that crosses threads.
+//!
+//! * [`SendPlist`] owns a whole tree and can be moved to another thread, where it is turned back
+//!   into a `Plist` with [`SendPlist::into_inner`].
+//! * [`FrozenPlist`] is an immutable tree behind an `Arc` that is `Send + Sync`. Its getters,
+//!   lookups, iteration and serializers only read nodes, so any number of threads may call them
+//!   at the same time. There is no way to mutate it; [`FrozenPlist::to_plist`] makes a deep copy
+//!   that can be edited.
+
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use plist_sys::{plist_copy, plist_free, plist_t};
+use crate::{Getter, Plist};
+use crate::plist_error::PlistError;
+use crate::plist_node_type::PlistNodeType;
+
+pub struct SendPlist(Plist);
+
+// The tree is a fresh copy owned by this value alone; nothing on the sending thread can reach it.
+unsafe impl Send for SendPlist {}
+
+impl SendPlist {
+    pub fn into_inner(self) -> Plist {
+        self.0
+    }
+}
+
+struct FrozenRoot(plist_t);
+
+impl Drop for FrozenRoot {
+    fn drop(&mut self) {
+        unsafe { plist_free(self.0) }
+    }
+}
+
+// The tree is a fresh copy that no `Plist` can reach, and only read-only libplist calls are made
+// on it.
+unsafe impl Send for FrozenRoot {}
+unsafe impl Sync for FrozenRoot {}
+
+#[derive(Clone)]
+pub struct FrozenPlist {
+    root: Arc<FrozenRoot>,
+    node: plist_t
+}
+
+// `node` always points into `root`, which is kept alive by the `Arc`.
+unsafe impl Send for FrozenPlist {}
+unsafe impl Sync for FrozenPlist {}
+
+impl Plist {
+    /// Copies the tree so it can be moved to another thread.
+    pub fn into_send(self) -> SendPlist {
+        SendPlist(self.copy())
+    }
+
+    /// Copies the tree into an immutable one that can be shared between threads. Later changes
+    /// made through other handles don't show up in the frozen copy:
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use plist_rs::Plist;
+    /// use plist_rs::dict::{DictGetter, DictSetter};
+    ///
+    /// let dict = Plist::from(HashMap::from([
+    ///     ("inner", Plist::from(HashMap::from([("a", Plist::from(0))]))),
+    /// ])).dict().unwrap();
+    ///
+    /// let frozen = dict.get("inner").unwrap().freeze();
+    /// dict.get("inner").unwrap().dict().unwrap().set("b", Plist::from(1));
+    ///
+    /// assert!(frozen.get("b").is_none());
+    /// ```
+    pub fn freeze(self) -> FrozenPlist {
+        let node = self.copy().into_raw();
+
+        FrozenPlist {
+            root: Arc::new(FrozenRoot(node)),
+            node
+        }
+    }
+}
+
+impl FrozenPlist {
+    fn view(&self) -> Plist {
+        Plist::new_with_weak(self.node)
+    }
+
+    fn child(&self, node: Plist) -> FrozenPlist {
+        FrozenPlist {
+            root: Arc::clone(&self.root),
+            node: node.as_ptr().unwrap()
+        }
+    }
+
+    pub fn node_type(&self) -> PlistNodeType {
+        self.view().node_type()
+    }
+
+    pub fn as_str(&self) -> Option<String> {
+        self.view().as_str()
+    }
+
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        self.view().as_bytes()
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        self.view().as_bool()
+    }
+
+    pub fn as_key(&self) -> Option<String> {
+        self.view().as_key()
+    }
+
+    pub fn as_uint(&self) -> Option<u64> {
+        self.view().as_uint()
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        self.view().as_int()
+    }
+
+    pub fn as_real(&self) -> Option<f64> {
+        self.view().as_real()
+    }
+
+    pub fn as_data(&self) -> Option<Vec<i8>> {
+        self.view().as_data()
+    }
+
+    pub fn as_uid(&self) -> Option<u64> {
+        self.view().as_uid()
+    }
+
+    pub fn as_date(&self) -> Option<(i32, i32)> {
+        self.view().as_date()
+    }
+
+    pub fn get(&self, key: &str) -> Option<FrozenPlist> {
+        if self.node_type() != PlistNodeType::Dictionary {
+            return None
+        }
+
+        self.view().get(key).map(|node| self.child(node))
+    }
+
+    pub fn get_index(&self, index: usize) -> Option<FrozenPlist> {
+        if self.node_type() != PlistNodeType::Array {
+            return None
+        }
+
+        self.view().get(index).map(|node| self.child(node))
+    }
+
+    pub fn len(&self) -> usize {
+        match self.node_type() {
+            PlistNodeType::Array => self.view().array().unwrap().len(),
+            PlistNodeType::Dictionary => self.view().dict().unwrap().len(),
+            _ => 0
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The keys' bytes as they are; they needn't be UTF-8.
+    pub fn entries(&self) -> Vec<(Vec<u8>, FrozenPlist)> {
+        match self.view().dict() {
+            Some(dict) => dict.iter().bytes().map(|(key, value)| (key, self.child(value))).collect(),
+            None => Vec::new()
+        }
+    }
+
+    pub fn items(&self) -> Vec<FrozenPlist> {
+        (0..self.len())
+            .filter_map(|index| self.get_index(index))
+            .collect()
+    }
+
+    pub fn xml(&self) -> Result<String, PlistError> {
+        self.view().xml()
+    }
+
+    pub fn bin(&self) -> Result<Vec<u8>, PlistError> {
+        self.view().bin()
+    }
+
+    pub fn json(&self, prettify: bool) -> Result<String, PlistError> {
+        self.view().json(prettify)
+    }
+
+    pub fn openstep(&self, prettify: bool) -> Result<String, PlistError> {
+        self.view().openstep(prettify)
+    }
+
+    /// Returns a mutable deep copy of this node.
+    pub fn to_plist(&self) -> Plist {
+        let p = unsafe { plist_copy(self.node) };
+
+        Plist::new(p)
+    }
+}
+
+impl Debug for FrozenPlist {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.view(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::thread;
+    use crate::{Getter, Plist};
+    use crate::dict::{DictGetter, DictSetter};
+
+    #[test]
+    fn send() {
+        let plist = Plist::from(HashMap::from([
+            ("key1", Plist::from(0)),
+        ])).into_send();
+
+        let plist = thread::spawn(move || {
+            let dict = plist.into_inner().dict().unwrap();
+            dict.set("key2", Plist::from(1));
+            Plist::from(HashMap::from([
+                ("len", Plist::from(dict.len() as u64)),
+            ])).into_send()
+        }).join().unwrap().into_inner();
+
+        assert_eq!(plist.get("len").unwrap().as_uint().unwrap(), 2);
+    }
+
+    #[test]
+    fn frozen() {
+        let plist = Plist::from(HashMap::from([
+            ("name", Plist::from("foo")),
+            ("values", Plist::from(vec![Plist::from(0), Plist::from(1)])),
+        ])).freeze();
+
+        let handles = (0..4).map(|_| {
+            let plist = plist.clone();
+            thread::spawn(move || {
+                assert_eq!(plist.get("name").unwrap().as_str().unwrap(), "foo");
+                assert_eq!(plist.get("values").unwrap().get_index(1).unwrap().as_uint().unwrap(), 1);
+                assert!(plist.xml().is_ok());
+            })
+        }).collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(plist.len(), 2);
+        assert_eq!(plist.entries().len(), 2);
+        assert_eq!(plist.get("values").unwrap().items().len(), 2);
+    }
+
+    #[test]
+    fn frozen_accessors() {
+        let plist = Plist::from(HashMap::from([
+            ("name", Plist::from("foo")),
+            ("offset", Plist::from_int(-1)),
+            ("date", Plist::from_date(60, 0)),
+        ])).freeze();
+
+        assert_eq!(plist.get("name").unwrap().as_bytes().unwrap(), b"foo");
+        assert_eq!(plist.get("offset").unwrap().as_int().unwrap(), -1);
+        assert_eq!(plist.get("date").unwrap().as_date().unwrap(), (60, 0));
+        assert_eq!(plist.get("name").unwrap().openstep(false).is_ok(), cfg!(libplist_2_3));
+    }
+
+    #[test]
+    fn frozen_child_outlives_parent() {
+        let child = {
+            let plist = Plist::from(HashMap::from([
+                ("name", Plist::from("foo")),
+            ])).freeze();
+            plist.get("name").unwrap()
+        };
+
+        assert_eq!(child.as_str().unwrap(), "foo");
+        assert_eq!(child.to_plist().as_str().unwrap(), "foo");
+    }
+
+    #[test]
+    fn copies_do_not_alias() {
+        let dict = Plist::from(HashMap::from([
+            ("inner", Plist::from(HashMap::from([("a", Plist::from(0))]))),
+        ])).dict().unwrap();
+
+        let frozen = dict.get("inner").unwrap().freeze();
+        let sent = dict.get("inner").unwrap().into_send();
+        dict.get("inner").unwrap().dict().unwrap().set("b", Plist::from(1));
+
+        assert!(frozen.get("b").is_none());
+        assert!(sent.into_inner().get("b").is_none());
+        drop(frozen);
+        assert_eq!(dict.get("inner").unwrap().get("a").unwrap().as_uint().unwrap(), 0);
+    }
+}
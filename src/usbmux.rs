@@ -0,0 +1,598 @@
+//! The usbmuxd protocol: plist messages behind a 16 byte little-endian header of total length,
+//! protocol version, message type and a tag that replies echo back.
+//!
+//! [`UsbmuxClient`] speaks it over any stream; on macOS and Linux the daemon listens on
+//! [`SOCKET_PATH`].
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{self, ErrorKind, Read, Write};
+use crate::Plist;
+use crate::codec::DEFAULT_MAX_SIZE;
+use crate::dict::DictSetter;
+use crate::format::PlistFormat;
+use crate::model::{self, ModelError};
+use crate::plist_error::PlistError;
+use crate::plist_node_type::PlistNodeType;
+
+pub const SOCKET_PATH: &str = "/var/run/usbmuxd";
+pub const HEADER_SIZE: usize = 16;
+/// The protocol version of plist messages; version 0 is the older binary protocol.
+pub const PLIST_VERSION: u32 = 1;
+/// The header message type of plist messages.
+pub const PLIST_MESSAGE: u32 = 8;
+
+const CLIENT_VERSION: &str = concat!("plist-rs-", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug)]
+pub enum UsbmuxError {
+    Plist(PlistError),
+    Model(ModelError),
+    /// usbmuxd answered with a non-zero result.
+    Result(ResultCode),
+    UnexpectedReply(String)
+}
+
+impl Display for UsbmuxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Plist(error) => write!(f, "{}", error),
+            Self::Model(error) => write!(f, "Invalid message: {}", error),
+            Self::Result(code) => write!(f, "usbmuxd returned {:?}", code),
+            Self::UnexpectedReply(message_type) => write!(f, "Unexpected {} message", message_type)
+        }
+    }
+}
+
+impl Error for UsbmuxError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Plist(error) => Some(error),
+            Self::Model(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<PlistError> for UsbmuxError {
+    fn from(error: PlistError) -> Self {
+        Self::Plist(error)
+    }
+}
+
+impl From<ModelError> for UsbmuxError {
+    fn from(error: ModelError) -> Self {
+        Self::Model(error)
+    }
+}
+
+impl From<io::Error> for UsbmuxError {
+    fn from(error: io::Error) -> Self {
+        Self::Plist(PlistError::Io(error))
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct Header {
+    /// The size of the whole packet, header included.
+    pub length: u32,
+    pub version: u32,
+    pub message: u32,
+    pub tag: u32
+}
+
+impl Header {
+    pub fn encode(&self) -> [u8; HEADER_SIZE] {
+        let mut data = [0u8; HEADER_SIZE];
+        for (chunk, value) in data.chunks_exact_mut(4).zip([self.length, self.version, self.message, self.tag]) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+
+        data
+    }
+
+    pub fn decode(data: &[u8; HEADER_SIZE]) -> Self {
+        let field = |index: usize| u32::from_le_bytes([data[index], data[index + 1], data[index + 2], data[index + 3]]);
+
+        Header {
+            length: field(0),
+            version: field(4),
+            message: field(8),
+            tag: field(12)
+        }
+    }
+}
+
+/// Writes `payload` as an XML plist packet.
+pub fn write_packet<W: Write>(writer: &mut W, tag: u32, payload: &Plist) -> Result<(), PlistError> {
+    let data = payload.to_format(PlistFormat::Xml, false)?;
+    let header = Header {
+        length: (HEADER_SIZE + data.len()) as u32,
+        version: PLIST_VERSION,
+        message: PLIST_MESSAGE,
+        tag
+    };
+
+    let mut packet = Vec::with_capacity(HEADER_SIZE + data.len());
+    packet.extend_from_slice(&header.encode());
+    packet.extend_from_slice(&data);
+    writer.write_all(&packet)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Reads one plist packet and returns its tag and payload.
+pub fn read_packet<R: Read>(reader: &mut R) -> Result<(u32, Plist), PlistError> {
+    let mut header = [0u8; HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+    let header = Header::decode(&header);
+
+    let invalid = |message: String| PlistError::Io(io::Error::new(ErrorKind::InvalidData, message));
+    if header.version != PLIST_VERSION || header.message != PLIST_MESSAGE {
+        return Err(invalid(format!("unsupported packet version {} type {}", header.version, header.message)))
+    }
+    let length = (header.length as usize).checked_sub(HEADER_SIZE)
+        .filter(|&length| length <= DEFAULT_MAX_SIZE as usize)
+        .ok_or_else(|| invalid(format!("invalid packet length {}", header.length)))?;
+
+    let mut data = vec![0u8; length];
+    reader.read_exact(&mut data)?;
+    Ok((header.tag, Plist::from_memory(&data)?))
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ResultCode {
+    Ok,
+    BadCommand,
+    BadDevice,
+    ConnectionRefused,
+    BadVersion,
+    Other(u64)
+}
+
+impl ResultCode {
+    pub fn from_number(number: u64) -> Self {
+        match number {
+            0 => Self::Ok,
+            1 => Self::BadCommand,
+            2 => Self::BadDevice,
+            3 => Self::ConnectionRefused,
+            6 => Self::BadVersion,
+            number => Self::Other(number)
+        }
+    }
+
+    pub fn number(&self) -> u64 {
+        match self {
+            Self::Ok => 0,
+            Self::BadCommand => 1,
+            Self::BadDevice => 2,
+            Self::ConnectionRefused => 3,
+            Self::BadVersion => 6,
+            Self::Other(number) => *number
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Request {
+    ListDevices,
+    Listen,
+    /// Opens a TCP connection to `port` on the device; on success the socket becomes the
+    /// connection.
+    Connect { device_id: u64, port: u16 },
+    ReadBuid,
+    ReadPairRecord { udid: String }
+}
+
+impl Request {
+    pub fn message_type(&self) -> &'static str {
+        match self {
+            Self::ListDevices => "ListDevices",
+            Self::Listen => "Listen",
+            Self::Connect { .. } => "Connect",
+            Self::ReadBuid => "ReadBUID",
+            Self::ReadPairRecord { .. } => "ReadPairRecord"
+        }
+    }
+
+    pub fn to_plist(&self, program: &str) -> Plist {
+        let dict = model::new_dict();
+        dict.set("MessageType", Plist::from(self.message_type()));
+        dict.set("ClientVersionString", Plist::from(CLIENT_VERSION));
+        dict.set("ProgName", Plist::from(program));
+        match self {
+            Self::Connect { device_id, port } => {
+                dict.set("DeviceID", Plist::from(*device_id));
+                // usbmuxd expects the port in network byte order.
+                dict.set("PortNumber", Plist::from(port.swap_bytes() as u64));
+            }
+            Self::ReadPairRecord { udid } => dict.set("PairRecordID", Plist::from(udid.as_str())),
+            _ => {}
+        }
+
+        dict.into_plist()
+    }
+
+    pub fn from_plist(plist: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(plist)?;
+        let message_type = model::require(model::get_string(&dict, "MessageType")?, "MessageType")?;
+
+        match message_type.as_str() {
+            "ListDevices" => Ok(Self::ListDevices),
+            "Listen" => Ok(Self::Listen),
+            "ReadBUID" => Ok(Self::ReadBuid),
+            "Connect" => {
+                let port = model::require(model::get_uint(&dict, "PortNumber")?, "PortNumber")?;
+                Ok(Self::Connect {
+                    device_id: model::require(model::get_uint(&dict, "DeviceID")?, "DeviceID")?,
+                    port: (port as u16).swap_bytes()
+                })
+            }
+            "ReadPairRecord" => Ok(Self::ReadPairRecord {
+                udid: model::require(model::get_string(&dict, "PairRecordID")?, "PairRecordID")?
+            }),
+            message_type => Err(ModelError::InvalidValue {
+                key: "MessageType".to_owned(),
+                reason: format!("unknown request {:?}", message_type)
+            })
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Device {
+    pub device_id: u64,
+    pub serial_number: String,
+    /// `USB` or `Network`.
+    pub connection_type: String,
+    pub product_id: Option<u64>,
+    pub location_id: Option<u64>
+}
+
+impl Device {
+    fn from_properties(plist: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(plist)?;
+
+        Ok(Device {
+            device_id: model::require(model::get_uint(&dict, "DeviceID")?, "DeviceID")?,
+            serial_number: model::require(model::get_string(&dict, "SerialNumber")?, "SerialNumber")?,
+            connection_type: model::get_string(&dict, "ConnectionType")?.unwrap_or_else(|| "USB".to_owned()),
+            product_id: model::get_uint(&dict, "ProductID")?,
+            location_id: model::get_uint(&dict, "LocationID")?
+        })
+    }
+
+    fn from_attached(plist: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(plist)?;
+        let properties = model::require(model::get_dict(&dict, "Properties")?, "Properties")?;
+
+        Device::from_properties(&properties.into_plist()).map_err(|error| error.within("Properties"))
+    }
+
+    fn to_properties(&self) -> Plist {
+        let dict = model::new_dict();
+        dict.set("ConnectionType", Plist::from(self.connection_type.as_str()));
+        dict.set("DeviceID", Plist::from(self.device_id));
+        model::set(&dict, "LocationID", self.location_id.map(Plist::from));
+        model::set(&dict, "ProductID", self.product_id.map(Plist::from));
+        dict.set("SerialNumber", Plist::from(self.serial_number.as_str()));
+
+        dict.into_plist()
+    }
+
+    /// The `Attached` message announcing this device.
+    fn to_attached(&self) -> Plist {
+        let dict = model::new_dict();
+        dict.set("DeviceID", Plist::from(self.device_id));
+        dict.set("MessageType", Plist::from("Attached"));
+        dict.set("Properties", self.to_properties());
+
+        dict.into_plist()
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Response {
+    Result(ResultCode),
+    DeviceList(Vec<Device>),
+    Buid(String),
+    PairRecord(Vec<u8>),
+    Attached(Device),
+    Detached(u64)
+}
+
+impl Response {
+    pub fn to_plist(&self) -> Plist {
+        let dict = model::new_dict();
+        match self {
+            Self::Result(code) => {
+                dict.set("MessageType", Plist::from("Result"));
+                dict.set("Number", Plist::from(code.number()));
+            }
+            Self::DeviceList(devices) => dict.set("DeviceList", Plist::from(devices.iter().map(Device::to_attached).collect::<Vec<Plist>>())),
+            Self::Buid(buid) => dict.set("BUID", Plist::from(buid.as_str())),
+            Self::PairRecord(data) => dict.set("PairRecordData", Plist::from(data.iter().map(|&byte| byte as i8).collect::<Vec<i8>>().as_slice())),
+            Self::Attached(device) => return device.to_attached(),
+            Self::Detached(device_id) => {
+                dict.set("DeviceID", Plist::from(*device_id));
+                dict.set("MessageType", Plist::from("Detached"));
+            }
+        }
+
+        dict.into_plist()
+    }
+
+    pub fn from_plist(plist: &Plist) -> Result<Self, ModelError> {
+        let dict = model::dict_copy(plist)?;
+        if let Some(devices) = model::get_items(&dict, "DeviceList", |key, device| Device::from_attached(device).map_err(|error| error.within(key)))? {
+            return Ok(Self::DeviceList(devices))
+        }
+        if let Some(buid) = model::get_string(&dict, "BUID")? {
+            return Ok(Self::Buid(buid))
+        }
+        if let Some(data) = model::get(&dict, "PairRecordData", PlistNodeType::Data)? {
+            return Ok(Self::PairRecord(data.as_data().unwrap_or_default().into_iter().map(|byte| byte as u8).collect()))
+        }
+
+        match model::require(model::get_string(&dict, "MessageType")?, "MessageType")?.as_str() {
+            "Result" => Ok(Self::Result(ResultCode::from_number(model::require(model::get_uint(&dict, "Number")?, "Number")?))),
+            "Attached" => Device::from_attached(plist).map(Self::Attached),
+            "Detached" => Ok(Self::Detached(model::require(model::get_uint(&dict, "DeviceID")?, "DeviceID")?)),
+            message_type => Err(ModelError::InvalidValue {
+                key: "MessageType".to_owned(),
+                reason: format!("unknown message {:?}", message_type)
+            })
+        }
+    }
+}
+
+pub struct UsbmuxClient<T> {
+    stream: T,
+    tag: u32,
+    program: String
+}
+
+#[cfg(unix)]
+impl UsbmuxClient<std::os::unix::net::UnixStream> {
+    /// Connects to the system usbmuxd at [`SOCKET_PATH`].
+    pub fn connect_default() -> Result<Self, UsbmuxError> {
+        Ok(Self::new(std::os::unix::net::UnixStream::connect(SOCKET_PATH)?))
+    }
+}
+
+impl<T: Read + Write> UsbmuxClient<T> {
+    pub fn new(stream: T) -> Self {
+        UsbmuxClient {
+            stream,
+            tag: 0,
+            program: env!("CARGO_PKG_NAME").to_owned()
+        }
+    }
+
+    /// The `ProgName` sent with every request.
+    pub fn program(mut self, program: &str) -> Self {
+        self.program = program.to_owned();
+        self
+    }
+
+    pub fn list_devices(&mut self) -> Result<Vec<Device>, UsbmuxError> {
+        match self.request(&Request::ListDevices)? {
+            Response::DeviceList(devices) => Ok(devices),
+            response => Err(unexpected(response))
+        }
+    }
+
+    pub fn read_buid(&mut self) -> Result<String, UsbmuxError> {
+        match self.request(&Request::ReadBuid)? {
+            Response::Buid(buid) => Ok(buid),
+            response => Err(unexpected(response))
+        }
+    }
+
+    /// Returns the pair record stored for `udid`, as the plist data usbmuxd keeps.
+    pub fn read_pair_record(&mut self, udid: &str) -> Result<Vec<u8>, UsbmuxError> {
+        match self.request(&Request::ReadPairRecord { udid: udid.to_owned() })? {
+            Response::PairRecord(data) => Ok(data),
+            response => Err(unexpected(response))
+        }
+    }
+
+    /// Connects to `port` on the device. The returned stream is the TCP connection itself.
+    pub fn connect(mut self, device_id: u64, port: u16) -> Result<T, UsbmuxError> {
+        match self.request(&Request::Connect { device_id, port })? {
+            Response::Result(ResultCode::Ok) => Ok(self.stream),
+            response => Err(unexpected(response))
+        }
+    }
+
+    /// Subscribes to `Attached` and `Detached` events, which are then read with
+    /// [`UsbmuxClient::next_event`].
+    pub fn listen(&mut self) -> Result<(), UsbmuxError> {
+        match self.request(&Request::Listen)? {
+            Response::Result(ResultCode::Ok) => Ok(()),
+            response => Err(unexpected(response))
+        }
+    }
+
+    pub fn next_event(&mut self) -> Result<Response, UsbmuxError> {
+        let (_, payload) = read_packet(&mut self.stream)?;
+
+        Ok(Response::from_plist(&payload)?)
+    }
+
+    fn request(&mut self, request: &Request) -> Result<Response, UsbmuxError> {
+        self.tag = self.tag.wrapping_add(1);
+        write_packet(&mut self.stream, self.tag, &request.to_plist(&self.program))?;
+
+        loop {
+            let (tag, payload) = read_packet(&mut self.stream)?;
+            // Events of an earlier Listen can arrive before the reply; only the reply has our tag.
+            if tag == self.tag {
+                return Ok(Response::from_plist(&payload)?)
+            }
+        }
+    }
+}
+
+fn unexpected(response: Response) -> UsbmuxError {
+    match response {
+        Response::Result(code) => UsbmuxError::Result(code),
+        Response::DeviceList(_) => UsbmuxError::UnexpectedReply("DeviceList".to_owned()),
+        Response::Buid(_) => UsbmuxError::UnexpectedReply("BUID".to_owned()),
+        Response::PairRecord(_) => UsbmuxError::UnexpectedReply("PairRecordData".to_owned()),
+        Response::Attached(_) => UsbmuxError::UnexpectedReply("Attached".to_owned()),
+        Response::Detached(_) => UsbmuxError::UnexpectedReply("Detached".to_owned())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::io::{Read, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use crate::{Getter, Plist};
+    use crate::usbmux::{read_packet, write_packet, Device, Header, Request, Response, ResultCode, UsbmuxClient, UsbmuxError, HEADER_SIZE};
+
+    const UDID: &str = "00008030-001A35E80C41802E";
+    const LOCKDOWN_PORT: u16 = 62078;
+
+    fn device() -> Device {
+        Device {
+            device_id: 3,
+            serial_number: UDID.to_owned(),
+            connection_type: "USB".to_owned(),
+            product_id: Some(0x12a8),
+            location_id: Some(0x1410_0000)
+        }
+    }
+
+    /// A usbmuxd stand-in listening on a socket in the temporary directory, serving one device.
+    struct MockUsbmuxd {
+        path: PathBuf
+    }
+
+    impl MockUsbmuxd {
+        fn start() -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!("usbmuxd-{}-{}", std::process::id(), COUNTER.fetch_add(1, Ordering::SeqCst)));
+            let listener = UnixListener::bind(&path).unwrap();
+
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = stream.unwrap();
+                    thread::spawn(move || Self::serve(stream));
+                }
+            });
+
+            MockUsbmuxd { path }
+        }
+
+        fn connect(&self) -> UsbmuxClient<UnixStream> {
+            UsbmuxClient::new(UnixStream::connect(&self.path).unwrap()).program("tests")
+        }
+
+        fn serve(mut stream: UnixStream) {
+            while let Ok((tag, payload)) = read_packet(&mut stream) {
+                assert_eq!(payload.get("ProgName").unwrap().as_str().unwrap(), "tests");
+                let reply = match Request::from_plist(&payload).unwrap() {
+                    Request::ListDevices => Response::DeviceList(vec![device()]),
+                    Request::ReadBuid => Response::Buid("1C4BFB26-6F5E-4E5B-9C9B-2AF2A1E3A5C9".to_owned()),
+                    Request::ReadPairRecord { udid } if udid == UDID => Response::PairRecord(b"<plist/>".to_vec()),
+                    Request::ReadPairRecord { .. } => Response::Result(ResultCode::BadDevice),
+                    Request::Connect { device_id: 3, port: LOCKDOWN_PORT } => {
+                        write_packet(&mut stream, tag, &Response::Result(ResultCode::Ok).to_plist()).unwrap();
+                        // From here on the socket is the device connection; echo it back.
+                        let mut data = [0u8; 5];
+                        stream.read_exact(&mut data).unwrap();
+                        stream.write_all(&data).unwrap();
+                        return
+                    }
+                    Request::Connect { .. } => Response::Result(ResultCode::ConnectionRefused),
+                    Request::Listen => {
+                        write_packet(&mut stream, tag, &Response::Result(ResultCode::Ok).to_plist()).unwrap();
+                        write_packet(&mut stream, 0, &Response::Attached(device()).to_plist()).unwrap();
+                        write_packet(&mut stream, 0, &Response::Detached(3).to_plist()).unwrap();
+                        continue
+                    }
+                };
+                write_packet(&mut stream, tag, &reply.to_plist()).unwrap();
+            }
+        }
+    }
+
+    impl Drop for MockUsbmuxd {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    #[test]
+    fn packets() {
+        let header = Header { length: 300, version: 1, message: 8, tag: 7 };
+        assert_eq!(header.encode(), [44, 1, 0, 0, 1, 0, 0, 0, 8, 0, 0, 0, 7, 0, 0, 0]);
+        assert_eq!(Header::decode(&header.encode()), header);
+
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        write_packet(&mut client, 9, &Request::ListDevices.to_plist("tests")).unwrap();
+        let mut data = [0u8; HEADER_SIZE];
+        server.read_exact(&mut data).unwrap();
+        let header = Header::decode(&data);
+        assert_eq!((header.version, header.message, header.tag), (1, 8, 9));
+        let mut body = vec![0u8; header.length as usize - HEADER_SIZE];
+        server.read_exact(&mut body).unwrap();
+        assert!(String::from_utf8(body).unwrap().contains("<string>ListDevices</string>"));
+
+        server.write_all(&Header { length: 16, version: 0, message: 8, tag: 0 }.encode()).unwrap();
+        assert!(read_packet(&mut client).is_err());
+    }
+
+    #[test]
+    fn requests() {
+        let connect = Request::Connect { device_id: 3, port: LOCKDOWN_PORT };
+        let plist = connect.to_plist("tests");
+        assert_eq!(plist.get("MessageType").unwrap().as_str().unwrap(), "Connect");
+        assert_eq!(plist.get("PortNumber").unwrap().as_uint().unwrap(), 0x7ef2);
+        assert_eq!(Request::from_plist(&plist).unwrap(), connect);
+
+        let response = Response::DeviceList(vec![device()]);
+        assert_eq!(Response::from_plist(&response.to_plist()).unwrap(), response);
+        assert!(Response::from_plist(&Plist::from(Vec::<Plist>::new())).is_err());
+    }
+
+    #[test]
+    fn queries() {
+        let usbmuxd = MockUsbmuxd::start();
+        let mut client = usbmuxd.connect();
+
+        assert_eq!(client.list_devices().unwrap(), vec![device()]);
+        assert_eq!(client.read_buid().unwrap(), "1C4BFB26-6F5E-4E5B-9C9B-2AF2A1E3A5C9");
+        assert_eq!(client.read_pair_record(UDID).unwrap(), b"<plist/>");
+        assert!(matches!(client.read_pair_record("unknown"), Err(UsbmuxError::Result(ResultCode::BadDevice))));
+    }
+
+    #[test]
+    fn connect() {
+        let usbmuxd = MockUsbmuxd::start();
+        assert!(matches!(usbmuxd.connect().connect(3, 22), Err(UsbmuxError::Result(ResultCode::ConnectionRefused))));
+
+        let mut stream = usbmuxd.connect().connect(3, LOCKDOWN_PORT).unwrap();
+        stream.write_all(b"hello").unwrap();
+        let mut data = [0u8; 5];
+        stream.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"hello");
+    }
+
+    #[test]
+    fn listen() {
+        let usbmuxd = MockUsbmuxd::start();
+        let mut client = usbmuxd.connect();
+        client.listen().unwrap();
+
+        assert_eq!(client.next_event().unwrap(), Response::Attached(device()));
+        assert_eq!(client.next_event().unwrap(), Response::Detached(3));
+    }
+}
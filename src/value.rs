@@ -1,28 +1,19 @@
 use std::ffi::{c_void, CStr, CString};
 use std::os::raw::c_char;
 use std::ptr::{null_mut, slice_from_raw_parts};
-use crate::{Plist, plist_get_bool_val, plist_get_data_val, plist_get_key_val, plist_get_real_val, plist_get_string_val, plist_get_uid_val, plist_get_uint_val, plist_mem_free, plist_new_bool, plist_new_data, plist_new_date, plist_new_null, plist_new_real, plist_new_string, plist_new_uid, plist_new_uint, plist_set_bool_val, plist_set_data_val, plist_set_date_val, plist_set_key_val, plist_set_real_val, plist_set_string_val, plist_set_uid_val, plist_set_uint_val};
+use crate::{Plist, plist_get_bool_val, plist_get_data_val, plist_get_date_val, plist_get_key_val, plist_get_real_val, plist_get_string_val, plist_get_uid_val, plist_get_uint_val, plist_mem_free, plist_new_bool, plist_new_data, plist_new_date, plist_new_real, plist_new_string, plist_new_uid, plist_new_uint, plist_set_bool_val, plist_set_data_val, plist_set_date_val, plist_set_key_val, plist_set_real_val, plist_set_string_val, plist_set_uid_val, plist_set_uint_val, plist_t};
+#[cfg(libplist_2_3)]
+use crate::{plist_get_int_val, plist_int_val_is_negative, plist_new_int, plist_new_null};
 use crate::plist_node_type::PlistNodeType;
 
+/// Seconds between the Unix epoch and 2001-01-01T00:00:00Z, which plist dates count from.
+pub const MAC_EPOCH: i64 = 978_307_200;
+
 // Getters
 impl Plist {
+    /// `None` unless this is a string holding valid UTF-8; see `as_str_lossy` and `as_bytes`.
     pub fn as_str(&self) -> Option<String> {
-        let mut val: *mut c_char = null_mut();
-        unsafe {
-            plist_get_string_val(self.as_ptr().ok()?, &mut val)
-        };
-
-        if val.is_null() {
-            return None;
-        }
-
-        let string = unsafe { CStr::from_ptr(val) };
-        let string = string.to_str().unwrap().to_owned();
-        unsafe {
-            plist_mem_free(val as *mut c_void);
-        }
-
-        Some(string)
+        String::from_utf8(self.string_bytes(plist_get_string_val)?).ok()
     }
 
     pub fn as_bool(&self) -> Option<bool> {
@@ -36,22 +27,40 @@ impl Plist {
         Some(bool == 1)
     }
 
+    /// `None` unless this is a key holding valid UTF-8.
     pub fn as_key(&self) -> Option<String> {
+        String::from_utf8(self.string_bytes(plist_get_key_val)?).ok()
+    }
+
+    /// A string or key, with anything that isn't valid UTF-8 replaced by U+FFFD.
+    pub fn as_str_lossy(&self) -> Option<String> {
+        self.as_bytes().map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// The bytes of a string or key. libplist takes them from XML and OpenStep input without
+    /// checking that they are UTF-8, so they may not be.
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        match self.node_type() {
+            PlistNodeType::String => self.string_bytes(plist_get_string_val),
+            PlistNodeType::Key => self.string_bytes(plist_get_key_val),
+            _ => None
+        }
+    }
+
+    fn string_bytes(&self, getter: unsafe extern "C" fn(plist_t, *mut *mut c_char)) -> Option<Vec<u8>> {
         let mut raw: *mut c_char = null_mut();
-        unsafe { plist_get_key_val(self.as_ptr().ok()?, &mut raw) };
+        unsafe { getter(self.as_ptr().ok()?, &mut raw) };
 
         if raw.is_null() {
             return None
         }
 
-        let key = unsafe { CStr::from_ptr(raw) };
-        let key = key.to_str().unwrap().to_owned();
-
+        let bytes = unsafe { CStr::from_ptr(raw) }.to_bytes().to_vec();
         unsafe {
             plist_mem_free(raw as *mut c_void)
         };
 
-        Some(key)
+        Some(bytes)
     }
 
     pub fn as_uint(&self) -> Option<u64> {
@@ -65,6 +74,32 @@ impl Plist {
         Some(uint)
     }
 
+    /// The integer as a signed value; `None` for unsigned values above `i64::MAX`.
+    ///
+    /// libplist 2.2 keeps no sign, so there every integer is read as a signed 64 bit value.
+    pub fn as_int(&self) -> Option<i64> {
+        if self.node_type() != PlistNodeType::UInt {
+            return None
+        }
+
+        #[cfg(libplist_2_3)]
+        {
+            let p = self.as_ptr().ok()?;
+            if unsafe { plist_int_val_is_negative(p) } == 0 {
+                return i64::try_from(self.as_uint()?).ok()
+            }
+
+            let mut int: i64 = 0;
+            unsafe { plist_get_int_val(p, &mut int) };
+
+            Some(int)
+        }
+        #[cfg(not(libplist_2_3))]
+        {
+            self.as_uint().map(|uint| uint as i64)
+        }
+    }
+
     pub fn as_real(&self) -> Option<f64> {
         if self.node_type() != PlistNodeType::Real {
             return None
@@ -91,6 +126,18 @@ impl Plist {
         Some(data)
     }
 
+    pub fn as_date(&self) -> Option<(i32, i32)> {
+        if self.node_type() != PlistNodeType::Date {
+            return None
+        }
+
+        let mut sec: i32 = 0;
+        let mut usec: i32 = 0;
+        unsafe { plist_get_date_val(self.as_ptr().ok()?, &mut sec, &mut usec) };
+
+        Some((sec, usec))
+    }
+
     pub fn as_uid(&self) -> Option<u64> {
         if self.node_type() != PlistNodeType::UID {
             return None
@@ -231,6 +278,18 @@ impl Plist {
     }
 }
 
+impl Plist {
+    /// libplist 2.2 keeps no sign, so there the value is stored as its 64 bit two's complement.
+    pub fn from_int(int: i64) -> Self {
+        #[cfg(libplist_2_3)]
+        let p = unsafe { plist_new_int(int) };
+        #[cfg(not(libplist_2_3))]
+        let p = unsafe { plist_new_uint(int as u64) };
+
+        Plist::new(p)
+    }
+}
+
 impl Plist {
     pub fn from_uid(uid: u64) -> Self {
         let p = unsafe { plist_new_uid(uid) };
@@ -240,6 +299,8 @@ impl Plist {
 }
 
 impl Plist {
+    /// Needs libplist 2.3 or newer, which added null nodes for JSON.
+    #[cfg(libplist_2_3)]
     pub fn null() -> Self {
         let p = unsafe { plist_new_null() };
 
@@ -250,6 +311,8 @@ impl Plist {
 #[cfg(test)]
 mod tests {
     use crate::Plist;
+    use crate::format::PlistFormat;
+    use crate::plist_error::PlistError;
     use crate::value::Setter;
 
     #[test]
@@ -268,6 +331,12 @@ mod tests {
         assert_eq!(plist.as_uint().unwrap(), 1);
         plist.set(u64::MAX);
         assert_eq!(plist.as_uint().unwrap(), u64::MAX);
+        #[cfg(libplist_2_3)]
+        assert_eq!(plist.as_int(), None);
+
+        let plist = Plist::from_int(-1);
+        assert_eq!(plist.as_int().unwrap(), -1);
+        assert_eq!(Plist::from_int(i64::MAX).as_int().unwrap(), i64::MAX);
 
         let plist = Plist::from(1 as f64);
         assert_eq!(plist.as_real().unwrap(), 1.0);
@@ -281,9 +350,37 @@ mod tests {
         plist.set(data);
         assert_eq!(plist.as_data().unwrap(), data);
 
+        let plist = Plist::from_date(1, 2);
+        assert_eq!(plist.as_date().unwrap(), (1, 2));
+        plist.set_date(-1, 0);
+        assert_eq!(plist.as_date().unwrap(), (-1, 0));
+
         let plist = Plist::from_uid(1);
         assert_eq!(plist.as_uid().unwrap(), 1);
         plist.set_uid(0);
         assert_eq!(plist.as_uid().unwrap(), 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn invalid_utf8() {
+        let plist = Plist::from_memory(b"<plist><dict><key>caf\xe9</key><string>iPhone \xff</string></dict></plist>").unwrap();
+        let error = plist.copy().dict().unwrap().iter().next().unwrap().unwrap_err();
+        assert_eq!(error.key, b"caf\xe9");
+        assert_eq!(error.to_string(), "The key \"caf\u{fffd}\" is not valid UTF-8");
+        let (key, value) = plist.copy().dict().unwrap().iter().next_bytes().unwrap();
+        assert_eq!(key, b"caf\xe9");
+        assert_eq!(value.as_str(), None);
+        assert_eq!(value.as_str_lossy().unwrap(), "iPhone \u{fffd}");
+        assert_eq!(value.as_bytes().unwrap(), b"iPhone \xff");
+
+        assert!(matches!(plist.xml(), Err(PlistError::Format)));
+        #[cfg(libplist_2_3)]
+        assert!(matches!(plist.json(false), Err(PlistError::Format)));
+        let xml = plist.to_format(PlistFormat::Xml, false).unwrap();
+        assert!(xml.windows(2).any(|bytes| bytes == b"\xff<"));
+        assert_eq!(Plist::from_memory(&xml).unwrap(), plist);
+
+        assert_eq!(Plist::from("caf\u{e9}").as_bytes().unwrap(), "caf\u{e9}".as_bytes());
+        assert_eq!(Plist::from(1).as_bytes(), None);
+    }
+}
@@ -0,0 +1,73 @@
+#!/usr/bin/env python3
+"""Writes the conformance fixtures in this directory, each case as NAME.xml and NAME.bplist.
+
+The fixtures are serialized by Python's plistlib, which follows CoreFoundation's layout
+(UTF-16 for non-ASCII strings, the smallest integer widths, shared duplicate objects) but is
+not Apple's encoder. On macOS, `--plutil` re-encodes every XML fixture with
+`plutil -convert binary1`, so the binary fixtures come from CoreFoundation instead.
+
+The expected decoded values are spelled out in src/conformance.rs; keep both in sync.
+"""
+
+import datetime
+import pathlib
+import plistlib
+import subprocess
+import sys
+
+ROOT = pathlib.Path(__file__).resolve().parent
+
+CASES = {
+    "empty_containers": {"array": [], "dict": {}, "string": "", "data": b"", "nested": [[], {}]},
+    "integers": {
+        "zero": 0,
+        "one_byte": 255,
+        "two_bytes": 65535,
+        "four_bytes": 2**32 - 1,
+        "eight_bytes": 2**32,
+        "max_i64": 2**63 - 1,
+        "max_u64": 2**64 - 1,
+        "minus_one": -1,
+        "min_i64": -(2**63),
+    },
+    "unicode_keys": {"café": 1, "日本語": 2, "\U0001f600": 3, "": 4, "plain": 5},
+    "utf16_strings": {
+        "ascii": "plain",
+        "latin": "café",
+        "cjk": "日本語",
+        "emoji": "\U0001f4f1 phone",
+        "mixed": "naïve — ü",
+    },
+    "dates": {
+        "reference": datetime.datetime(2001, 1, 1),
+        "unix_epoch": datetime.datetime(1970, 1, 1),
+        "before_2001": datetime.datetime(1950, 6, 15, 12, 0, 0),
+        "after_2001": datetime.datetime(2024, 2, 29, 12, 30, 45),
+    },
+    "reals_and_ints": {
+        "int_one": 1,
+        "real_one": 1.0,
+        "real_zero": 0.0,
+        "half": 0.5,
+        "negative": -2.5,
+        "pi": 3.141592653589793,
+        "huge": 1e300,
+        "tiny": 5e-324,
+    },
+    "nested_data": {
+        "items": [{"blob": bytes(range(16))}, [b"\x00\xff", [b""]]],
+        "shared": [b"same", b"same", "same", "same"],
+    },
+}
+
+
+def main():
+    for name, value in CASES.items():
+        (ROOT / f"{name}.xml").write_bytes(plistlib.dumps(value, fmt=plistlib.FMT_XML))
+        (ROOT / f"{name}.bplist").write_bytes(plistlib.dumps(value, fmt=plistlib.FMT_BINARY))
+        if "--plutil" in sys.argv[1:]:
+            subprocess.run(["plutil", "-convert", "binary1", "-o", ROOT / f"{name}.bplist", ROOT / f"{name}.xml"], check=True)
+
+
+if __name__ == "__main__":
+    main()